@@ -0,0 +1,68 @@
+use crate::trace::TracePoint;
+
+/// How far a trace's positions are from another's at the same times.
+pub struct DistanceStats {
+    pub samples: usize,
+    pub mean: f64,
+    pub max: f64,
+}
+
+/// Distance from each reference point to `other` at the same moment, as
+/// `(time, distance)`, where `other`'s times shifted by `offset` cover it.
+/// `other` is `(time, position)` in time order and is interpolated linearly
+/// between its samples.
+pub fn distances(reference: &[TracePoint], other: &[(f64, [f32; 2])], offset: f64) -> Vec<(f64, f64)> {
+    let (Some(first), Some(last)) = (other.first(), other.last()) else { return Vec::new() };
+    reference.iter()
+        .filter(|p| p.time - offset >= first.0 && p.time - offset <= last.0)
+        .map(|p| {
+            let t = p.time - offset;
+            let i = other.partition_point(|(time, _)| *time < t);
+            let at = match i {
+                0 => other[0].1,
+                _ => {
+                    let ((t0, a), (t1, b)) = (other[i - 1], other[i]);
+                    let f = ((t - t0) / (t1 - t0)) as f32;
+                    [a[0] + (b[0] - a[0]) * f, a[1] + (b[1] - a[1]) * f]
+                }
+            };
+            let d = ((p.pos[0] - at[0]) as f64).hypot((p.pos[1] - at[1]) as f64);
+            (p.time, d)
+        })
+        .collect()
+}
+
+pub fn stats(series: &[(f64, f64)]) -> Option<DistanceStats> {
+    if series.is_empty() {
+        return None;
+    }
+    let mean = series.iter().map(|(_, d)| d).sum::<f64>() / series.len() as f64;
+    let max = series.iter().map(|(_, d)| *d).fold(0.0, f64::max);
+    Some(DistanceStats { samples: series.len(), mean, max })
+}
+
+/// The offset within `reach` seconds of `around` at which `other` lies
+/// closest to `reference` on average, tried every `step` seconds and then
+/// refined a tenth of that. Offsets where the traces overlap for less than
+/// half as many samples as they can are passed over, so a sliver of overlap
+/// can't win by luck.
+pub fn best_offset(reference: &[TracePoint], other: &[(f64, [f32; 2])], around: f64, reach: f64, step: f64) -> Option<f64> {
+    if step <= 0.0 {
+        return None;
+    }
+    let search = |from: f64, to: f64, step: f64| {
+        let n = ((to - from) / step).round() as i64;
+        let tried: Vec<(f64, DistanceStats)> = (0..=n)
+            .map(|k| from + k as f64 * step)
+            .filter_map(|offset| Some((offset, stats(&distances(reference, other, offset))?)))
+            .collect();
+        let most = tried.iter().map(|(_, s)| s.samples).max()?;
+        tried.into_iter()
+            .filter(|(_, s)| 2 * s.samples >= most)
+            .min_by(|(_, a), (_, b)| a.mean.total_cmp(&b.mean))
+            .map(|(offset, _)| offset)
+    };
+    let coarse = search(around - reach, around + reach, step)?;
+    let (from, to) = ((coarse - step).max(around - reach), (coarse + step).min(around + reach));
+    search(from, to, step / 10.0).or(Some(coarse))
+}
@@ -0,0 +1,76 @@
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::playlist;
+
+/// Audio is decoded at this rate, plenty to tell clicks apart.
+const SAMPLE_RATE: u32 = 8000;
+/// Waveform levels per second of audio.
+pub const WAVEFORM_RATE: u32 = 100;
+
+/// The RMS level of each 1/[`WAVEFORM_RATE`] s window of the first audio
+/// track, from 0 (silence) to 1 (full scale). Empty if the file has no
+/// audio; only failing to run ffmpeg at all is an error.
+pub fn extract_waveform(path: &Path) -> Result<Vec<f32>, String> {
+    let binary = if cfg!(windows) { "ffmpeg" } else { "./ffmpeg" };
+    let mut cmd = Command::new(binary);
+    cmd.args(["-loglevel", "error"]);
+    playlist::input(&mut cmd, path);
+    let mut child = cmd
+        // optional, so a file without audio just gives no samples
+        .args(["-map", "0:a:0?", "-vn", "-ac", "1", "-ar"])
+        .arg(SAMPLE_RATE.to_string())
+        .args(["-f", "s16le", "-"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Cannot run ffmpeg for the audio of {}: {}", path.display(), e))?;
+
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let window = (SAMPLE_RATE / WAVEFORM_RATE) as usize;
+    let mut buffer = vec![0u8; 2 * window];
+    let mut levels = Vec::new();
+    loop {
+        // fill a whole window unless the stream ends part way
+        let mut filled = 0;
+        while filled < buffer.len() {
+            match stdout.read(&mut buffer[filled..]) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => filled += n,
+            }
+        }
+        let samples = filled / 2;
+        if samples == 0 {
+            break;
+        }
+        let sum: f64 = buffer[..2 * samples].chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]) as f64 / 32768.0)
+            .map(|s| s * s)
+            .sum();
+        levels.push((sum / samples as f64).sqrt() as f32);
+        if filled < buffer.len() {
+            break;
+        }
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+    Ok(levels)
+}
+
+/// Indices of the local maxima of `levels` at or above `min_level`, at
+/// least `spacing` windows apart. Of equal neighbours the first is kept.
+pub fn peaks(levels: &[f32], min_level: f32, spacing: usize) -> Vec<usize> {
+    let mut peaks: Vec<usize> = Vec::new();
+    for (i, &level) in levels.iter().enumerate() {
+        if level < min_level {
+            continue;
+        }
+        let before = &levels[i.saturating_sub(spacing)..i];
+        let after = &levels[i + 1..(i + 1 + spacing).min(levels.len())];
+        if before.iter().all(|&l| l < level) && after.iter().all(|&l| l <= level) {
+            peaks.push(i);
+        }
+    }
+    peaks
+}
@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::detect::DetectionParams;
+use crate::trace::TracePoint;
+use crate::worker::ScanScope;
+
+/// Identifies a detection pass: which file, as it was on disk, under which
+/// settings. A cached pass is only valid for an identical key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheKey {
+    source: PathBuf,
+    /// Modification time in nanoseconds since the epoch, and size in bytes.
+    modified: u64,
+    len: u64,
+    /// Hash of the app version and everything that affects detection.
+    settings: String,
+}
+
+impl CacheKey {
+    pub fn new(source: &Path, params: &DetectionParams, scope: &ScanScope, range: Option<[f64; 2]>) -> io::Result<Self> {
+        let source = source.canonicalize()?;
+        let meta = fs::metadata(&source)?;
+        let modified = meta.modified()?.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+        let settings = json!({
+            "app_version": env!("CARGO_PKG_VERSION"),
+            "detection_params": params,
+            "scope": scope,
+            "time_range": range,
+        });
+        Ok(Self { source, modified, len: meta.len(), settings: hex(&Sha256::digest(settings.to_string())) })
+    }
+
+    /// One file per source video, so a new pass replaces whatever was
+    /// cached for it under older settings.
+    fn file_name(&self) -> String {
+        format!("{}.json", hex(&Sha256::digest(self.source.to_string_lossy().as_bytes())))
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Whether detection found the cursor on a frame it ran on.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct FrameResult {
+    pub time: f64,
+    pub detected: bool,
+}
+
+/// The outcome of one complete pass over a video, with everything the app
+/// keeps about the pass besides the points. Entries written before a field
+/// was added fail to read and are passed over like a mismatched key.
+#[derive(Serialize, Deserialize)]
+pub struct CachedRun {
+    pub key: CacheKey,
+    pub frames_seen: u64,
+    pub frames_scanned: u64,
+    pub points: Vec<TracePoint>,
+    /// First and last frame of the pass.
+    pub traversed: Option<[u64; 2]>,
+    /// Every frame detection ran on, misses included.
+    pub frame_results: BTreeMap<u64, FrameResult>,
+    /// Names of the recording segments the points are in, and the frames
+    /// scanned while each was recording.
+    pub segments: Vec<String>,
+    pub segment_scanned: Vec<u64>,
+}
+
+/// Completed detection passes kept on disk, so re-opening a video with the
+/// same settings doesn't mean decoding it all again.
+pub struct DetectionCache {
+    dir: PathBuf,
+}
+
+impl DetectionCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// The cached pass for `key`. Entries left from a different version of
+    /// the file or different settings don't match and are ignored.
+    pub fn load(&self, key: &CacheKey) -> Option<CachedRun> {
+        let file = File::open(self.dir.join(key.file_name())).ok()?;
+        let run: CachedRun = serde_json::from_reader(BufReader::new(file)).ok()?;
+        (run.key == *key).then_some(run)
+    }
+
+    pub fn store(&self, run: &CachedRun) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.dir).with_context(|| format!("creating {}", self.dir.display()))?;
+        let path = self.dir.join(run.key.file_name());
+        // written aside and renamed so an interrupted write can't leave a
+        // truncated entry behind
+        let partial = path.with_extension("partial");
+        let file = File::create(&partial).with_context(|| format!("creating {}", partial.display()))?;
+        let mut out = BufWriter::new(file);
+        serde_json::to_writer(&mut out, run)?;
+        out.flush()?;
+        drop(out);
+        fs::rename(&partial, &path).with_context(|| format!("writing {}", path.display()))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,264 @@
+use serde::{Deserialize, Serialize};
+
+use crate::trace::TracePoint;
+
+/// How much freedom the frame → playfield mapping has.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransformModel {
+    /// Uniform scale, rotation and translation.
+    Similarity,
+    /// Adds independent axis scales and shear.
+    Affine,
+    /// Full homography, for captures that aren't square-on to the screen.
+    Perspective,
+}
+
+impl TransformModel {
+    pub const ALL: [TransformModel; 3] = [TransformModel::Similarity, TransformModel::Affine, TransformModel::Perspective];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TransformModel::Similarity => "Similarity",
+            TransformModel::Affine => "Affine",
+            TransformModel::Perspective => "Perspective",
+        }
+    }
+
+    pub fn min_points(&self) -> usize {
+        match self {
+            TransformModel::Similarity => 2,
+            TransformModel::Affine => 3,
+            TransformModel::Perspective => 4,
+        }
+    }
+}
+
+/// A 3×3 homogeneous transform from frame pixels to playfield units.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Transform {
+    pub m: [[f64; 3]; 3],
+}
+
+impl Transform {
+    pub fn apply(&self, p: [f64; 2]) -> [f64; 2] {
+        let m = &self.m;
+        let w = m[2][0] * p[0] + m[2][1] * p[1] + m[2][2];
+        [
+            (m[0][0] * p[0] + m[0][1] * p[1] + m[0][2]) / w,
+            (m[1][0] * p[0] + m[1][1] * p[1] + m[1][2]) / w,
+        ]
+    }
+
+    pub fn apply_f32(&self, p: [f32; 2]) -> [f32; 2] {
+        let [x, y] = self.apply([p[0] as f64, p[1] as f64]);
+        [x as f32, y as f32]
+    }
+
+    /// Maps a trace into playfield space. Angles are carried through the
+    /// transform too, so they stay consistent under shear and perspective.
+    pub fn map_points(&self, points: &[TracePoint]) -> Vec<TracePoint> {
+        points
+            .iter()
+            .map(|p| {
+                let pos = self.apply_f32(p.pos);
+                let angle = p.angle.map(|a| {
+                    let (sin, cos) = a.to_radians().sin_cos();
+                    let ahead = self.apply_f32([p.pos[0] + cos, p.pos[1] + sin]);
+                    (ahead[1] - pos[1]).atan2(ahead[0] - pos[0]).to_degrees()
+                });
+                TracePoint { pos, angle, ..*p }
+            })
+            .collect()
+    }
+}
+
+/// A fitted transform together with how well it matches its reference points.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Calibration {
+    pub model: TransformModel,
+    pub transform: Transform,
+    /// Root-mean-square distance between mapped and entered points, in playfield units.
+    pub rms_error: f64,
+    pub max_error: f64,
+}
+
+/// Solves `a x = b` by Gaussian elimination with partial pivoting.
+fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let pivot_row = a[col].clone();
+        for row in col + 1..n {
+            let f = a[row][col] / pivot_row[col];
+            for (x, p) in a[row][col..].iter_mut().zip(&pivot_row[col..]) {
+                *x -= f * p;
+            }
+            b[row] -= f * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let s: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - s) / a[row][row];
+    }
+    Some(x)
+}
+
+/// Least-squares solution of the overdetermined system `rows · x = rhs`
+/// via the normal equations.
+fn least_squares(rows: &[Vec<f64>], rhs: &[f64]) -> Option<Vec<f64>> {
+    let n = rows.first()?.len();
+    let mut ata = vec![vec![0.0; n]; n];
+    let mut atb = vec![0.0; n];
+    for (row, r) in rows.iter().zip(rhs) {
+        for i in 0..n {
+            atb[i] += row[i] * r;
+            for j in 0..n {
+                ata[i][j] += row[i] * row[j];
+            }
+        }
+    }
+    solve(ata, atb)
+}
+
+/// Fits `model` to pairs of (frame point, playfield point).
+pub fn fit(model: TransformModel, pairs: &[([f64; 2], [f64; 2])]) -> Result<Calibration, String> {
+    if pairs.len() < model.min_points() {
+        return Err(format!("{} needs at least {} points", model.label(), model.min_points()));
+    }
+
+    let mut rows = Vec::new();
+    let mut rhs = Vec::new();
+    for &([x, y], [u, v]) in pairs {
+        match model {
+            // u = a x - b y + tx, v = b x + a y + ty
+            TransformModel::Similarity => {
+                rows.push(vec![x, -y, 1.0, 0.0]);
+                rhs.push(u);
+                rows.push(vec![y, x, 0.0, 1.0]);
+                rhs.push(v);
+            }
+            TransformModel::Affine => {
+                rows.push(vec![x, y, 1.0, 0.0, 0.0, 0.0]);
+                rhs.push(u);
+                rows.push(vec![0.0, 0.0, 0.0, x, y, 1.0]);
+                rhs.push(v);
+            }
+            // direct linear transform with h33 = 1
+            TransformModel::Perspective => {
+                rows.push(vec![x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y]);
+                rhs.push(u);
+                rows.push(vec![0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y]);
+                rhs.push(v);
+            }
+        }
+    }
+
+    let p = least_squares(&rows, &rhs).ok_or("reference points are degenerate (collinear or repeated)")?;
+    let m = match model {
+        TransformModel::Similarity => [[p[0], -p[1], p[2]], [p[1], p[0], p[3]], [0.0, 0.0, 1.0]],
+        TransformModel::Affine => [[p[0], p[1], p[2]], [p[3], p[4], p[5]], [0.0, 0.0, 1.0]],
+        TransformModel::Perspective => [[p[0], p[1], p[2]], [p[3], p[4], p[5]], [p[6], p[7], 1.0]],
+    };
+    let transform = Transform { m };
+
+    let errors: Vec<f64> = pairs
+        .iter()
+        .map(|&(frame, playfield)| {
+            let [u, v] = transform.apply(frame);
+            (u - playfield[0]).hypot(v - playfield[1])
+        })
+        .collect();
+    let rms_error = (errors.iter().map(|e| e * e).sum::<f64>() / errors.len() as f64).sqrt();
+    let max_error = errors.iter().copied().fold(0.0, f64::max);
+
+    if !rms_error.is_finite() {
+        return Err("calibration is numerically unstable".to_string());
+    }
+    Ok(Calibration { model, transform, rms_error, max_error })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: [[f64; 2]; 6] = [[10.0, 20.0], [630.0, 15.0], [600.0, 470.0], [25.0, 450.0], [320.0, 240.0], [150.0, 390.0]];
+
+    fn pairs(transform: &Transform, n: usize) -> Vec<([f64; 2], [f64; 2])> {
+        FRAME[..n].iter().map(|&p| (p, transform.apply(p))).collect()
+    }
+
+    fn assert_round_trip(model: TransformModel, known: Transform) {
+        for n in [model.min_points(), FRAME.len()] {
+            let calibration = fit(model, &pairs(&known, n)).unwrap();
+            assert!(calibration.rms_error < 1e-6, "{} from {} points: rms {}", model.label(), n, calibration.rms_error);
+            for p in [[0.0, 0.0], [333.0, 111.0], [640.0, 480.0]] {
+                let ([a, b], [c, d]) = (calibration.transform.apply(p), known.apply(p));
+                assert!((a - c).abs() < 1e-6 && (b - d).abs() < 1e-6, "{} at {:?}", model.label(), p);
+            }
+        }
+    }
+
+    #[test]
+    fn recovers_a_similarity() {
+        let (s, r) = (0.05, 0.3f64);
+        let (sin, cos) = r.sin_cos();
+        let known = Transform { m: [[s * cos, -s * sin, 3.0], [s * sin, s * cos, -7.0], [0.0, 0.0, 1.0]] };
+        assert_round_trip(TransformModel::Similarity, known);
+    }
+
+    #[test]
+    fn recovers_an_affine() {
+        let known = Transform { m: [[0.02, 0.004, 1.5], [-0.003, 0.03, 2.0], [0.0, 0.0, 1.0]] };
+        assert_round_trip(TransformModel::Affine, known);
+    }
+
+    #[test]
+    fn recovers_a_homography() {
+        let known = Transform { m: [[1.2, 0.1, 5.0], [0.05, 0.9, -3.0], [0.0004, 0.0002, 1.0]] };
+        assert_round_trip(TransformModel::Perspective, known);
+    }
+
+    #[test]
+    fn rejects_degenerate_points() {
+        let identity = Transform { m: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]] };
+        let collinear: Vec<_> = [[0.0, 0.0], [10.0, 10.0], [20.0, 20.0], [35.0, 35.0]].iter().map(|&p| (p, identity.apply(p))).collect();
+        let repeated = vec![([5.0, 5.0], [1.0, 1.0]); 4];
+        for model in [TransformModel::Affine, TransformModel::Perspective] {
+            assert!(fit(model, &collinear).is_err(), "{} on collinear points", model.label());
+        }
+        for model in TransformModel::ALL {
+            assert!(fit(model, &repeated).is_err(), "{} on repeated points", model.label());
+            assert!(fit(model, &repeated[..model.min_points() - 1]).is_err(), "{} on too few points", model.label());
+        }
+    }
+
+    #[test]
+    fn reports_rms_error() {
+        // two points pulled 1 unit apart more than a scale-only fit allows
+        let pairs = [([0.0, 0.0], [-0.5, 0.0]), ([10.0, 0.0], [10.5, 0.0]), ([0.0, 10.0], [-0.5, 10.0]), ([10.0, 10.0], [10.5, 10.0])];
+        let calibration = fit(TransformModel::Similarity, &pairs).unwrap();
+        // best similarity is uniform scale 1.05 about the centre, leaving
+        // 0.25 of the 0.5 along x and 0.25 along y at every point
+        assert!((calibration.rms_error - 0.25 * 2f64.sqrt()).abs() < 1e-9, "rms {}", calibration.rms_error);
+        assert!((calibration.max_error - calibration.rms_error).abs() < 1e-9);
+    }
+
+    #[test]
+    fn maps_points_and_angles() {
+        // a quarter turn and double scale, then a shift
+        let transform = Transform { m: [[0.0, -2.0, 100.0], [2.0, 0.0, 50.0], [0.0, 0.0, 1.0]] };
+        let points = [TracePoint::new(3, 0.1, [10.0, 5.0]).with_angle(Some(0.0)), TracePoint::new(4, 0.2, [0.0, 0.0])];
+        let mapped = transform.map_points(&points);
+        assert_eq!(mapped[0].pos, [90.0, 70.0]);
+        assert!((mapped[0].angle.unwrap() - 90.0).abs() < 1e-3);
+        assert_eq!((mapped[0].frame, mapped[0].time), (3, 0.1));
+        assert_eq!(mapped[1].pos, [100.0, 50.0]);
+        assert_eq!(mapped[1].angle, None);
+    }
+}
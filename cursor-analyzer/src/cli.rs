@@ -0,0 +1,279 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use crate::detect::{BitDepth, Channel, DetectionParams, PROFILES};
+use crate::export::ExportFormat;
+use crate::worker::ScanScope;
+
+pub const USAGE: &str = "\
+Usage:
+  cursor-analyzer [--fullscreen]
+  cursor-analyzer analyze <video> -o <trace> [options]
+  cursor-analyzer --batch <dir> --out <dir> [--recursive] [--jobs <n>] [options]
+  cursor-analyzer serve   JSON commands on stdin, JSON events on stdout
+
+Output options:
+  --format csv|json     trace format (default: json, or the -o extension)
+
+Batch options:
+  --jobs <n>            analyze up to n files at once (default: the number of
+                        logical CPU threads, so two per core with SMT; halve
+                        it to run one job per physical core). Each job runs
+                        one ffmpeg process and one detection thread, and reads
+                        the next frame only once the last is detected; there
+                        is no prefetch queue, so each job holds about one
+                        frame in memory
+
+Detection options:
+  --profile <name>      start from a named parameter set (default, dim, strict)
+  --channel <name>      threshold all, red, green, blue or luma (default all)
+  --depth 8|16          decode at 8 or 16 bits per channel (default 8)
+  --bright-min <0-255>  --dark-max <0-255>  --right-max <0-255>
+  --diag-min <0-255>    --diag-hits <0-7>
+  --right-probe         also require a dark outline right of the spine (off
+                        for arrows, whose body lies on that side)
+  --probe-len <px>      arrow probe length (default 13)
+  --margin <px>         bottom rows never scanned for a tip (default 20)
+  --reference-height <px>  scale probe length and margin from this frame height
+  --blur <0-4>          box blur radius before detection (default 0, off)
+  --blur-strength <0-100>  percentage of the blur mixed in (default 20)
+  --downsample <1-4>    search a coarser grid first, refine at full resolution (default 1, off)
+  --roi <x,y,w,h>       only detect inside this region (frame pixels)
+  --every-n <n>         only detect on every n-th frame
+  --video-stream <n>    decode the n-th video stream, from 0 (default: the one
+                        ffmpeg picks)
+  --start <s>           start decoding at this time (seconds)
+  --end <s>             stop decoding at this time (seconds)";
+
+pub enum Mode {
+    Gui { fullscreen: bool },
+    Analyze(AnalyzeArgs),
+    Batch(BatchArgs),
+    /// Newline-delimited JSON over stdin/stdout, see `rpc`.
+    Serve,
+}
+
+/// Detection settings shared by the headless modes.
+pub struct ScanArgs {
+    pub profile: String,
+    pub params: DetectionParams,
+    pub scope: ScanScope,
+    /// In and out points in seconds, `(0, inf)` for the whole video.
+    pub range: (f64, f64),
+    /// Video stream to decode, `None` for ffmpeg's choice.
+    pub stream: Option<usize>,
+}
+
+pub struct AnalyzeArgs {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub format: ExportFormat,
+    pub scan: ScanArgs,
+}
+
+pub struct BatchArgs {
+    pub input: PathBuf,
+    pub out: PathBuf,
+    pub recursive: bool,
+    /// Files analyzed at the same time.
+    pub jobs: usize,
+    pub format: ExportFormat,
+    pub scan: ScanArgs,
+}
+
+fn value(args: &mut impl Iterator<Item = OsString>, flag: &str) -> Result<OsString, String> {
+    args.next().ok_or_else(|| format!("{} needs a value", flag))
+}
+
+/// A value that isn't a path, so must be UTF-8.
+fn text(args: &mut impl Iterator<Item = OsString>, flag: &str) -> Result<String, String> {
+    value(args, flag)?.into_string().map_err(|v| format!("invalid value for {}: {}", flag, v.to_string_lossy()))
+}
+
+fn number<T: std::str::FromStr>(args: &mut impl Iterator<Item = OsString>, flag: &str) -> Result<T, String> {
+    let v = text(args, flag)?;
+    v.parse().map_err(|_| format!("invalid value for {}: {}", flag, v))
+}
+
+fn apply_override(params: &mut DetectionParams, flag: &str, v: u32) -> Result<(), String> {
+    let byte = || u8::try_from(v).map_err(|_| format!("{} must be between 0 and 255", flag));
+    match flag {
+        "--bright-min" => params.bright_min = byte()?,
+        "--dark-max" => params.dark_max = byte()?,
+        "--right-max" => params.right_max = byte()?,
+        "--diag-min" => params.diag_min = byte()?,
+        "--diag-hits" => params.diag_hits = v,
+        "--probe-len" => params.probe_len = v,
+        "--margin" => params.margin = v,
+        "--reference-height" => params.reference_height = v,
+        "--blur" if v > 4 => return Err("--blur must be between 0 and 4".to_string()),
+        "--blur" => params.blur_radius = v,
+        "--blur-strength" if v > 100 => return Err("--blur-strength must be between 0 and 100".to_string()),
+        "--blur-strength" => params.blur_strength = v as f32 / 100.0,
+        "--downsample" if !(1..=4).contains(&v) => return Err("--downsample must be between 1 and 4".to_string()),
+        "--downsample" => params.downsample = v,
+        _ => unreachable!("not a detection flag: {}", flag),
+    }
+    Ok(())
+}
+
+fn parse_format(v: &str) -> Result<ExportFormat, String> {
+    match v {
+        "csv" => Ok(ExportFormat::Csv),
+        "json" => Ok(ExportFormat::Json),
+        other => Err(format!("unknown format: {}", other)),
+    }
+}
+
+/// Paths are taken as given by the OS, so they needn't be valid UTF-8.
+pub fn parse(args: impl Iterator<Item = OsString>) -> Result<Mode, String> {
+    let mut args = args.peekable();
+    if args.next_if(|a| a == "serve").is_some() {
+        return match args.next() {
+            None => Ok(Mode::Serve),
+            Some(other) => Err(format!("serve takes no arguments, got: {}", other.to_string_lossy())),
+        };
+    }
+    let analyze = args.next_if(|a| a == "analyze").is_some();
+
+    let mut fullscreen = false;
+    let mut positional = Vec::new();
+    let mut output = None;
+    let mut batch = None;
+    let mut out = None;
+    let mut recursive = false;
+    let mut jobs = None;
+    let mut format = None;
+    let mut profile = "default".to_string();
+    let mut scope = ScanScope::default();
+    let mut range = (0.0, f64::INFINITY);
+    let mut stream = None;
+    let mut channel: Option<Channel> = None;
+    let mut depth: Option<BitDepth> = None;
+    let mut right_probe = false;
+    // individual thresholds are applied on top of the profile, whatever the flag order
+    let mut overrides: Vec<(String, u32)> = Vec::new();
+
+    while let Some(arg) = args.next() {
+        let Some(flag) = arg.to_str() else {
+            // flags are ASCII, so this can only be a path
+            if analyze {
+                positional.push(PathBuf::from(arg));
+                continue;
+            }
+            return Err(format!("unknown argument: {}", arg.to_string_lossy()));
+        };
+        match flag {
+            "--fullscreen" => fullscreen = true,
+            "-o" | "--output" => output = Some(PathBuf::from(value(&mut args, flag)?)),
+            "--batch" => batch = Some(PathBuf::from(value(&mut args, flag)?)),
+            "--out" => out = Some(PathBuf::from(value(&mut args, flag)?)),
+            "--recursive" => recursive = true,
+            "--jobs" => {
+                let n: usize = number(&mut args, flag)?;
+                if n == 0 {
+                    return Err("--jobs must be at least 1".to_string());
+                }
+                jobs = Some(n);
+            }
+            "--right-probe" => right_probe = true,
+            "--format" => format = Some(parse_format(&text(&mut args, flag)?)?),
+            "--profile" => profile = text(&mut args, flag)?,
+            "--channel" => channel = Some(text(&mut args, flag)?.parse()?),
+            "--depth" => depth = Some(text(&mut args, flag)?.parse()?),
+            "--roi" => scope.roi = Some(text(&mut args, flag)?.parse()?),
+            "--every-n" => {
+                scope.every_n = number(&mut args, flag)?;
+                if scope.every_n == 0 {
+                    return Err("--every-n must be at least 1".to_string());
+                }
+            }
+            "--video-stream" => stream = Some(number(&mut args, flag)?),
+            "--start" => range.0 = number(&mut args, flag)?,
+            "--end" => range.1 = number(&mut args, flag)?,
+            "--bright-min" | "--dark-max" | "--right-max" | "--diag-min" | "--diag-hits"
+            | "--probe-len" | "--margin" | "--reference-height" | "--blur" | "--blur-strength" | "--downsample" => {
+                let v = number(&mut args, flag)?;
+                overrides.push((flag.to_string(), v));
+            }
+            "-h" | "--help" => return Err(String::new()),
+            other if analyze && !other.starts_with('-') => positional.push(PathBuf::from(other)),
+            other => return Err(format!("unknown argument: {}", other)),
+        }
+    }
+
+    if !analyze && batch.is_none() {
+        return Ok(Mode::Gui { fullscreen });
+    }
+
+    let mut params = DetectionParams::profile(&profile)
+        .ok_or_else(|| format!("unknown profile '{}', expected one of: {}", profile, PROFILES.join(", ")))?;
+    for (flag, v) in overrides {
+        apply_override(&mut params, &flag, v)?;
+    }
+    if let Some(channel) = channel {
+        params.channel = channel;
+    }
+    if let Some(depth) = depth {
+        params.depth = depth;
+    }
+    params.right_probe |= right_probe;
+    if !(range.0 >= 0.0 && range.1 > range.0) {
+        return Err("--end must be after --start, and --start not negative".to_string());
+    }
+    let scan = ScanArgs { profile, params, scope, range, stream };
+
+    if analyze {
+        if jobs.is_some() {
+            return Err("--jobs only applies to --batch".to_string());
+        }
+        let [input]: [PathBuf; 1] = positional.try_into()
+            .map_err(|_| "analyze needs exactly one input video".to_string())?;
+        let output = output.ok_or("analyze needs -o <trace>")?;
+        let format = match format {
+            Some(f) => f,
+            None if output.extension().is_some_and(|e| e.eq_ignore_ascii_case("csv")) => ExportFormat::Csv,
+            None => ExportFormat::Json,
+        };
+        return Ok(Mode::Analyze(AnalyzeArgs { input, output, format, scan }));
+    }
+
+    let input = batch.unwrap();
+    let out = out.ok_or("--batch needs --out <dir>")?;
+    let format = format.unwrap_or(ExportFormat::Json);
+    let jobs = jobs.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+    Ok(Mode::Batch(BatchArgs { input, out, recursive, jobs, format, scan }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_paths_that_are_not_utf8() {
+        use std::os::unix::ffi::OsStringExt;
+
+        // "café.mp4" and "sortie" with a Latin-1 é, which isn't valid UTF-8
+        let video = OsString::from_vec(b"/tmp/caf\xe9.mp4".to_vec());
+        let dir = OsString::from_vec(b"/tmp/sorti\xe9".to_vec());
+        let trace = OsString::from_vec(b"/tmp/caf\xe9.csv".to_vec());
+
+        let analyze = vec!["analyze".into(), video.clone(), "-o".into(), trace.clone()];
+        match parse(analyze.into_iter()) {
+            Ok(Mode::Analyze(a)) => {
+                assert_eq!(a.input.as_os_str(), video);
+                assert_eq!(a.output.as_os_str(), trace);
+            }
+            _ => panic!("analyze didn't parse"),
+        }
+        let batch = vec!["--batch".into(), dir.clone(), "--out".into(), dir.clone()];
+        match parse(batch.into_iter()) {
+            Ok(Mode::Batch(b)) => {
+                assert_eq!(b.input.as_os_str(), dir);
+                assert_eq!(b.out.as_os_str(), dir);
+            }
+            _ => panic!("--batch didn't parse"),
+        }
+    }
+}
@@ -0,0 +1,711 @@
+use serde::{Deserialize, Serialize};
+
+/// A colour value of a decoded frame, 8 or 16 bits per channel. Frames are
+/// RGBA, four samples per pixel, whatever the depth.
+pub trait Sample: Copy + Ord + Into<u32> {
+    /// The thresholds in [`DetectionParams`] are on the 8-bit scale; this
+    /// maps one onto the sample's range, 255 to full scale.
+    fn from_8bit(v: u8) -> Self;
+    /// Narrows a value known to be in range, e.g. a weighted mean of samples.
+    fn narrow(v: u32) -> Self;
+}
+
+impl Sample for u8 {
+    fn from_8bit(v: u8) -> Self {
+        v
+    }
+
+    fn narrow(v: u32) -> Self {
+        v as u8
+    }
+}
+
+impl Sample for u16 {
+    fn from_8bit(v: u8) -> Self {
+        v as u16 * 257
+    }
+
+    fn narrow(v: u32) -> Self {
+        v as u16
+    }
+}
+
+/// Bits per channel ffmpeg decodes frames to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum BitDepth {
+    /// `rgba`, enough for most screen captures and the fastest.
+    #[default]
+    #[serde(rename = "8")]
+    Eight,
+    /// `rgba64le`, keeps the precision of 10-bit and HDR sources so bright
+    /// whites aren't clipped together before thresholding.
+    #[serde(rename = "16")]
+    Sixteen,
+}
+
+impl BitDepth {
+    pub fn label(&self) -> &'static str {
+        match self {
+            BitDepth::Eight => "8-bit",
+            BitDepth::Sixteen => "16-bit",
+        }
+    }
+
+    pub fn pix_fmt(&self) -> &'static str {
+        match self {
+            BitDepth::Eight => "rgba",
+            BitDepth::Sixteen => "rgba64le",
+        }
+    }
+
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            BitDepth::Eight => 4,
+            BitDepth::Sixteen => 8,
+        }
+    }
+
+}
+
+impl std::str::FromStr for BitDepth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "8" => Ok(BitDepth::Eight),
+            "16" => Ok(BitDepth::Sixteen),
+            other => Err(format!("unknown bit depth '{}', expected 8 or 16", other)),
+        }
+    }
+}
+
+/// Which part of a pixel the thresholds in [`DetectionParams`] compare against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    /// Every channel must pass, i.e. a white-ish cursor.
+    #[default]
+    All,
+    Red,
+    Green,
+    Blue,
+    /// Rec. 601 luma, 0.299 R + 0.587 G + 0.114 B.
+    Luma,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 5] = [Channel::All, Channel::Red, Channel::Green, Channel::Blue, Channel::Luma];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Channel::All => "All (RGB)",
+            Channel::Red => "Red",
+            Channel::Green => "Green",
+            Channel::Blue => "Blue",
+            Channel::Luma => "Luma",
+        }
+    }
+
+    fn single<S: Sample>(&self, px: &[S]) -> S {
+        match self {
+            Channel::All => unreachable!("all channels have no single value"),
+            Channel::Red => px[0],
+            Channel::Green => px[1],
+            Channel::Blue => px[2],
+            Channel::Luma => S::narrow((299 * px[0].into() + 587 * px[1].into() + 114 * px[2].into() + 500) / 1000),
+        }
+    }
+
+    /// The value a "brighter than" threshold has to beat: the dimmest
+    /// channel, so that every channel passes.
+    fn low<S: Sample>(&self, px: &[S]) -> S {
+        match self {
+            Channel::All => px[0].min(px[1]).min(px[2]),
+            _ => self.single(px),
+        }
+    }
+
+    /// The value a "darker than" threshold has to beat: the brightest channel.
+    fn high<S: Sample>(&self, px: &[S]) -> S {
+        match self {
+            Channel::All => px[0].max(px[1]).max(px[2]),
+            _ => self.single(px),
+        }
+    }
+}
+
+impl std::str::FromStr for Channel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(Channel::All),
+            "red" => Ok(Channel::Red),
+            "green" => Ok(Channel::Green),
+            "blue" => Ok(Channel::Blue),
+            "luma" => Ok(Channel::Luma),
+            other => Err(format!("unknown channel '{}', expected all, red, green, blue or luma", other)),
+        }
+    }
+}
+
+/// Thresholds and probe geometry for the white-arrow heuristic in
+/// [`find_position`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DetectionParams {
+    /// What the thresholds below compare against; "every channel" in their
+    /// descriptions means the selected one when it is not [`Channel::All`].
+    pub channel: Channel,
+    /// Minimum value of every channel for the tip and spine pixels.
+    pub bright_min: u8,
+    /// Every channel of the outline left of the spine must be below this.
+    pub dark_max: u8,
+    /// Every channel of the pixel right of the tip must be below this.
+    pub right_max: u8,
+    /// Also require the column right of the spine to be dark all the way
+    /// down, below `dark_max` like the outline on the left. Keeps the match
+    /// off the left edge of wide bright areas, but rejects arrow cursors,
+    /// whose body lies right of the spine.
+    pub right_probe: bool,
+    /// A diagonal pixel counts as part of the arrow above this value...
+    pub diag_min: u8,
+    /// ...and at least this many of the 7 diagonal pixels must.
+    pub diag_hits: u32,
+    /// Length in pixels of the spine probe below the tip and of the outline
+    /// probe left of it. The 7 diagonal samples spread over the same length.
+    pub probe_len: u32,
+    /// Rows at the bottom of the frame never scanned for a tip.
+    pub margin: u32,
+    /// Frame height `probe_len` and `margin` were chosen for. When non-zero
+    /// both scale with the actual frame height, so one setting covers every
+    /// capture resolution.
+    pub reference_height: u32,
+    /// Box blur radius applied to the frame before detection, 0 for none.
+    /// Suppresses single-pixel compression noise on low-bitrate footage.
+    pub blur_radius: u32,
+    /// How much of the blurred frame is mixed into the original, 0 to 1.
+    /// Up to 0.2 the tip is still found on the same pixel at any radius;
+    /// stronger mixes dim the one-pixel tip below `bright_min` and lose it.
+    pub blur_strength: f32,
+    /// Depth frames are decoded at. The thresholds above stay on the 8-bit
+    /// scale and are scaled up for 16-bit frames.
+    pub depth: BitDepth,
+    /// Look for bright pixels on a grid this many pixels apart first and
+    /// only run the full test around them, 1 for off. Several times faster
+    /// on large frames; an arrow narrower than the grid can be missed.
+    pub downsample: u32,
+}
+
+impl Default for DetectionParams {
+    fn default() -> Self {
+        Self {
+            channel: Channel::All,
+            bright_min: 210,
+            dark_max: 90,
+            right_max: 130,
+            right_probe: false,
+            diag_min: 160,
+            diag_hits: 4,
+            probe_len: 13,
+            margin: 20,
+            reference_height: 0,
+            blur_radius: 0,
+            blur_strength: 0.2,
+            depth: BitDepth::Eight,
+            downsample: 1,
+        }
+    }
+}
+
+/// Built-in named parameter sets, selectable from the command line.
+pub const PROFILES: [&str; 3] = ["default", "dim", "strict"];
+
+impl DetectionParams {
+    /// Probe length and bottom margin in pixels for a frame `height` tall.
+    pub fn probe_geometry(&self, height: usize) -> (usize, usize) {
+        let scale = if self.reference_height > 0 { height as f64 / self.reference_height as f64 } else { 1.0 };
+        let probe = ((self.probe_len as f64 * scale).round() as usize).max(2);
+        let margin = (self.margin as f64 * scale).round() as usize;
+        // the spine and outline probes cover the tip row and `probe - 1`
+        // below it, and the diagonal no more; that far and no further from
+        // the last row the tip can still be tested
+        (probe, margin.max(probe - 1))
+    }
+
+    pub fn profile(name: &str) -> Option<Self> {
+        let default = Self::default();
+        match name {
+            "default" => Some(default),
+            // washed-out or low-bitrate footage where the arrow never gets fully white
+            "dim" => Some(Self { bright_min: 180, diag_min: 140, ..default }),
+            // clean captures with lots of other white UI elements
+            "strict" => Some(Self { bright_min: 230, dark_max: 60, right_max: 110, diag_min: 180, diag_hits: 5, ..default }),
+            _ => None,
+        }
+    }
+}
+
+/// Region of interest in frame pixels; detection only considers tips inside it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Roi {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+impl Roi {
+    /// The region both cover, `None` if they don't overlap.
+    fn intersect(&self, other: &Roi) -> Option<Roi> {
+        let (x, y) = (self.x.max(other.x), self.y.max(other.y));
+        let right = self.x.saturating_add(self.w).min(other.x.saturating_add(other.w));
+        let bottom = self.y.saturating_add(self.h).min(other.y.saturating_add(other.h));
+        (right > x && bottom > y).then(|| Roi { x, y, w: right - x, h: bottom - y })
+    }
+}
+
+impl std::str::FromStr for Roi {
+    type Err = String;
+
+    /// Parses `x,y,w,h`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<u32> = s.split(',')
+            .map(|p| p.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("invalid ROI '{}', expected x,y,w,h", s))?;
+        match parts[..] {
+            [x, y, w, h] if w > 0 && h > 0 => Ok(Roi { x, y, w, h }),
+            _ => Err(format!("invalid ROI '{}', expected x,y,w,h with non-zero size", s)),
+        }
+    }
+}
+
+/// Mixes every pixel with the mean of its `(2r+1)²` neighbourhood as
+/// `(1 - strength) * pixel + strength * mean`, clamping at the frame
+/// edges. Alpha is copied through. Done as two separable passes.
+pub fn smooth<S: Sample>(data: &[S], width: usize, height: usize, radius: usize, strength: f32) -> Vec<S> {
+    let r = radius as isize;
+    let n = (2 * radius + 1) as f32;
+    let clamp = |v: isize, max: usize| v.clamp(0, max as isize - 1) as usize;
+
+    let mut rows = vec![0f32; width * height * 3];
+    for y in 0..height {
+        for x in 0..width {
+            for c in 0..3 {
+                let sum: f32 = (-r..=r).map(|d| data[(y * width + clamp(x as isize + d, width)) * 4 + c].into() as f32).sum();
+                rows[(y * width + x) * 3 + c] = sum / n;
+            }
+        }
+    }
+
+    let mut out = data.to_vec();
+    for y in 0..height {
+        for x in 0..width {
+            for c in 0..3 {
+                let sum: f32 = (-r..=r).map(|d| rows[(clamp(y as isize + d, height) * width + x) * 3 + c]).sum();
+                let i = (y * width + x) * 4 + c;
+                out[i] = S::narrow(((1.0 - strength) * data[i].into() as f32 + strength * sum / n).round() as u32);
+            }
+        }
+    }
+    out
+}
+
+/// Which test of the arrow heuristic turned down a bright candidate pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rejection {
+    /// The pixel right of the tip isn't dark enough.
+    Right,
+    /// The vertical probe below the tip isn't bright all the way.
+    Spine,
+    /// The outline probe left of the spine isn't dark all the way.
+    Left,
+    /// The probe right of the spine isn't dark all the way.
+    RightEdge,
+    /// Too few diagonal samples are bright.
+    Diagonal,
+}
+
+/// How many candidate tips one frame had and where each was turned down,
+/// to tell which threshold or probe length is filtering out a cursor.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Rejections {
+    /// Pixels at least `bright_min` inside the scanned area.
+    pub candidates: u32,
+    pub right: u32,
+    pub spine: u32,
+    pub left: u32,
+    pub right_edge: u32,
+    pub diagonal: u32,
+    /// Candidates that passed every test; detection reports the first.
+    pub accepted: u32,
+}
+
+/// The scan shared by [`find_position`] and [`diagnose`]: calls `visit`
+/// with each bright candidate in scan order and the outcome of its tests,
+/// until `visit` returns false.
+fn scan<S: Sample>(data: &[S], width: usize, height: usize, params: &DetectionParams, roi: Option<&Roi>, mut visit: impl FnMut(usize, usize, Result<(), Rejection>) -> bool) {
+    let stride = width * 4;
+    let lim_max = S::from_8bit(params.bright_min);
+    let lim_min = S::from_8bit(params.dark_max);
+    let lim_k_min = S::from_8bit(params.right_max);
+    let diag_min = S::from_8bit(params.diag_min);
+    let (probe, margin) = params.probe_geometry(height);
+    let diag: Vec<usize> = (1..8).map(|k| ((k * probe) as f64 / 13.0).round().max(1.0) as usize).collect();
+
+    let mut scan_limit_y = height.saturating_sub(margin);
+    let mut scan_limit_x = width.saturating_sub(1);
+    let (mut start_y, mut start_x) = (0, 1);
+    if let Some(roi) = roi {
+        start_x = start_x.max(roi.x as usize);
+        start_y = roi.y as usize;
+        // saturating, a region reaching past u32 just runs to the frame's edge
+        scan_limit_x = scan_limit_x.min(roi.x.saturating_add(roi.w) as usize);
+        scan_limit_y = scan_limit_y.min(roi.y.saturating_add(roi.h) as usize);
+    }
+
+    let channel = params.channel;
+    let low = |i: usize| channel.low(&data[i..i + 3]);
+    let high = |i: usize| channel.high(&data[i..i + 3]);
+
+    let test = |x: usize, i: usize| {
+        if high(i + 4) >= lim_k_min {
+            return Err(Rejection::Right);
+        }
+        if (1..probe).any(|j| low(i + j * stride) <= lim_max) {
+            return Err(Rejection::Spine);
+        }
+        if (0..probe).any(|j| high(i + j * stride - 4) >= lim_min) {
+            return Err(Rejection::Left);
+        }
+        // the pixel beside the tip had its own test above
+        if params.right_probe && (1..probe).any(|j| high(i + j * stride + 4) >= lim_min) {
+            return Err(Rejection::RightEdge);
+        }
+        // samples past the right edge would wrap into the next row; a cursor
+        // clipped by the edge just scores fewer hits
+        let diag_score = diag.iter().filter(|&&d| x + d < width && high(i + d * stride + d * 4) > diag_min).count();
+        if (diag_score as u32) < params.diag_hits {
+            return Err(Rejection::Diagonal);
+        }
+        Ok(())
+    };
+
+    for y in start_y..scan_limit_y {
+        for x in start_x..scan_limit_x {
+            let i = (y * width + x) * 4;
+            if low(i) < lim_max {
+                continue;
+            }
+            if !visit(x, y, test(x, i)) {
+                return;
+            }
+        }
+    }
+}
+
+/// The arrow tip in full-resolution frame pixels. With
+/// [`DetectionParams::downsample`] above 1, only a grid of every n-th pixel
+/// of every n-th row is searched for bright pixels first, and the heuristic
+/// runs at full resolution only in a window around each, where the tip of
+/// an arrow whose body that pixel is would lie.
+pub fn find_position<S: Sample>(data: &[S], width: usize, height: usize, params: &DetectionParams, roi: Option<&Roi>) -> Option<[f32; 2]> {
+    let f = params.downsample.max(1) as usize;
+    if f == 1 {
+        return find_first(data, width, height, params, roi);
+    }
+
+    let (probe, _) = params.probe_geometry(height);
+    let bright_min = S::from_8bit(params.bright_min);
+    let area = roi.copied().unwrap_or(Roi { x: 0, y: 0, w: width as u32, h: height as u32 });
+    let (x0, y0) = (area.x as usize, area.y as usize);
+    let x1 = (area.x.saturating_add(area.w) as usize).min(width);
+    // all the way down: an arrow whose tip is just above the margin has its
+    // body below it, and find_first keeps the margin itself
+    let y1 = (area.y.saturating_add(area.h) as usize).min(height);
+
+    // grid cells already covered by a refinement window
+    let cols = width.div_ceil(f);
+    let mut covered = vec![false; cols * height.div_ceil(f)];
+    for y in (y0..y1).step_by(f) {
+        for x in (x0..x1).step_by(f) {
+            let i = (y * width + x) * 4;
+            if covered[(y / f) * cols + x / f] || params.channel.low(&data[i..i + 3]) < bright_min {
+                continue;
+            }
+            // one window serves every grid pixel in the block below and right
+            // of this one, which the scan would reach next; it has to reach
+            // an arrow's width left and its height above any of them
+            let block = 2 * probe;
+            for cy in y / f..((y + block) / f).min(covered.len() / cols) {
+                for cx in x / f..((x + block) / f).min(cols) {
+                    covered[cy * cols + cx] = true;
+                }
+            }
+            let left = x.saturating_sub(probe + f);
+            let top = y.saturating_sub(2 * probe + f);
+            let (right, bottom) = ((x + block + f).min(width), (y + block + f).min(height));
+            let window = Roi { x: left as u32, y: top as u32, w: (right - left) as u32, h: (bottom - top) as u32 };
+            let Some(window) = window.intersect(&area) else { continue };
+            if let Some(tip) = find_first(data, width, height, params, Some(&window)) {
+                return Some(tip);
+            }
+        }
+    }
+    None
+}
+
+fn find_first<S: Sample>(data: &[S], width: usize, height: usize, params: &DetectionParams, roi: Option<&Roi>) -> Option<[f32; 2]> {
+    let mut found = None;
+    scan(data, width, height, params, roi, |x, y, outcome| {
+        if outcome.is_ok() {
+            found = Some([x as f32, y as f32]);
+        }
+        found.is_none()
+    });
+    found
+}
+
+/// Runs the whole scan without stopping at the first match, counting where
+/// each candidate was rejected.
+pub fn diagnose<S: Sample>(data: &[S], width: usize, height: usize, params: &DetectionParams, roi: Option<&Roi>) -> Rejections {
+    let mut counts = Rejections::default();
+    scan(data, width, height, params, roi, |_, _, outcome| {
+        counts.candidates += 1;
+        match outcome {
+            Ok(()) => counts.accepted += 1,
+            Err(Rejection::Right) => counts.right += 1,
+            Err(Rejection::Spine) => counts.spine += 1,
+            Err(Rejection::Left) => counts.left += 1,
+            Err(Rejection::RightEdge) => counts.right_edge += 1,
+            Err(Rejection::Diagonal) => counts.diagonal += 1,
+        }
+        true
+    });
+    counts
+}
+
+
+/// Only pixels this close to the tip are considered part of the cursor.
+const ORIENTATION_RADIUS: i64 = 32;
+
+/// Estimates which way the cursor at `tip` points, in degrees in image
+/// coordinates (0° = right, 90° = down). Uses the principal axis of the
+/// bright pixels connected to the tip, oriented from their centroid
+/// towards the tip. `None` if the blob is too small to tell.
+pub fn estimate_orientation<S: Sample>(data: &[S], width: usize, height: usize, tip: [f32; 2], params: &DetectionParams) -> Option<f32> {
+    let (tx, ty) = (tip[0] as i64, tip[1] as i64);
+    let bright_min = S::from_8bit(params.bright_min);
+    let bright = |x: i64, y: i64| {
+        let i = (y as usize * width + x as usize) * 4;
+        params.channel.low(&data[i..i + 3]) >= bright_min
+    };
+
+    let side = (2 * ORIENTATION_RADIUS + 1) as usize;
+    let mut visited = vec![false; side * side];
+    let local = |x: i64, y: i64| ((y - ty + ORIENTATION_RADIUS) as usize) * side + (x - tx + ORIENTATION_RADIUS) as usize;
+
+    let mut stack = vec![(tx, ty)];
+    visited[local(tx, ty)] = true;
+    let mut pixels = Vec::new();
+
+    while let Some((x, y)) = stack.pop() {
+        pixels.push((x as f64, y as f64));
+        for (nx, ny) in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+            if nx < 0 || ny < 0 || nx >= width as i64 || ny >= height as i64 {
+                continue;
+            }
+            if (nx - tx).abs() > ORIENTATION_RADIUS || (ny - ty).abs() > ORIENTATION_RADIUS {
+                continue;
+            }
+            let l = local(nx, ny);
+            if !visited[l] && bright(nx, ny) {
+                visited[l] = true;
+                stack.push((nx, ny));
+            }
+        }
+    }
+
+    if pixels.len() < 5 {
+        return None;
+    }
+
+    let n = pixels.len() as f64;
+    let (cx, cy) = pixels.iter().fold((0.0, 0.0), |(sx, sy), (x, y)| (sx + x, sy + y));
+    let (cx, cy) = (cx / n, cy / n);
+    let (mut sxx, mut syy, mut sxy) = (0.0, 0.0, 0.0);
+    for (x, y) in &pixels {
+        let (dx, dy) = (x - cx, y - cy);
+        sxx += dx * dx;
+        syy += dy * dy;
+        sxy += dx * dy;
+    }
+
+    let theta = 0.5 * (2.0 * sxy).atan2(sxx - syy);
+    let (mut ax, mut ay) = (theta.cos(), theta.sin());
+    // the axis has no sign of its own; point it at the tip
+    if ax * (tx as f64 - cx) + ay * (ty as f64 - cy) < 0.0 {
+        ax = -ax;
+        ay = -ay;
+    }
+    Some(ay.atan2(ax).to_degrees() as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An opaque black frame.
+    fn frame(width: usize, height: usize) -> Vec<u8> {
+        filled(width, height, [0, 0, 0])
+    }
+
+    /// An opaque frame of one colour.
+    fn filled(width: usize, height: usize, [r, g, b]: [u8; 3]) -> Vec<u8> {
+        [r, g, b, 255].repeat(width * height)
+    }
+
+    /// A white arrow `size` rows tall with its tip at `(x, y)`: row `r`
+    /// covers the tip column and `r` to its right, cut off at the edges.
+    fn arrow(data: &mut [u8], width: usize, height: usize, tip: (usize, usize), size: usize) {
+        painted_arrow(data, width, height, tip, size, [255, 255, 255]);
+    }
+
+    /// [`arrow`] in another colour.
+    fn painted_arrow(data: &mut [u8], width: usize, height: usize, (x, y): (usize, usize), size: usize, rgb: [u8; 3]) {
+        for r in 0..size.min(height - y) {
+            for dx in 0..=r.min(width - 1 - x) {
+                let i = ((y + r) * width + x + dx) * 4;
+                data[i..i + 3].copy_from_slice(&rgb);
+            }
+        }
+    }
+
+    /// The same frame decoded at 16 bits.
+    fn widened(data: &[u8]) -> Vec<u16> {
+        data.iter().map(|&v| u16::from_8bit(v)).collect()
+    }
+
+    #[test]
+    fn probe_length_matches_cursor_size() {
+        let (w, h) = (64, 64);
+        for size in [8, 16] {
+            let mut data = frame(w, h);
+            arrow(&mut data, w, h, (20, 10), size);
+            let params = DetectionParams { probe_len: size as u32, ..Default::default() };
+            assert_eq!(find_position(&data, w, h, &params, None), Some([20.0, 10.0]), "{} px arrow", size);
+        }
+
+        // a probe longer than the arrow runs off its spine
+        let mut data = frame(w, h);
+        arrow(&mut data, w, h, (20, 10), 8);
+        let params = DetectionParams { probe_len: 16, ..Default::default() };
+        assert_eq!(find_position(&data, w, h, &params, None), None);
+    }
+
+    #[test]
+    fn probe_geometry_scales_with_height() {
+        let params = DetectionParams { probe_len: 8, margin: 10, reference_height: 540, ..Default::default() };
+        assert_eq!(params.probe_geometry(540), (8, 10));
+        assert_eq!(params.probe_geometry(1080), (16, 20));
+
+        // set up for 8 px arrows at 540 rows, a 16 px one at 1080 is found
+        let (w, h) = (64, 1080);
+        let mut data = frame(w, h);
+        arrow(&mut data, w, h, (20, 10), 16);
+        assert_eq!(find_position(&data, w, h, &params, None), Some([20.0, 10.0]));
+    }
+
+    #[test]
+    fn tips_one_probe_from_each_edge() {
+        let params = DetectionParams { margin: 0, ..Default::default() };
+        let (probe, _) = params.probe_geometry(40);
+        for (w, h) in [(4000, 40), (40, 4000), (64, 64)] {
+            let (mid_x, mid_y) = ((w - probe) / 2, (h - probe) / 2);
+            // the outline is left of the tip, so column 0 can't hold one
+            for tip in [(1, mid_y), (mid_x, 0), (w - probe, mid_y), (mid_x, h - probe)] {
+                let mut data = frame(w, h);
+                arrow(&mut data, w, h, tip, probe);
+                let found = find_position(&data, w, h, &params, None);
+                assert_eq!(found, Some([tip.0 as f32, tip.1 as f32]), "{}x{} tip at {:?}", w, h, tip);
+            }
+        }
+    }
+
+    #[test]
+    fn downsampled_search_matches_full_resolution() {
+        let (w, h) = (640, 360);
+        let params = DetectionParams::default();
+        let (probe, margin) = params.probe_geometry(h);
+        // odd offsets, the edges, and a tip on the last row above the margin
+        let tips = [(1, 0), (101, 57), (320, 180), (333, 201), (w - probe, 40), (7, 250), (500, h - probe - 9), (250, h - margin - 1)];
+        for tip in tips {
+            let mut data = frame(w, h);
+            arrow(&mut data, w, h, tip, 17);
+            let full = find_position(&data, w, h, &params, None);
+            assert_eq!(full, Some([tip.0 as f32, tip.1 as f32]));
+            for downsample in 2..=4 {
+                let coarse = find_position(&data, w, h, &DetectionParams { downsample, ..params }, None);
+                assert_eq!(coarse, full, "tip at {:?}, downsample {}", tip, downsample);
+            }
+        }
+    }
+
+    #[test]
+    fn coloured_cursors_need_their_channel() {
+        let (w, h) = (64, 64);
+        let grey = [60, 60, 60];
+        let find = |data: &[u8], channel| {
+            let params = DetectionParams { channel, ..Default::default() };
+            (find_position(data, w, h, &params, None), find_position(&widened(data), w, h, &params, None))
+        };
+        let tip = Some([20.0, 10.0]);
+        for (rgb, channel) in [([40, 255, 40], Channel::Green), ([255, 30, 30], Channel::Red)] {
+            let mut data = filled(w, h, grey);
+            painted_arrow(&mut data, w, h, (20, 10), 17, rgb);
+            assert_eq!(find(&data, channel), (tip, tip), "{:?} arrow", channel);
+            // the other channels are dark, so the arrow isn't white
+            assert_eq!(find(&data, Channel::All), (None, None), "{:?} arrow", channel);
+        }
+
+        let mut data = filled(w, h, grey);
+        arrow(&mut data, w, h, (20, 10), 17);
+        for channel in Channel::ALL {
+            assert_eq!(find(&data, channel), (tip, tip), "white arrow, {:?}", channel);
+        }
+    }
+
+
+    /// The frame with an arrow of `size` rows, blurred, and the parameters
+    /// that find it unblurred.
+    fn blurred_arrow(size: usize, radius: usize, strength: f32) -> (Vec<u8>, DetectionParams) {
+        let (w, h) = (96, 96);
+        let mut data = frame(w, h);
+        arrow(&mut data, w, h, (30, 20), size);
+        let params = DetectionParams { probe_len: size as u32, ..Default::default() };
+        assert_eq!(find_position(&data, w, h, &params, None), Some([30.0, 20.0]));
+        (smooth(&data, w, h, radius, strength), params)
+    }
+
+    #[test]
+    fn light_blur_keeps_the_tip() {
+        for size in [16, 24] {
+            for radius in 1..=3 {
+                let (data, params) = blurred_arrow(size, radius, 0.2);
+                assert_eq!(find_position(&data, 96, 96, &params, None), Some([30.0, 20.0]), "{} px arrow, radius {}", size, radius);
+            }
+        }
+    }
+
+    #[test]
+    fn stronger_blur_loses_the_tip() {
+        for size in [16, 24] {
+            for radius in 1..=3 {
+                let (data, params) = blurred_arrow(size, radius, 0.3);
+                assert_eq!(find_position(&data, 96, 96, &params, None), None, "{} px arrow, radius {}", size, radius);
+            }
+        }
+    }
+
+}
@@ -0,0 +1,123 @@
+use crate::kinematics;
+use crate::trace::TracePoint;
+
+/// Sectors of the direction rose, each 360 / 16 = 22.5° wide.
+pub const ROSE_SECTORS: usize = 16;
+
+/// Thresholds for splitting a trace into movement segments and picking
+/// out sharp direction changes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DirectionParams {
+    /// Shortest segment in pixels. Samples closer together than this are
+    /// merged, so jitter while the cursor rests doesn't read as turning.
+    pub min_length: f32,
+    /// Smallest change of heading in degrees that counts as a turn.
+    pub turn_min: f32,
+}
+
+impl Default for DirectionParams {
+    fn default() -> Self {
+        Self { min_length: 10.0, turn_min: 60.0 }
+    }
+}
+
+/// A sharp change of direction at a trace point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TurningPoint {
+    /// Index into the trace.
+    pub index: usize,
+    pub time: f64,
+    pub pos: [f32; 2],
+    /// Signed change of heading in degrees, positive clockwise on screen.
+    pub turn: f32,
+}
+
+/// Heading in degrees from `a` to `b` in image coordinates (0° = right,
+/// 90° = down), the same convention as cursor angles.
+fn heading(a: [f32; 2], b: [f32; 2]) -> f32 {
+    (b[1] - a[1]).atan2(b[0] - a[0]).to_degrees()
+}
+
+type DirectionKey = (usize, Option<u64>, u32, u32);
+
+/// Movement segments of a trace: within each gap-free run, a segment ends
+/// at the first sample at least `min_length` from where it started.
+/// Summarised as a rose of distance travelled per direction and the
+/// points where the heading changes by at least `turn_min`.
+/// Only recomputed when the trace or the thresholds change.
+#[derive(Default)]
+pub struct DirectionAnalysis {
+    key: Option<DirectionKey>,
+    rose: [f64; ROSE_SECTORS],
+    turns: Vec<TurningPoint>,
+}
+
+impl DirectionAnalysis {
+    pub fn update(&mut self, points: &[TracePoint], params: DirectionParams) {
+        let key = (points.len(), points.last().map(|p| p.frame), params.min_length.to_bits(), params.turn_min.to_bits());
+        if self.key == Some(key) {
+            return;
+        }
+        self.key = Some(key);
+        self.rose = [0.0; ROSE_SECTORS];
+        self.turns.clear();
+
+        let sector = 360.0 / ROSE_SECTORS as f32;
+        for run in kinematics::contiguous_runs(points) {
+            // segment ends, as trace indices, and the heading after each
+            let mut vertices = vec![run.start];
+            let mut headings = Vec::new();
+            for i in run.start + 1..run.end {
+                let (a, b) = (points[vertices[vertices.len() - 1]].pos, points[i].pos);
+                let length = (b[0] - a[0]).hypot(b[1] - a[1]);
+                if length < params.min_length.max(f32::EPSILON) {
+                    continue;
+                }
+                let h = heading(a, b);
+                // sector 0 is centred on "right"
+                let s = ((h + sector / 2.0).rem_euclid(360.0) / sector) as usize % ROSE_SECTORS;
+                self.rose[s] += length as f64;
+                vertices.push(i);
+                headings.push(h);
+            }
+
+            // turn at each inner vertex, from the segment before to the one after
+            let turns: Vec<f32> = headings.windows(2).map(|h| (h[1] - h[0] + 180.0).rem_euclid(360.0) - 180.0).collect();
+            let mut m = 0;
+            while m < turns.len() {
+                // a corner between two vertices shows up as two smaller turns
+                // the same way, one at each; count them as one
+                let (mut turn, mut at) = (turns[m], m);
+                if turn.abs() < params.turn_min {
+                    match turns.get(m + 1) {
+                        Some(&next) if next.signum() == turn.signum() && (turn + next).abs() >= params.turn_min => {
+                            if next.abs() > turn.abs() {
+                                at = m + 1;
+                            }
+                            turn += next;
+                            m += 1;
+                        }
+                        _ => {
+                            m += 1;
+                            continue;
+                        }
+                    }
+                }
+                let index = vertices[at + 1];
+                let p = &points[index];
+                self.turns.push(TurningPoint { index, time: p.time, pos: p.pos, turn });
+                m += 1;
+            }
+        }
+    }
+
+    /// Distance travelled per direction sector, sector `i` centred on
+    /// `i * 22.5°`.
+    pub fn rose(&self) -> &[f64; ROSE_SECTORS] {
+        &self.rose
+    }
+
+    pub fn turns(&self) -> &[TurningPoint] {
+        &self.turns
+    }
+}
@@ -0,0 +1,426 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+
+use crate::calibration::Calibration;
+use crate::detect::DetectionParams;
+use crate::kinematics;
+use crate::trace::TracePoint;
+use crate::worker::ScanScope;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "CSV",
+            ExportFormat::Json => "JSON",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportContent {
+    Positions,
+    PositionsWithKinematics,
+    KinematicsOnly,
+}
+
+/// Where a trace came from and how it was produced. Embedded in every
+/// export so old files stay interpretable.
+#[derive(Clone)]
+pub struct Provenance {
+    pub source: Option<PathBuf>,
+    pub duration: f64,
+    pub size: [u32; 2],
+    pub fps: f64,
+    pub params: DetectionParams,
+    pub scope: ScanScope,
+    /// In and out points in seconds when only part of the video was decoded.
+    pub range: Option<[f64; 2]>,
+    /// Set when positions were mapped into playfield coordinates.
+    pub calibration: Option<Calibration>,
+    /// Positions are fractions of the frame width and height, see
+    /// [`normalize`].
+    pub normalized: bool,
+    /// Post-processing applied to the exported points, e.g. simplification.
+    pub processing: Vec<(&'static str, Value)>,
+}
+
+impl Provenance {
+    pub fn to_map(&self) -> Map<String, Value> {
+        let mut map = Map::new();
+        map.insert("app_version".into(), env!("CARGO_PKG_VERSION").into());
+        map.insert("exported_at".into(), utc_timestamp().into());
+        map.insert("source".into(), self.source.as_ref().map(|p| p.display().to_string()).into());
+        map.insert("source_duration".into(), self.duration.into());
+        map.insert("source_width".into(), self.size[0].into());
+        map.insert("source_height".into(), self.size[1].into());
+        map.insert("source_fps".into(), self.fps.into());
+        map.insert("detection_mode".into(), "arrow".into());
+        map.insert("detection_params".into(), json!(self.params));
+        map.insert("roi".into(), json!(self.scope.roi));
+        map.insert("every_n".into(), self.scope.every_n.into());
+        map.insert("time_range".into(), json!(self.range));
+        let space = match (&self.calibration, self.normalized) {
+            (Some(_), _) => "playfield",
+            (None, true) => "normalized",
+            (None, false) => "pixels",
+        };
+        map.insert("coordinate_space".into(), space.into());
+        if let Some(calibration) = &self.calibration {
+            map.insert("calibration".into(), json!(calibration));
+        }
+        for (key, value) in &self.processing {
+            map.insert(key.to_string(), value.clone());
+        }
+        map
+    }
+}
+
+/// Frame and detection counts for the run an export came from.
+pub struct RunStats {
+    /// Frames decoded and shown.
+    pub frames: u64,
+    /// Frames detection actually ran on, see [`ScanScope::scans`].
+    pub scanned: u64,
+    pub detections: usize,
+}
+
+/// Path of the run sidecar for `export_path`, e.g. `trace.csv` → `trace.run.json`.
+pub fn sidecar_path(export_path: &Path) -> PathBuf {
+    export_path.with_extension("run.json")
+}
+
+/// Writes a JSON description of the run next to an export, so the
+/// analysis can be reproduced and audited later. `source_sha256` is the
+/// source's [`sha256_file`], worked out once by the caller.
+pub fn write_sidecar(export_path: &Path, provenance: &Provenance, stats: &RunStats, source_sha256: Option<&str>) -> anyhow::Result<PathBuf> {
+    let mut map = provenance.to_map();
+    map.insert("source_sha256".into(), source_sha256.into());
+    map.insert("export".into(), export_path.display().to_string().into());
+    map.insert("frames_decoded".into(), stats.frames.into());
+    map.insert("frames_scanned".into(), stats.scanned.into());
+    map.insert("detections".into(), stats.detections.into());
+    let rate = (stats.scanned > 0).then(|| stats.detections as f64 / stats.scanned as f64);
+    map.insert("detection_rate".into(), opt(rate));
+
+    let path = sidecar_path(export_path);
+    let file = File::create(&path).with_context(|| format!("creating {}", path.display()))?;
+    let mut out = BufWriter::new(file);
+    serde_json::to_writer_pretty(&mut out, &Value::Object(map))?;
+    writeln!(out)?;
+    out.flush()?;
+    Ok(path)
+}
+
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Current UTC time as ISO 8601, e.g. `2024-03-01T12:34:56Z`.
+fn utc_timestamp() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0) as i64;
+    let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+
+    // days since 1970-01-01 to a civil date (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+/// Column-oriented view of a trace, shared by all output formats.
+/// Missing values (e.g. velocity across a gap) are `Value::Null`.
+pub struct Table {
+    pub columns: Vec<&'static str>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl Table {
+    /// Renames the position and kinematics columns of a table built from
+    /// [`normalize`]d points, so they don't read as pixels.
+    pub fn label_normalized(&mut self) {
+        for column in &mut self.columns {
+            if let Some((_, normalized)) = NORMALIZED_COLUMNS.iter().find(|(pixels, _)| pixels == column) {
+                *column = normalized;
+            }
+        }
+    }
+
+    /// Appends a column, one value per row.
+    pub fn push_column(&mut self, name: &'static str, values: impl IntoIterator<Item = Value>) {
+        self.columns.push(name);
+        for (row, value) in self.rows.iter_mut().zip(values) {
+            row.push(value);
+        }
+    }
+}
+
+const NORMALIZED_COLUMNS: [(&str, &str); 8] = [
+    ("x", "x_norm"),
+    ("y", "y_norm"),
+    ("vx", "vx_norm"),
+    ("vy", "vy_norm"),
+    ("speed", "speed_norm"),
+    ("ax", "ax_norm"),
+    ("ay", "ay_norm"),
+    ("acceleration", "acceleration_norm"),
+];
+
+/// Positions as fractions of a `size` pixel frame, 0 to 1 across it, so
+/// clips of different resolutions compare directly.
+pub fn normalize(points: &[TracePoint], size: [u32; 2]) -> Vec<TracePoint> {
+    let [w, h] = size.map(|s| s.max(1) as f32);
+    points.iter().map(|p| TracePoint { pos: [p.pos[0] / w, p.pos[1] / h], ..*p }).collect()
+}
+
+/// Fills gaps of up to `max_gap` missing samples with points linearly
+/// interpolated between the detections either side, at the trace's usual
+/// frame step. Longer gaps, and gaps between recording segments, stay
+/// empty. Returns the filled trace and which of its points were filled in.
+pub fn interpolate_gaps(points: &[TracePoint], max_gap: u64) -> (Vec<TracePoint>, Vec<bool>) {
+    let mut steps: Vec<u64> = points.windows(2).map(|w| w[1].frame.saturating_sub(w[0].frame)).filter(|d| *d > 0).collect();
+    steps.sort_unstable();
+    let step = steps.get(steps.len() / 2).copied().unwrap_or(1);
+
+    let mut filled = Vec::with_capacity(points.len());
+    let mut flags = Vec::with_capacity(points.len());
+    for (i, p) in points.iter().enumerate() {
+        if let Some(prev) = i.checked_sub(1).map(|j| &points[j]) {
+            let span = p.frame.saturating_sub(prev.frame);
+            let missing = (span / step).saturating_sub(1);
+            if span % step == 0 && (1..=max_gap).contains(&missing) && prev.segment == p.segment {
+                for k in 1..=missing {
+                    let t = (k * step) as f64 / span as f64;
+                    let lerp = |a: f32, b: f32| a + (b - a) * t as f32;
+                    filled.push(TracePoint {
+                        frame: prev.frame + k * step,
+                        time: prev.time + (p.time - prev.time) * t,
+                        pos: [lerp(prev.pos[0], p.pos[0]), lerp(prev.pos[1], p.pos[1])],
+                        angle: None,
+                        sampled_at: None,
+                        segment: p.segment,
+                    });
+                    flags.push(true);
+                }
+            }
+        }
+        filled.push(*p);
+        flags.push(false);
+    }
+    (filled, flags)
+}
+
+fn opt(v: Option<f64>) -> Value {
+    v.map(Value::from).unwrap_or(Value::Null)
+}
+
+/// Builds the export table. A `wall_time` column is added when any point
+/// was sampled by Magic, so the sampling schedule can be reconstructed,
+/// and a `segment` column when any point belongs to a recording segment.
+pub fn build_table(points: &[TracePoint], content: ExportContent, window: usize) -> Table {
+    let wall_time = points.iter().any(|p| p.sampled_at.is_some());
+    let segment = points.iter().any(|p| p.segment.is_some());
+    table_with(points, content, window, wall_time, segment)
+}
+
+fn table_with(points: &[TracePoint], content: ExportContent, window: usize, wall_time: bool, segment: bool) -> Table {
+    let with_positions = content != ExportContent::KinematicsOnly;
+    let with_kinematics = content != ExportContent::Positions;
+
+    let mut columns = vec!["frame", "time"];
+    if wall_time {
+        columns.push("wall_time");
+    }
+    if segment {
+        columns.push("segment");
+    }
+    if with_positions {
+        columns.extend(["x", "y", "angle"]);
+    }
+    if with_kinematics {
+        columns.extend(["vx", "vy", "speed", "heading", "ax", "ay", "acceleration"]);
+    }
+
+    let kin = if with_kinematics { kinematics::compute(points, window) } else { Vec::new() };
+
+    let rows = points
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let mut row = vec![Value::from(p.frame), Value::from(p.time)];
+            if wall_time {
+                row.push(opt(p.sampled_at));
+            }
+            if segment {
+                row.push(p.segment.map(Value::from).unwrap_or(Value::Null));
+            }
+            if with_positions {
+                row.extend([Value::from(p.pos[0]), Value::from(p.pos[1]), opt(p.angle.map(f64::from))]);
+            }
+            if let Some(k) = kin.get(i) {
+                row.extend([
+                    opt(k.velocity.map(|v| v[0])),
+                    opt(k.velocity.map(|v| v[1])),
+                    opt(k.speed()),
+                    opt(k.heading()),
+                    opt(k.acceleration.map(|a| a[0])),
+                    opt(k.acceleration.map(|a| a[1])),
+                    opt(k.acceleration_magnitude()),
+                ]);
+            }
+            row
+        })
+        .collect();
+
+    Table { columns, rows }
+}
+
+pub fn write(path: &Path, table: &Table, format: ExportFormat, provenance: &Provenance) -> anyhow::Result<()> {
+    let file = File::create(path).with_context(|| format!("creating {}", path.display()))?;
+    let mut out = BufWriter::new(file);
+    let metadata = provenance.to_map();
+    match format {
+        ExportFormat::Csv => write_csv(&mut out, table, &metadata)?,
+        ExportFormat::Json => write_json(&mut out, table, metadata)?,
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn write_csv(out: &mut impl Write, table: &Table, metadata: &Map<String, Value>) -> anyhow::Result<()> {
+    write_csv_header(out, &table.columns, metadata)?;
+    for row in &table.rows {
+        write_csv_row(out, row)?;
+    }
+    Ok(())
+}
+
+fn write_csv_header(out: &mut impl Write, columns: &[&str], metadata: &Map<String, Value>) -> io::Result<()> {
+    // `# key: value` comment lines, values as JSON so nested settings survive
+    for (key, value) in metadata {
+        let value = match value {
+            Value::String(s) => s.clone(),
+            v => v.to_string(),
+        };
+        writeln!(out, "# {}: {}", key, value)?;
+    }
+    writeln!(out, "{}", columns.join(","))
+}
+
+fn write_csv_row(out: &mut impl Write, row: &[Value]) -> io::Result<()> {
+    let cells: Vec<String> = row
+        .iter()
+        .map(|v| match v {
+            Value::Null => String::new(),
+            v => v.to_string(),
+        })
+        .collect();
+    writeln!(out, "{}", cells.join(","))
+}
+
+/// A positions CSV written as detections arrive rather than at the end,
+/// flushed at least every [`CsvStream::FLUSH_INTERVAL`] so a crash loses
+/// at most that much. Same layout as an [`ExportContent::Positions`] export,
+/// always with the `wall_time` and `segment` columns since Magic or a
+/// recording segment may start mid-stream.
+pub struct CsvStream {
+    path: PathBuf,
+    out: BufWriter<File>,
+    last_flush: Instant,
+}
+
+impl CsvStream {
+    pub const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+    pub fn create(path: &Path, provenance: &Provenance) -> anyhow::Result<Self> {
+        let file = File::create(path).with_context(|| format!("creating {}", path.display()))?;
+        let mut out = BufWriter::new(file);
+        let columns = table_with(&[], ExportContent::Positions, 1, true, true).columns;
+        write_csv_header(&mut out, &columns, &provenance.to_map())?;
+        out.flush()?;
+        Ok(Self { path: path.to_path_buf(), out, last_flush: Instant::now() })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn push(&mut self, point: &TracePoint) -> io::Result<()> {
+        let table = table_with(std::slice::from_ref(point), ExportContent::Positions, 1, true, true);
+        write_csv_row(&mut self.out, &table.rows[0])?;
+        self.flush_if_due()
+    }
+
+    /// Also worth calling when nothing was pushed, so the last points of a
+    /// run don't wait in the buffer for the next detection.
+    pub fn flush_if_due(&mut self) -> io::Result<()> {
+        if self.last_flush.elapsed() >= Self::FLUSH_INTERVAL {
+            self.out.flush()?;
+            self.last_flush = Instant::now();
+        }
+        Ok(())
+    }
+
+    /// Flushes and closes the file, reporting what dropping it would swallow.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+fn write_json(out: &mut impl Write, table: &Table, metadata: Map<String, Value>) -> anyhow::Result<()> {
+    let points: Vec<Value> = table
+        .rows
+        .iter()
+        .map(|row| {
+            let obj: Map<String, Value> = table
+                .columns
+                .iter()
+                .zip(row)
+                .map(|(c, v)| (c.to_string(), v.clone()))
+                .collect();
+            Value::Object(obj)
+        })
+        .collect();
+
+    let mut root = Map::new();
+    root.insert("metadata".to_string(), Value::Object(metadata));
+    root.insert("points".to_string(), Value::Array(points));
+    serde_json::to_writer_pretty(&mut *out, &Value::Object(root))?;
+    writeln!(out)?;
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use serde::Serialize;
+
+use crate::kinematics;
+use crate::trace::TracePoint;
+
+/// A sudden change of acceleration: the sample where the jerk peaked in a
+/// stretch of consecutive samples above the threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Flick {
+    pub frame: u64,
+    pub time: f64,
+    pub pos: [f32; 2],
+    /// px/s³.
+    pub jerk: f64,
+}
+
+/// Jerk (px/s³) from each sample's acceleration to the next one's, `None`
+/// where either acceleration isn't known. Accelerations come from central
+/// differences over `window` samples on each side, which is what keeps
+/// detection noise from reading as flicks.
+pub fn jerks(points: &[TracePoint], window: usize) -> Vec<Option<f64>> {
+    let kin = kinematics::compute(points, window);
+    let mut out = vec![None; points.len()];
+    for run in kinematics::contiguous_runs(points) {
+        for i in run.start + 1..run.end {
+            if let (Some(a), Some(b)) = (kin[i - 1].acceleration, kin[i].acceleration) {
+                let dt = points[i].time - points[i - 1].time;
+                out[i] = Some((b[0] - a[0]).hypot(b[1] - a[1]) / dt);
+            }
+        }
+    }
+    out
+}
+
+pub fn find_flicks(points: &[TracePoint], window: usize, threshold: f64) -> Vec<Flick> {
+    let mut flicks: Vec<Flick> = Vec::new();
+    let mut above = false;
+    for (p, jerk) in points.iter().zip(jerks(points, window)) {
+        let Some(jerk) = jerk.filter(|j| *j >= threshold) else {
+            above = false;
+            continue;
+        };
+        let flick = Flick { frame: p.frame, time: p.time, pos: p.pos, jerk };
+        match flicks.last_mut() {
+            Some(last) if above => {
+                if jerk > last.jerk {
+                    *last = flick;
+                }
+            }
+            _ => flicks.push(flick),
+        }
+        above = true;
+    }
+    flicks
+}
+
+type FlickKey = (usize, Option<u64>, usize, u64);
+
+/// The flicks in a trace, only found again when the trace, the window or
+/// the threshold change.
+#[derive(Default)]
+pub struct FlickAnalysis {
+    key: Option<FlickKey>,
+    flicks: Vec<Flick>,
+}
+
+impl FlickAnalysis {
+    pub fn update(&mut self, points: &[TracePoint], window: usize, threshold: f64) {
+        let key = (points.len(), points.last().map(|p| p.frame), window, threshold.to_bits());
+        if self.key == Some(key) {
+            return;
+        }
+        self.key = Some(key);
+        self.flicks = find_flicks(points, window, threshold);
+    }
+
+    pub fn flicks(&self) -> &[Flick] {
+        &self.flicks
+    }
+}
@@ -0,0 +1,91 @@
+use serde::Serialize;
+
+use crate::kinematics;
+use crate::trace::TracePoint;
+
+/// A side of the frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Edge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+/// The edge `pos` is within `margin` pixels of, the nearest if several,
+/// and the point on it straight out from `pos`.
+pub fn near_edge(pos: [f32; 2], size: [u32; 2], margin: f32) -> Option<(Edge, [f32; 2])> {
+    let (w, h) = (size[0] as f32, size[1] as f32);
+    let [x, y] = pos;
+    [
+        (x, Edge::Left, [0.0, y]),
+        (w - 1.0 - x, Edge::Right, [w - 1.0, y]),
+        (y, Edge::Top, [x, 0.0]),
+        (h - 1.0 - y, Edge::Bottom, [x, h - 1.0]),
+    ]
+    .into_iter()
+    .filter(|(d, _, _)| *d <= margin)
+    .min_by(|a, b| a.0.total_cmp(&b.0))
+    .map(|(_, edge, at)| (edge, at))
+}
+
+/// A stretch without detections between two that were made.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Gap {
+    /// Frames of the detections either side.
+    pub after_frame: u64,
+    pub before_frame: u64,
+    /// The last detection before the gap.
+    pub last_pos: [f32; 2],
+    /// Where the cursor left the frame, when the last detection before the
+    /// gap was close enough to an edge to call it off-screen rather than
+    /// lost.
+    pub exit: Option<(Edge, [f32; 2])>,
+    /// Where it came back, if the first detection after was near an edge.
+    pub entry: Option<(Edge, [f32; 2])>,
+}
+
+impl Gap {
+    pub fn off_screen(&self) -> bool {
+        self.exit.is_some()
+    }
+}
+
+type GapKey = (usize, Option<u64>, [u32; 2], u32);
+
+/// The gaps in a trace, told apart by whether the cursor was last seen at
+/// the frame's edge. Only recomputed when the trace, the frame size or the
+/// margin change.
+#[derive(Default)]
+pub struct GapAnalysis {
+    key: Option<GapKey>,
+    gaps: Vec<Gap>,
+}
+
+impl GapAnalysis {
+    pub fn update(&mut self, points: &[TracePoint], size: [u32; 2], margin: f32) {
+        let key = (points.len(), points.last().map(|p| p.frame), size, margin.to_bits());
+        if self.key == Some(key) {
+            return;
+        }
+        self.key = Some(key);
+        self.gaps = kinematics::contiguous_runs(points)
+            .windows(2)
+            .map(|runs| {
+                let (last, first) = (&points[runs[0].end - 1], &points[runs[1].start]);
+                Gap {
+                    after_frame: last.frame,
+                    before_frame: first.frame,
+                    last_pos: last.pos,
+                    exit: near_edge(last.pos, size, margin),
+                    entry: near_edge(first.pos, size, margin),
+                }
+            })
+            .collect();
+    }
+
+    pub fn gaps(&self) -> &[Gap] {
+        &self.gaps
+    }
+}
@@ -0,0 +1,267 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use anyhow::Context;
+use crossbeam_channel::unbounded;
+use ffmpeg_sidecar::download::auto_download;
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+
+use crate::cli::{AnalyzeArgs, BatchArgs, ScanArgs};
+use crate::export::{self, ExportContent, Provenance, RunStats};
+use crate::trace::TracePoint;
+use crate::worker::{video_worker, AppCommand, AppEvent};
+
+const VIDEO_EXTENSIONS: [&str; 9] = ["mp4", "mkv", "mov", "avi", "webm", "m4v", "flv", "wmv", "ffconcat"];
+
+pub struct Analysis {
+    pub points: Vec<TracePoint>,
+    pub frames: u64,
+    pub scanned: u64,
+    pub duration: f64,
+    pub size: [u32; 2],
+    pub fps: f64,
+}
+
+impl Analysis {
+    pub fn provenance(&self, source: &Path, scan: &ScanArgs) -> Provenance {
+        let (start, end) = scan.range;
+        let range = (start > 0.0 || end < self.duration).then(|| [start, end.min(self.duration)]);
+        Provenance {
+            source: Some(source.to_path_buf()),
+            duration: self.duration,
+            size: self.size,
+            fps: self.fps,
+            params: scan.params,
+            scope: scan.scope,
+            range,
+            calibration: None,
+            normalized: false,
+            processing: Vec::new(),
+        }
+    }
+
+    pub fn stats(&self) -> RunStats {
+        RunStats { frames: self.frames, scanned: self.scanned, detections: self.points.len() }
+    }
+
+    /// Writes the trace and its run sidecar.
+    fn write(&self, source: &Path, scan: &ScanArgs, output: &Path, format: export::ExportFormat) -> anyhow::Result<()> {
+        let provenance = self.provenance(source, scan);
+        let table = export::build_table(&self.points, ExportContent::Positions, 1);
+        export::write(output, &table, format, &provenance)?;
+        let hash = export::sha256_file(source).with_context(|| format!("hashing {}", source.display()))?;
+        export::write_sidecar(output, &provenance, &self.stats(), Some(&hash))?;
+        Ok(())
+    }
+}
+
+/// Decodes every frame of `path` through the same worker the GUI drives,
+/// collecting detections. `progress` gets the fraction of the video done.
+pub fn analyze_file(path: &Path, scan: &ScanArgs, mut progress: impl FnMut(f64)) -> Result<Analysis, String> {
+    let (cmd_tx, cmd_rx) = unbounded();
+    let (event_tx, event_rx) = unbounded();
+    let handle = thread::spawn(move || video_worker(cmd_rx, event_tx));
+
+    let _ = cmd_tx.send(AppCommand::SetDetection(scan.params));
+    let _ = cmd_tx.send(AppCommand::SetScope(scan.scope));
+    let _ = cmd_tx.send(AppCommand::SetRange(scan.range.0, scan.range.1));
+    let _ = cmd_tx.send(AppCommand::SetVideoStream(scan.stream));
+    let _ = cmd_tx.send(AppCommand::LoadFile(path.to_path_buf()));
+
+    let mut analysis = Analysis { points: Vec::new(), frames: 0, scanned: 0, duration: 0.0, size: [0, 0], fps: 0.0 };
+    let result = loop {
+        match event_rx.recv() {
+            Ok(AppEvent::Metadata { duration, width, height, fps }) => {
+                analysis.duration = duration;
+                analysis.size = [width, height];
+                analysis.fps = fps;
+            }
+            Ok(AppEvent::FrameReady { frame, time, position, angle, .. }) => {
+                analysis.frames += 1;
+                if scan.scope.scans(frame) {
+                    analysis.scanned += 1;
+                }
+                if let Some(pos) = position {
+                    analysis.points.push(TracePoint::new(frame, time, pos).with_angle(angle));
+                }
+                if analysis.duration > 0.0 {
+                    let start = scan.range.0.min(analysis.duration);
+                    let end = scan.range.1.min(analysis.duration);
+                    if end > start {
+                        progress(((time - start) / (end - start)).clamp(0.0, 1.0));
+                    }
+                }
+                let _ = cmd_tx.send(AppCommand::Step);
+            }
+            Ok(
+                AppEvent::LoadStarted { .. } | AppEvent::Diagnosis { .. } | AppEvent::FrameCache { .. }
+                | AppEvent::Stats(_) | AppEvent::Log { .. } | AppEvent::Waveform { .. }
+                | AppEvent::RescanProgress(_) | AppEvent::Rescanned(_) | AppEvent::VideoStreams { .. }
+            ) => {}
+            Ok(AppEvent::EndOfStream) => break Ok(()),
+            Ok(AppEvent::Error(e)) => break Err(e),
+            Err(_) => break Err("video worker stopped unexpectedly".to_string()),
+        }
+    };
+
+    drop(cmd_tx);
+    let _ = handle.join();
+    result.map(|_| analysis)
+}
+
+fn collect_videos(dir: &Path, recursive: bool, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if recursive {
+                collect_videos(&path, recursive, out)?;
+            }
+        } else if path.extension()
+            .map(|e| VIDEO_EXTENSIONS.contains(&e.to_string_lossy().to_lowercase().as_str()))
+            .unwrap_or(false)
+        {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// `analyze` entry point: one video to one trace, printing whole-percent
+/// progress lines. Returns the process exit code.
+pub fn run_analyze(args: &AnalyzeArgs) -> i32 {
+    if let Err(e) = auto_download() {
+        eprintln!("Failed to download ffmpeg: {}", e);
+    }
+
+    let mut last_percent = None;
+    let result = analyze_file(&args.input, &args.scan, |fraction| {
+        let percent = (fraction * 100.0).floor() as u32;
+        if last_percent != Some(percent) {
+            last_percent = Some(percent);
+            println!("{}%", percent);
+        }
+    });
+
+    let analysis = match result {
+        Ok(analysis) => analysis,
+        Err(e) => {
+            eprintln!("Cannot analyze {}: {}", args.input.display(), e);
+            return 1;
+        }
+    };
+
+    if let Err(e) = analysis.write(&args.input, &args.scan, &args.output, args.format) {
+        eprintln!("Export failed: {:#}", e);
+        return 1;
+    }
+    if last_percent != Some(100) {
+        println!("100%");
+    }
+    println!("{} frames, {} detections -> {}", analysis.frames, analysis.points.len(), args.output.display());
+    0
+}
+
+/// Analyzes `input` into its place under `--out`, returning its summary
+/// entry and the line to report it with, an error if it failed.
+fn batch_file(args: &BatchArgs, input: &Path) -> (Value, Result<String, String>) {
+    let relative = input.strip_prefix(&args.input).unwrap_or(input);
+    let output = args.out.join(relative).with_extension(args.format.extension());
+    let result = analyze_file(input, &args.scan, |_| {}).and_then(|analysis| {
+        if let Some(parent) = output.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        analysis.write(input, &args.scan, &output, args.format).map_err(|e| format!("{:#}", e))?;
+        Ok(analysis)
+    });
+
+    match result {
+        Ok(analysis) => {
+            let entry = json!({
+                "input": input.display().to_string(),
+                "output": output.display().to_string(),
+                "duration": analysis.duration,
+                "width": analysis.size[0],
+                "height": analysis.size[1],
+                "fps": analysis.fps,
+                "frames": analysis.frames,
+                "detections": analysis.points.len(),
+                "error": Value::Null,
+            });
+            (entry, Ok(format!("{} frames, {} detections", analysis.frames, analysis.points.len())))
+        }
+        Err(e) => {
+            let entry = json!({
+                "input": input.display().to_string(),
+                "output": Value::Null,
+                "error": e,
+            });
+            (entry, Err(e))
+        }
+    }
+}
+
+/// `--batch` entry point, analyzing up to `--jobs` files at a time and
+/// printing a line as each finishes. Returns the process exit code: 0 when
+/// every file was analyzed, 1 if any failed or nothing could be started.
+pub fn run_batch(args: &BatchArgs) -> i32 {
+    if let Err(e) = auto_download() {
+        eprintln!("Failed to download ffmpeg: {}", e);
+    }
+
+    let mut files = Vec::new();
+    if let Err(e) = collect_videos(&args.input, args.recursive, &mut files) {
+        eprintln!("Cannot read {}: {}", args.input.display(), e);
+        return 1;
+    }
+    files.sort();
+    if files.is_empty() {
+        eprintln!("No video files found in {}", args.input.display());
+        return 1;
+    }
+    if let Err(e) = std::fs::create_dir_all(&args.out) {
+        eprintln!("Cannot create {}: {}", args.out.display(), e);
+        return 1;
+    }
+
+    // files are handed out in order; the summary keeps that order whatever
+    // order they finish in
+    let (next, done) = (AtomicUsize::new(0), AtomicUsize::new(0));
+    let results = Mutex::new(vec![None; files.len()]);
+    thread::scope(|scope| {
+        for _ in 0..args.jobs.min(files.len()) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(input) = files.get(i) else { break };
+                let (entry, outcome) = batch_file(args, input);
+                let relative = input.strip_prefix(&args.input).unwrap_or(input);
+                let k = done.fetch_add(1, Ordering::Relaxed) + 1;
+                match &outcome {
+                    Ok(line) => println!("[{}/{}] {} ... {}", k, files.len(), relative.display(), line),
+                    Err(e) => println!("[{}/{}] {} ... failed: {}", k, files.len(), relative.display(), e),
+                }
+                results.lock()[i] = Some((entry, outcome.is_err()));
+            });
+        }
+    });
+    let (entries, failures): (Vec<Value>, Vec<bool>) = results.into_inner().into_iter().flatten().unzip();
+    let failed = failures.iter().filter(|&&f| f).count();
+
+    let summary = json!({
+        "profile": args.scan.profile,
+        "files": entries,
+        "failed": failed,
+    });
+    let summary_path = args.out.join("summary.json");
+    let written = std::fs::File::create(&summary_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|f| serde_json::to_writer_pretty(f, &summary).map_err(anyhow::Error::from));
+    if let Err(e) = written {
+        eprintln!("Cannot write {}: {}", summary_path.display(), e);
+        return 1;
+    }
+
+    println!("{} of {} files analyzed, summary in {}", files.len() - failed, files.len(), summary_path.display());
+    if failed > 0 { 1 } else { 0 }
+}
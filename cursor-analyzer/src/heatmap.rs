@@ -0,0 +1,90 @@
+use crate::trace::TracePoint;
+
+/// Colour stops from least to most visited, alpha included so empty and
+/// rarely visited cells let the video show through.
+const COLORMAP: [[f32; 4]; 5] = [
+    [0.0, 0.0, 255.0, 0.0],
+    [0.0, 160.0, 255.0, 160.0],
+    [0.0, 220.0, 80.0, 200.0],
+    [255.0, 230.0, 0.0, 230.0],
+    [255.0, 30.0, 0.0, 255.0],
+];
+
+/// Visit counts of a trace on a coarse grid over the frame. Kept up to date
+/// incrementally so long traces don't get re-binned on every new point.
+#[derive(Default)]
+pub struct Heatmap {
+    cell_size: u32,
+    frame_size: [u32; 2],
+    cols: usize,
+    rows: usize,
+    counts: Vec<u32>,
+    max: u32,
+    /// How many points of the trace are binned, and the last of them, to
+    /// tell appends from a cleared or replaced trace.
+    binned: usize,
+    last: Option<TracePoint>,
+}
+
+impl Heatmap {
+    /// Brings the grid up to date with `points`. Returns whether anything
+    /// changed, i.e. whether the image needs to be rebuilt.
+    pub fn update(&mut self, points: &[TracePoint], frame_size: [u32; 2], cell_size: u32) -> bool {
+        let cell_size = cell_size.max(1);
+        let appended = cell_size == self.cell_size
+            && frame_size == self.frame_size
+            && match self.binned {
+                0 => true,
+                n => points.get(n - 1) == self.last.as_ref(),
+            };
+
+        if !appended {
+            self.cell_size = cell_size;
+            self.frame_size = frame_size;
+            self.cols = frame_size[0].div_ceil(cell_size) as usize;
+            self.rows = frame_size[1].div_ceil(cell_size) as usize;
+            self.counts = vec![0; self.cols * self.rows];
+            self.max = 0;
+            self.binned = 0;
+        } else if points.len() == self.binned {
+            return false;
+        }
+
+        for p in &points[self.binned..] {
+            let col = (p.pos[0] / cell_size as f32).floor();
+            let row = (p.pos[1] / cell_size as f32).floor();
+            if col < 0.0 || row < 0.0 || col as usize >= self.cols || row as usize >= self.rows {
+                continue;
+            }
+            let count = &mut self.counts[row as usize * self.cols + col as usize];
+            *count += 1;
+            self.max = self.max.max(*count);
+        }
+        self.binned = points.len();
+        self.last = points.last().copied();
+        true
+    }
+
+    /// Grid size in cells, `[cols, rows]`.
+    pub fn size(&self) -> [usize; 2] {
+        [self.cols, self.rows]
+    }
+
+    /// The grid as unmultiplied RGBA, one pixel per cell, log-scaled so a
+    /// few hot spots don't wash out everything else.
+    pub fn to_rgba(&self) -> Vec<u8> {
+        let scale = 1.0 / (1.0 + self.max as f32).ln().max(f32::EPSILON);
+        let mut rgba = Vec::with_capacity(self.counts.len() * 4);
+        for &count in &self.counts {
+            if count == 0 {
+                rgba.extend([0, 0, 0, 0]);
+                continue;
+            }
+            let t = (1.0 + count as f32).ln() * scale * (COLORMAP.len() - 1) as f32;
+            let i = (t.floor() as usize).min(COLORMAP.len() - 2);
+            let f = t - i as f32;
+            rgba.extend(COLORMAP[i].iter().zip(&COLORMAP[i + 1]).map(|(a, b)| (a + (b - a) * f) as u8));
+        }
+        rgba
+    }
+}
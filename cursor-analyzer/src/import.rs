@@ -0,0 +1,143 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+
+use crate::trace::TracePoint;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum TimeUnit {
+    Seconds,
+    Milliseconds,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum CoordSpace {
+    Pixels,
+    Normalized,
+}
+
+/// Which CSV columns hold time/x/y and how to interpret them.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ColumnMapping {
+    pub time: usize,
+    pub x: usize,
+    pub y: usize,
+    pub time_unit: TimeUnit,
+    pub coords: CoordSpace,
+}
+
+/// A parsed CSV file, kept as text until the user has chosen a mapping.
+pub struct CsvTable {
+    pub path: PathBuf,
+    /// `# key: value` comment lines, as written by our own CSV export.
+    pub metadata: Vec<(String, String)>,
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl CsvTable {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("reading {}", path.display()))?;
+
+        let metadata = text
+            .lines()
+            .filter_map(|l| l.trim().strip_prefix('#')?.split_once(':'))
+            .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+            .collect();
+
+        let mut lines = text
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'));
+
+        let Some(first) = lines.next() else {
+            bail!("{} is empty", path.display());
+        };
+        let delimiter = [',', ';', '\t']
+            .into_iter()
+            .max_by_key(|d| first.matches(*d).count())
+            .unwrap_or(',');
+        let split = |line: &str| -> Vec<String> {
+            line.split(delimiter).map(|c| c.trim().trim_matches('"').to_string()).collect()
+        };
+
+        let first = split(first);
+        let has_header = first.iter().any(|c| c.parse::<f64>().is_err());
+
+        let mut rows: Vec<Vec<String>> = lines.map(split).collect();
+        let headers = if has_header {
+            first
+        } else {
+            let headers = (1..=first.len()).map(|i| format!("column {}", i)).collect();
+            rows.insert(0, first);
+            headers
+        };
+
+        if headers.len() < 3 {
+            bail!("{} has fewer than three columns", path.display());
+        }
+
+        Ok(Self { path: path.to_path_buf(), metadata, headers, rows })
+    }
+
+    /// Identifies files of the same kind so a mapping chosen once can be reused.
+    pub fn shape_key(&self) -> String {
+        let ext = self.path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+        format!("{}|{}", ext, self.headers.join(",").to_lowercase())
+    }
+
+    /// Picks columns by common names, falling back to the first three columns.
+    pub fn guess_mapping(&self) -> ColumnMapping {
+        let find = |names: &[&str]| {
+            self.headers.iter().position(|h| names.contains(&h.to_lowercase().as_str()))
+        };
+        let time = find(&["t", "time", "timestamp", "ms", "time_ms", "seconds"]).unwrap_or(0);
+        let x = find(&["x", "pos_x", "cursor_x", "mouse_x"]).unwrap_or(1);
+        let y = find(&["y", "pos_y", "cursor_y", "mouse_y"]).unwrap_or(2);
+
+        let header = self.headers[time].to_lowercase();
+        let time_unit = if header.contains("ms") { TimeUnit::Milliseconds } else { TimeUnit::Seconds };
+
+        let coords = if self.column_values(x).chain(self.column_values(y)).all(|v| (0.0..=1.0).contains(&v)) {
+            CoordSpace::Normalized
+        } else {
+            CoordSpace::Pixels
+        };
+
+        ColumnMapping { time, x, y, time_unit, coords }
+    }
+
+    fn column_values(&self, col: usize) -> impl Iterator<Item = f64> + '_ {
+        self.rows.iter().filter_map(move |r| r.get(col)?.parse().ok())
+    }
+
+    /// Converts the rows to trace points in frame pixels. Rows that can't be
+    /// parsed are skipped; the second value is how many were.
+    pub fn to_points(&self, mapping: &ColumnMapping, frame_size: [u32; 2], fps: f64) -> (Vec<TracePoint>, usize) {
+        let mut points = Vec::with_capacity(self.rows.len());
+        let mut skipped = 0;
+
+        for row in &self.rows {
+            let cell = |i: usize| row.get(i).and_then(|c| c.parse::<f64>().ok());
+            let (Some(t), Some(x), Some(y)) = (cell(mapping.time), cell(mapping.x), cell(mapping.y)) else {
+                skipped += 1;
+                continue;
+            };
+
+            let time = match mapping.time_unit {
+                TimeUnit::Seconds => t,
+                TimeUnit::Milliseconds => t / 1000.0,
+            };
+            let (x, y) = match mapping.coords {
+                CoordSpace::Pixels => (x, y),
+                CoordSpace::Normalized => (x * frame_size[0] as f64, y * frame_size[1] as f64),
+            };
+            let frame = (time * fps).round().max(0.0) as u64;
+            points.push(TracePoint::new(frame, time, [x as f32, y as f32]));
+        }
+
+        points.sort_by(|a, b| a.time.total_cmp(&b.time));
+        (points, skipped)
+    }
+}
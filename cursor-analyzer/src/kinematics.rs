@@ -0,0 +1,185 @@
+use std::ops::Range;
+
+use crate::trace::TracePoint;
+
+/// Consecutive samples further apart than this multiple of the typical
+/// sample interval are treated as a gap in the trace.
+const GAP_FACTOR: f64 = 1.5;
+
+/// Velocity (px/s) and acceleration (px/s²) at a trace point. Either is
+/// `None` when its differentiation window would reach across a gap or past
+/// the ends of the trace.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Kinematics {
+    pub velocity: Option<[f64; 2]>,
+    pub acceleration: Option<[f64; 2]>,
+}
+
+impl Kinematics {
+    pub fn speed(&self) -> Option<f64> {
+        self.velocity.map(|[vx, vy]| vx.hypot(vy))
+    }
+
+    /// Direction of movement in degrees (0° = right, 90° = down), `None`
+    /// while standing still.
+    pub fn heading(&self) -> Option<f64> {
+        self.velocity.filter(|v| v[0] != 0.0 || v[1] != 0.0).map(|[vx, vy]| vy.atan2(vx).to_degrees())
+    }
+
+    pub fn acceleration_magnitude(&self) -> Option<f64> {
+        self.acceleration.map(|[ax, ay]| ax.hypot(ay))
+    }
+}
+
+/// Median time between consecutive samples, or `None` for fewer than two points.
+pub fn typical_interval(points: &[TracePoint]) -> Option<f64> {
+    let mut deltas: Vec<f64> = points
+        .windows(2)
+        .map(|w| w[1].time - w[0].time)
+        .filter(|dt| *dt > 0.0)
+        .collect();
+    if deltas.is_empty() {
+        return None;
+    }
+    deltas.sort_by(|a, b| a.total_cmp(b));
+    Some(deltas[deltas.len() / 2])
+}
+
+/// Splits the trace into index ranges without gaps between their samples.
+pub fn contiguous_runs(points: &[TracePoint]) -> Vec<Range<usize>> {
+    if points.is_empty() {
+        return Vec::new();
+    }
+    let max_dt = typical_interval(points).map_or(f64::INFINITY, |dt| dt * GAP_FACTOR);
+
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for i in 1..points.len() {
+        let dt = points[i].time - points[i - 1].time;
+        if dt <= 0.0 || dt > max_dt {
+            runs.push(start..i);
+            start = i;
+        }
+    }
+    runs.push(start..points.len());
+    runs
+}
+
+/// Differentiates the trace with a central difference spanning `window`
+/// samples on each side. Larger windows trade time resolution for less noise.
+pub fn compute(points: &[TracePoint], window: usize) -> Vec<Kinematics> {
+    let k = window.max(1);
+    let mut out = vec![Kinematics::default(); points.len()];
+
+    for run in contiguous_runs(points) {
+        let inner = |i: usize| i >= run.start + k && i + k < run.end;
+
+        for i in run.clone() {
+            if !inner(i) {
+                continue;
+            }
+            let (a, b) = (&points[i - k], &points[i + k]);
+            let dt = b.time - a.time;
+            out[i].velocity = Some([
+                (b.pos[0] - a.pos[0]) as f64 / dt,
+                (b.pos[1] - a.pos[1]) as f64 / dt,
+            ]);
+        }
+
+        for i in run.clone() {
+            if !inner(i) {
+                continue;
+            }
+            if let (Some(va), Some(vb)) = (out[i - k].velocity, out[i + k].velocity) {
+                let dt = points[i + k].time - points[i - k].time;
+                out[i].acceleration = Some([(vb[0] - va[0]) / dt, (vb[1] - va[1]) / dt]);
+            }
+        }
+    }
+
+    out
+}
+
+/// Speed (px/s) along each segment between consecutive samples, `None`
+/// for segments that span a gap.
+pub fn segment_speeds(points: &[TracePoint]) -> Vec<Option<f64>> {
+    let mut out = vec![None; points.len().saturating_sub(1)];
+    for run in contiguous_runs(points) {
+        for i in run.start + 1..run.end {
+            let (a, b) = (&points[i - 1], &points[i]);
+            let dist = (b.pos[0] - a.pos[0]).hypot(b.pos[1] - a.pos[1]) as f64;
+            out[i - 1] = Some(dist / (b.time - a.time));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FPS: f64 = 60.0;
+
+    /// A sample on each of `frames`, at `pos(t)`.
+    fn trace(frames: impl IntoIterator<Item = u64>, pos: impl Fn(f64) -> [f64; 2]) -> Vec<TracePoint> {
+        frames.into_iter()
+            .map(|f| {
+                let t = f as f64 / FPS;
+                let [x, y] = pos(t);
+                TracePoint::new(f, t, [x as f32, y as f32])
+            })
+            .collect()
+    }
+
+    fn close(a: [f64; 2], b: [f64; 2], tolerance: f64) -> bool {
+        (a[0] - b[0]).abs() <= tolerance && (a[1] - b[1]).abs() <= tolerance
+    }
+
+    #[test]
+    fn constant_velocity() {
+        let points = trace(0..60, |t| [100.0 + 120.0 * t, 50.0 - 30.0 * t]);
+        for window in [1, 3] {
+            let kin = compute(&points, window);
+            for (i, k) in kin.iter().enumerate() {
+                let inner = i >= window && i + window < points.len();
+                assert_eq!(k.velocity.is_some(), inner, "velocity at {} with window {}", i, window);
+                if let Some(v) = k.velocity {
+                    assert!(close(v, [120.0, -30.0], 0.05), "{:?} at {}", v, i);
+                }
+                if let Some(a) = k.acceleration {
+                    assert!(close(a, [0.0, 0.0], 5.0), "{:?} at {}", a, i);
+                }
+            }
+            assert!(kin[points.len() / 2].acceleration.is_some());
+        }
+        assert!(segment_speeds(&points).iter().all(|s| s.is_some_and(|s| (s - 120f64.hypot(30.0)).abs() < 0.05)));
+    }
+
+    #[test]
+    fn sinusoid() {
+        let (amplitude, omega) = (200.0, std::f64::consts::TAU);
+        let points = trace(0..120, |t| [400.0 + amplitude * (omega * t).sin(), 300.0]);
+        let kin = compute(&points, 1);
+        for (p, k) in points.iter().zip(&kin) {
+            let (Some(v), Some(a)) = (k.velocity, k.acceleration) else { continue };
+            let (sin, cos) = (omega * p.time).sin_cos();
+            // central differences of one sample each way at 60 fps
+            assert!(close(v, [amplitude * omega * cos, 0.0], 0.01 * amplitude * omega), "velocity {:?} at {}", v, p.time);
+            assert!(close(a, [-amplitude * omega * omega * sin, 0.0], 0.03 * amplitude * omega * omega), "acceleration {:?} at {}", a, p.time);
+        }
+        // acceleration needs the velocities a window either side
+        assert_eq!(kin.iter().filter(|k| k.acceleration.is_some()).count(), points.len() - 4);
+    }
+
+    #[test]
+    fn nothing_across_a_gap() {
+        let points = trace((0..30).chain(40..70), |t| [60.0 * t, 0.0]);
+        assert_eq!(contiguous_runs(&points), vec![0..30, 30..60]);
+
+        let kin = compute(&points, 2);
+        // the window of these would reach the other side of the gap
+        assert!(kin[28..32].iter().all(|k| k.velocity.is_none()));
+        assert!(kin[27].velocity.is_some() && kin[32].velocity.is_some());
+        assert_eq!(segment_speeds(&points)[29], None);
+    }
+}
@@ -0,0 +1,5 @@
+//! The analyzer's frame-to-playfield calibration and the trace points it
+//! maps, usable without the app, its window or ffmpeg.
+
+pub mod calibration;
+pub mod trace;
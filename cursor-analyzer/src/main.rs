@@ -1,482 +1,6304 @@
+mod align;
+mod audio;
+mod cache;
+mod cli;
+mod detect;
+mod direction;
+mod export;
+mod flicks;
+mod gaps;
+mod headless;
+mod heatmap;
+mod import;
+mod kinematics;
+mod median;
+mod playlist;
+mod privacy;
+mod plot;
+mod rpc;
+mod simplify;
+mod spatial;
+mod strokes;
+mod table;
+mod worker;
+
 use eframe::egui;
 use std::path::{Path, PathBuf};
-use std::time::Instant;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use crossbeam_channel::{unbounded, Receiver, Sender};
-use image::RgbaImage;
 use std::thread;
-use std::process::{Command, Stdio, Child, ChildStdout};
-use std::io::{Read, BufReader};
-use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
 use ffmpeg_sidecar::download::auto_download;
+use cursor_analyzer::{calibration, trace};
+
+use cache::{CacheKey, CachedRun, DetectionCache, FrameResult};
+use calibration::{Calibration, TransformModel};
+use cli::Mode;
+use detect::{BitDepth, Channel, DetectionParams, Rejections, PROFILES};
+use direction::{DirectionAnalysis, DirectionParams, ROSE_SECTORS};
+use export::{CsvStream, ExportContent, ExportFormat, Provenance, RunStats};
+use flicks::FlickAnalysis;
+use gaps::{Edge, Gap, GapAnalysis};
+use heatmap::Heatmap;
+use import::{ColumnMapping, CoordSpace, CsvTable, TimeUnit};
+use median::MedianCache;
+use plot::{SpeedHistogram, SpeedSeries};
+use simplify::SimplifyCache;
+use spatial::PointGrid;
+use strokes::{StrokeParams, Strokes};
+use table::{SortColumn, TableView};
+use trace::TracePoint;
+use worker::{smooth_stat, video_worker, AppCommand, AppEvent, LogLevel, ScanScope, VideoStream, WorkerStats, DEFAULT_FPS, DEFAULT_PROBE_TIMEOUT};
+
+/// Window title, and the directory name eframe keeps our data under.
+const APP_NAME: &str = "Cursor analyser";
+
+const IMPORT_COLORS: [egui::Color32; 4] = [
+    egui::Color32::LIGHT_BLUE,
+    egui::Color32::GREEN,
+    egui::Color32::YELLOW,
+    egui::Color32::from_rgb(255, 0, 255),
+];
 
-#[derive(Debug, Clone)]
-enum AppCommand {
-    LoadFile(PathBuf),
-    Seek(f64), 
-    Step,      
-    Play,      
-    Pause,     
-}
-
-#[derive(Debug)]
-enum AppEvent {
-    FrameReady {
-        image: RgbaImage,
-        width: u32,
-        height: u32,
-        position: Option<[f32; 2]>,
-    },
-    Metadata {
-        duration: f64,
-        width: u32,
-        height: u32,
-    },
-    Error(String),
+/// What one Magic tick moves forward by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+enum MagicStep {
+    /// The next decoded frame, however long the interval is, so samples
+    /// are evenly spaced in wall-clock time.
+    Frame,
+    /// `interval_ms` of video, so samples are evenly spaced in video time.
+    Interval,
 }
 
-struct VideoApp {
+impl MagicStep {
+    fn label(&self) -> &'static str {
+        match self {
+            MagicStep::Frame => "Wall clock: 1 frame per tick",
+            MagicStep::Interval => "Video time: interval per tick",
+        }
+    }
 
-    file_path: Option<PathBuf>,
-    speed: f64,
-    interval_ms: u64,
-    is_simulating: bool,
-    last_sim_time: Instant,
+    /// How exports name the schedule.
+    fn clock(&self) -> &'static str {
+        match self {
+            MagicStep::Frame => "wall_clock",
+            MagicStep::Interval => "video_time",
+        }
+    }
+}
 
-    is_playing: bool,
-    last_play_frame: Instant,
+/// Turning point markers and the direction rose.
+const TURN_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 160, 0);
+/// Where the cursor left the frame, and where it was last seen before a
+/// gap it didn't leave the frame for.
+const EXIT_COLOR: egui::Color32 = egui::Color32::from_rgb(80, 220, 255);
+const LOST_COLOR: egui::Color32 = egui::Color32::from_rgb(160, 160, 160);
+const FLICK_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 60, 140);
+const MEASURE_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 230, 80);
+/// Detections on frames shown while scrubbing, not yet kept.
+const PREVIEW_COLOR: egui::Color32 = egui::Color32::from_rgb(120, 200, 255);
+const BOOKMARK_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 110, 180);
+/// Height of each row of annotation bands under the timeline.
+const ANNOTATION_LANE: f32 = 7.0;
 
-    texture: Option<egui::TextureHandle>,
-    current_frame_size: [u32; 2],
-    video_duration: f64,
-    current_time: f64, 
+const SETTINGS_KEY: &str = "settings";
+/// Where the overlay style and grid were saved before [`Settings`] took
+/// them in, read when there are no settings yet.
+const STYLE_KEY: &str = "overlay_style";
+const GRID_KEY: &str = "grid_overlay";
+/// How long settings have to stay put after a change before they are
+/// written, besides on exit.
+const SETTINGS_SAVE_DELAY: Duration = Duration::from_secs(2);
+/// How far the contrast halo reaches past points and lines, in screen pixels.
+const HALO_WIDTH: f32 = 1.5;
+const HALO_COLOR: egui::Color32 = egui::Color32::from_black_alpha(170);
+/// Screen pixels between the vertices a curved trail is drawn with.
+const SPLINE_STEP: f32 = 3.0;
+/// How close, in screen pixels, the mouse has to be to a trail point to
+/// pick it.
+const HOVER_REACH: f32 = 8.0;
 
-    positions: Vec<[f32; 2]>,
+/// Colours and sizes of the trail, saved with the app's other settings.
+/// Sizes are in video pixels.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+struct OverlayStyle {
+    point_color: egui::Color32,
+    line_color: egui::Color32,
+    point_radius: f32,
+    line_width: f32,
+    /// Dark outline under the trail so it reads on any background.
+    halo: bool,
+    render: TrailRender,
+    /// Draw the line as a curve through the points instead of straight
+    /// segments. Only the drawing changes; the points stay as detected.
+    spline: bool,
+    /// Draw a marker on every n-th point only; the line still uses them all.
+    marker_every: u32,
+    /// Ring around the detection nearest the playhead.
+    now_marker: bool,
+    /// Extend the ring with crosshair lines out to the frame edges.
+    now_crosshair: bool,
+    now_color: egui::Color32,
+    /// Position, and optionally frame number, printed by the trail's
+    /// points: every `label_every`-th one, and only those in view.
+    point_labels: bool,
+    label_every: u32,
+    label_frames: bool,
+    /// Ends of the gradient the trail is coloured with by time, oldest first.
+    time_colors: [egui::Color32; 2],
+    /// Time and frame number printed on the frame itself, for screenshots.
+    stamp: bool,
+    stamp_corner: Corner,
+    /// Text height of the stamp, in video pixels like the other sizes.
+    stamp_size: f32,
+    /// Fill of the bars around the video where it doesn't fill the panel.
+    letterbox: egui::Color32,
+}
 
-    cmd_tx: Sender<AppCommand>,
-    event_rx: Receiver<AppEvent>,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
 }
 
-impl VideoApp {
-    fn new() -> Self {
-        let (cmd_tx, cmd_rx) = unbounded();
-        let (event_tx, event_rx) = unbounded();
+impl Corner {
+    const ALL: [Corner; 4] = [Corner::TopLeft, Corner::TopRight, Corner::BottomLeft, Corner::BottomRight];
 
-        if let Err(e) = auto_download() {
-            eprintln!("Failed to download ffmpeg: {}", e);
+    fn label(&self) -> &'static str {
+        match self {
+            Corner::TopLeft => "Top left",
+            Corner::TopRight => "Top right",
+            Corner::BottomLeft => "Bottom left",
+            Corner::BottomRight => "Bottom right",
         }
+    }
 
-        thread::spawn(move || {
-            video_worker(cmd_rx, event_tx);
-        });
+    fn align(&self) -> egui::Align2 {
+        match self {
+            Corner::TopLeft => egui::Align2::LEFT_TOP,
+            Corner::TopRight => egui::Align2::RIGHT_TOP,
+            Corner::BottomLeft => egui::Align2::LEFT_BOTTOM,
+            Corner::BottomRight => egui::Align2::RIGHT_BOTTOM,
+        }
+    }
+}
+
+/// Which parts of the trail get drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+enum TrailRender {
+    Points,
+    Line,
+    Both,
+}
+
+impl TrailRender {
+    const ALL: [TrailRender; 3] = [TrailRender::Points, TrailRender::Line, TrailRender::Both];
+
+    fn label(&self) -> &'static str {
+        match self {
+            TrailRender::Points => "Points",
+            TrailRender::Line => "Line",
+            TrailRender::Both => "Both",
+        }
+    }
 
+    fn lines(&self) -> bool {
+        *self != TrailRender::Points
+    }
+
+    fn next(&self) -> Self {
+        match self {
+            TrailRender::Points => TrailRender::Line,
+            TrailRender::Line => TrailRender::Both,
+            TrailRender::Both => TrailRender::Points,
+        }
+    }
+}
+
+impl Default for OverlayStyle {
+    fn default() -> Self {
         Self {
-            file_path: None,
-            speed: 1.0,
-            interval_ms: 1000,
-            is_simulating: false,
-            last_sim_time: Instant::now(),
-            is_playing: false,
-            last_play_frame: Instant::now(),
-            texture: None,
-            current_frame_size: [0, 0],
-            video_duration: 0.0,
-            current_time: 0.0,
-            positions: Vec::new(),
-            cmd_tx,
-            event_rx,
+            point_color: egui::Color32::RED,
+            line_color: egui::Color32::RED,
+            point_radius: 5.0,
+            line_width: 3.0,
+            halo: false,
+            render: TrailRender::Both,
+            spline: false,
+            marker_every: 1,
+            now_marker: true,
+            now_crosshair: false,
+            now_color: egui::Color32::from_rgb(0, 255, 255),
+            point_labels: false,
+            label_every: 10,
+            label_frames: true,
+            time_colors: [egui::Color32::from_rgb(40, 80, 255), egui::Color32::from_rgb(255, 230, 0)],
+            stamp: false,
+            stamp_corner: Corner::TopLeft,
+            stamp_size: 24.0,
+            letterbox: egui::Color32::BLACK,
         }
     }
+}
 
-    fn handle_events(&mut self, ctx: &egui::Context) {
-        while let Ok(event) = self.event_rx.try_recv() {
-            match event {
-                AppEvent::FrameReady { image, width, height, position } => {
-                    self.current_frame_size = [width, height];
+impl OverlayStyle {
+    /// Whether point `i` of the trail gets a marker.
+    fn marker_at(&self, i: usize) -> bool {
+        self.render != TrailRender::Line && i.is_multiple_of(self.marker_every.max(1) as usize)
+    }
 
-                    let color_image = egui::ColorImage::from_rgba_unmultiplied(
-                        [width as usize, height as usize],
-                        image.as_flat_samples().as_slice(),
-                    );
+    /// Whether point `i` of the trail gets a coordinate label.
+    fn label_at(&self, i: usize) -> bool {
+        self.point_labels && i.is_multiple_of(self.label_every.max(1) as usize)
+    }
 
-                    self.texture = Some(ctx.load_texture(
-                        "video_frame",
-                        color_image,
-                        egui::TextureOptions::LINEAR,
-                    ));
+    /// Stroke of the halo under a line `width` wide, faded by `alpha`.
+    fn halo_stroke(&self, width: f32, alpha: f32) -> egui::Stroke {
+        egui::Stroke::new(width + 2.0 * HALO_WIDTH, HALO_COLOR.gamma_multiply(alpha))
+    }
+}
 
-                    if let Some(pos) = position {
-                        self.positions.push(pos);
-                    }
+/// Which of the things drawn over the video are shown. Hiding a layer only
+/// skips drawing it; what it draws stays as it was.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+struct Layers {
+    trail: bool,
+    /// The current point, turns, gaps, the selection and the preview.
+    markers: bool,
+    heatmap: bool,
+    grid: bool,
+    /// Measurements and hand-placed labels.
+    annotations: bool,
+}
 
-                    self.current_time += 1.0 / 60.0;
-                }
-                AppEvent::Metadata { duration, width, height } => {
-                    self.video_duration = duration;
-                    self.current_frame_size = [width, height];
-                    self.current_time = 0.0;
-                }
-                AppEvent::Error(msg) => {
-                    eprintln!("Video Error: {}", msg);
-                }
+impl Default for Layers {
+    fn default() -> Self {
+        Self { trail: true, markers: true, heatmap: true, grid: true, annotations: true }
+    }
+}
+
+impl Layers {
+    const NONE: Layers = Layers { trail: false, markers: false, heatmap: false, grid: false, annotations: false };
+}
+
+/// Reference grid over the video, in frame pixels so it stays on the image
+/// while zooming. Saved with the app's other settings.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+struct GridStyle {
+    shown: bool,
+    spacing: GridSpacing,
+    /// Line colour, its alpha the opacity.
+    color: egui::Color32,
+    /// Coordinates of the lines along the top and left edges.
+    labels: bool,
+    /// Points placed by hand (labels, measurements, reference points) move
+    /// to the nearest intersection, whether or not the grid is shown.
+    snap: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+enum GridSpacing {
+    /// A line every this many frame pixels from the top-left corner.
+    Pixels(f32),
+    /// The frame split into this many columns and rows.
+    Divisions([u32; 2]),
+}
+
+impl Default for GridStyle {
+    fn default() -> Self {
+        Self {
+            shown: false,
+            spacing: GridSpacing::Pixels(100.0),
+            color: egui::Color32::from_white_alpha(80),
+            labels: true,
+            snap: false,
+        }
+    }
+}
+
+impl GridStyle {
+    /// Positions of the vertical lines (axis 0) or horizontal lines (axis
+    /// 1) within `from..=to`, for a frame `size` pixels along that axis.
+    fn lines(&self, axis: usize, size: f32, from: f32, to: f32) -> Vec<f32> {
+        match self.spacing {
+            GridSpacing::Pixels(step) => {
+                let step = step.max(1.0);
+                ((from / step).ceil() as i64..=(to / step).floor() as i64).map(|k| k as f32 * step).collect()
+            }
+            GridSpacing::Divisions(n) => {
+                let n = n[axis].max(1);
+                (0..=n).map(|k| size * k as f32 / n as f32).filter(|x| (from..=to).contains(x)).collect()
             }
         }
     }
+
+    /// The intersection nearest `pos`, both in frame pixels so it doesn't
+    /// depend on how large the video is drawn.
+    fn snap(&self, pos: [f64; 2], size: [f32; 2]) -> [f64; 2] {
+        [0, 1].map(|axis| {
+            let step = match self.spacing {
+                GridSpacing::Pixels(step) => step.max(1.0) as f64,
+                GridSpacing::Divisions(n) => size[axis] as f64 / n[axis].max(1) as f64,
+            };
+            (pos[axis] / step).round() * step
+        })
+    }
 }
 
-impl eframe::App for VideoApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        self.handle_events(ctx);
+/// What the app starts with next time. The window size and position are
+/// kept by eframe itself.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+struct Settings {
+    speed: f64,
+    interval_ms: u64,
+    magic_step: MagicStep,
+    detection: DetectionParams,
+    frame_cache: usize,
+    probe_timeout: f64,
+    /// Where the last file dialog picked a file.
+    last_dir: Option<PathBuf>,
+    reveal_after_export: bool,
+    sharp_pixels: bool,
+    style: OverlayStyle,
+    grid: GridStyle,
+    show_table: bool,
+    show_speed_plot: bool,
+    show_plots: bool,
+    show_histogram: bool,
+    show_direction: bool,
+    show_strokes: bool,
+    show_waveform: bool,
+    show_segments: bool,
+    show_bookmarks: bool,
+    show_annotations: bool,
+    show_ffmpeg_log: bool,
+    privacy: bool,
+    show_stats: bool,
+    show_overlays: bool,
+    layers: Layers,
+    detached: bool,
+    /// Size of the monitor the window was last on, to tell when the saved
+    /// position is for a monitor that is gone.
+    monitor_size: Option<egui::Vec2>,
+    /// Column mappings picked on import, keyed by [`CsvTable::shape_key`].
+    import_mappings: HashMap<String, ColumnMapping>,
+    /// Playfield calibrations keyed by [`calibration_key`].
+    calibrations: HashMap<String, Calibration>,
+}
 
-        if self.is_simulating {
-            if self.last_sim_time.elapsed().as_millis() as u64 >= self.interval_ms {
-                let _ = self.cmd_tx.send(AppCommand::Step);
-                self.last_sim_time = Instant::now();
-            }
-            ctx.request_repaint();
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            interval_ms: 1000,
+            magic_step: MagicStep::Frame,
+            detection: DetectionParams::default(),
+            frame_cache: DEFAULT_FRAME_CACHE,
+            probe_timeout: DEFAULT_PROBE_TIMEOUT.as_secs_f64(),
+            last_dir: None,
+            reveal_after_export: false,
+            sharp_pixels: false,
+            style: OverlayStyle::default(),
+            grid: GridStyle::default(),
+            show_table: false,
+            show_speed_plot: false,
+            show_plots: false,
+            show_histogram: false,
+            show_direction: false,
+            show_strokes: false,
+            show_waveform: false,
+            show_segments: false,
+            show_bookmarks: false,
+            show_annotations: false,
+            show_ffmpeg_log: false,
+            privacy: false,
+            show_stats: false,
+            show_overlays: true,
+            layers: Layers::default(),
+            detached: false,
+            monitor_size: None,
+            import_mappings: HashMap::new(),
+            calibrations: HashMap::new(),
         }
+    }
+}
 
-        if self.is_playing && !self.is_simulating {
-             let target_dt = 1.0 / (60.0 * self.speed);
-             if self.last_play_frame.elapsed().as_secs_f64() >= target_dt {
-                 let _ = self.cmd_tx.send(AppCommand::Step);
-                 self.last_play_frame = Instant::now();
-             }
-             ctx.request_repaint();
+impl Settings {
+    /// The saved settings, or the defaults if there are none or they can't
+    /// be read, so a damaged file never keeps the app from starting.
+    fn load(storage: Option<&dyn eframe::Storage>) -> Self {
+        let Some(storage) = storage else { return Self::default() };
+        if let Some(settings) = eframe::get_value(storage, SETTINGS_KEY) {
+            return settings;
         }
+        if storage.get_string(SETTINGS_KEY).is_some() {
+            eprintln!("Saved settings could not be read, starting with the defaults");
+        }
+        Self {
+            style: eframe::get_value(storage, STYLE_KEY).unwrap_or_default(),
+            grid: eframe::get_value(storage, GRID_KEY).unwrap_or_default(),
+            ..Self::default()
+        }
+    }
+}
 
-        egui::TopBottomPanel::bottom("controls").show(ctx, |ui| {
-            ui.horizontal(|ui| {
-                if ui.button("Open File").clicked() {
-                    if let Some(path) = rfd::FileDialog::new().add_filter("Video", &["mp4"]).pick_file() {
-                        self.file_path = Some(path.clone());
-                        self.positions.clear();
-                        self.is_playing = false;
-                        let _ = self.cmd_tx.send(AppCommand::LoadFile(path));
-                    }
-                }
+/// How detections are drawn over the video.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OverlayMode {
+    Trail,
+    Heatmap,
+    Both,
+}
 
-                if ui.button(if self.is_playing { "Pause" } else { "Play" }).clicked() {
-                    self.is_playing = !self.is_playing;
-                    self.last_play_frame = Instant::now();
-                    if self.is_playing && self.is_simulating {
-                        self.is_simulating = false; 
-                    }
-                }
+impl OverlayMode {
+    fn label(&self) -> &'static str {
+        match self {
+            OverlayMode::Trail => "Trail",
+            OverlayMode::Heatmap => "Heatmap",
+            OverlayMode::Both => "Both",
+        }
+    }
 
-                ui.label("Speed:");
-                ui.add(egui::Slider::new(&mut self.speed, 0.07..=2.0).step_by(0.01));
+    fn trail(&self) -> bool {
+        *self != OverlayMode::Heatmap
+    }
 
-                ui.label("Interval (ms):");
-                ui.add(egui::DragValue::new(&mut self.interval_ms).speed(10).range(1..=10000));
+    fn heatmap(&self) -> bool {
+        *self != OverlayMode::Trail
+    }
+}
 
-                if ui.button(if self.is_simulating { "Stop Magic" } else { "Magic" }).clicked() {
-                    self.is_simulating = !self.is_simulating;
-                    if self.is_simulating {
-                        self.is_playing = false; 
-                        self.last_sim_time = Instant::now();
-                    }
-                }
+/// What the video area shows of each frame. Detection always runs on the
+/// decoded frame itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DisplayMode {
+    Normal,
+    /// |current - previous| per channel, times the gain.
+    Difference,
+    /// White where any channel changed by at least the threshold.
+    Threshold,
+}
 
-                if ui.button("Clear Pos").clicked() {
-                    self.positions.clear();
-                }
-            });
-        });
+impl DisplayMode {
+    fn label(&self) -> &'static str {
+        match self {
+            DisplayMode::Normal => "Normal",
+            DisplayMode::Difference => "Difference",
+            DisplayMode::Threshold => "Threshold",
+        }
+    }
+}
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            let available_size = ui.available_size();
+/// What the trail's segments are coloured by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TrailColor {
+    Solid,
+    Speed,
+    Stroke,
+    Segment,
+    Time,
+}
 
-            if let Some(tex) = &self.texture {
-                 let tex_size = tex.size_vec2();
+/// Blue for slow through green and yellow to red for fast, `t` in `0..=1`.
+fn speed_color(t: f32) -> egui::Color32 {
+    const STOPS: [[f32; 3]; 4] = [[40.0, 80.0, 255.0], [0.0, 220.0, 120.0], [255.0, 230.0, 0.0], [255.0, 30.0, 0.0]];
+    let t = t * (STOPS.len() - 1) as f32;
+    let i = (t.floor() as usize).min(STOPS.len() - 2);
+    let f = t - i as f32;
+    let [r, g, b] = [0, 1, 2].map(|c| (STOPS[i][c] + (STOPS[i + 1][c] - STOPS[i][c]) * f) as u8);
+    egui::Color32::from_rgb(r, g, b)
+}
 
-                 let scale_x = available_size.x / tex_size.x;
-                 let scale_y = available_size.y / tex_size.y;
-                 let scale = scale_x.min(scale_y);
+/// A colour per stroke or segment, the hue stepping by the golden angle
+/// so neighbours stand apart.
+fn series_color(i: usize) -> egui::Color32 {
+    egui::ecolor::Hsva::new((i as f32 * 0.381_966).fract(), 0.75, 1.0, 1.0).into()
+}
 
-                 let display_size = tex_size * scale;
+/// Opens the system file manager on `path`'s folder, with the file
+/// selected where the platform can do that.
+fn reveal(path: &Path) -> std::io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut c = std::process::Command::new("open");
+        c.arg("-R").arg(path);
+        c
+    } else if cfg!(windows) {
+        // explorer wants the switch and the path as one argument
+        let mut select = std::ffi::OsString::from("/select,");
+        select.push(path);
+        let mut c = std::process::Command::new("explorer");
+        c.arg(select);
+        c
+    } else {
+        let mut c = std::process::Command::new("xdg-open");
+        c.arg(path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new(".")));
+        c
+    };
+    // not waited for; the file manager outlives the call
+    command.spawn().map(drop)
+}
 
-                 let (rect, _response) = ui.allocate_exact_size(display_size, egui::Sense::click());
+/// The detection settings tuned in the sidebar, for the main video or the
+/// compare one.
+fn detection_grid(ui: &mut egui::Ui, params: &mut DetectionParams) {
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_id_salt("detect_profile")
+            .selected_text("Load profile")
+            .show_ui(ui, |ui| {
+                for name in PROFILES {
+                    if ui.selectable_label(false, name).clicked() {
+                        *params = DetectionParams::profile(name).expect("listed profiles exist");
+                    }
+                }
+            })
+            .response
+            .on_hover_text("Replace every setting below with a named set: dim for washed-out footage, strict for captures with lots of white UI");
+        if ui.button("Reset to defaults").clicked() {
+            *params = DetectionParams::default();
+        }
+    });
+    egui::Grid::new("probe_geometry").num_columns(2).show(ui, |ui| {
+        ui.label("Bright min:");
+        ui.add(egui::DragValue::new(&mut params.bright_min))
+            .on_hover_text("Tip and spine pixels must be at least this bright");
+        ui.end_row();
+        ui.label("Dark max:");
+        ui.add(egui::DragValue::new(&mut params.dark_max))
+            .on_hover_text("The outline left of the spine must be darker than this");
+        ui.end_row();
+        ui.label("Right max:");
+        ui.add(egui::DragValue::new(&mut params.right_max))
+            .on_hover_text("The pixel right of the tip must be darker than this");
+        ui.end_row();
+        ui.label("Diagonal min:");
+        ui.add(egui::DragValue::new(&mut params.diag_min))
+            .on_hover_text("A pixel on the arrow's diagonal edge counts as a hit above this");
+        ui.end_row();
+        ui.label("Diagonal hits:");
+        ui.add(egui::DragValue::new(&mut params.diag_hits).range(0..=7))
+            .on_hover_text("Hits needed out of the 7 diagonal samples");
+        ui.end_row();
+        ui.label("Channel:");
+        egui::ComboBox::from_id_salt("detect_channel")
+            .selected_text(params.channel.label())
+            .show_ui(ui, |ui| {
+                for channel in Channel::ALL {
+                    ui.selectable_value(&mut params.channel, channel, channel.label());
+                }
+            })
+            .response
+            .on_hover_text("Threshold a single channel or luma instead of requiring every channel, for coloured cursors");
+        ui.end_row();
+        ui.label("Decode depth:");
+        egui::ComboBox::from_id_salt("decode_depth")
+            .selected_text(params.depth.label())
+            .show_ui(ui, |ui| {
+                for depth in [BitDepth::Eight, BitDepth::Sixteen] {
+                    ui.selectable_value(&mut params.depth, depth, depth.label());
+                }
+            })
+            .response
+            .on_hover_text("16-bit keeps the precision of 10-bit and HDR sources, at twice the memory and decode cost");
+        ui.end_row();
+        ui.label("Probe length (px):");
+        ui.add(egui::DragValue::new(&mut params.probe_len).range(2..=200));
+        ui.end_row();
+        ui.label("Right probe:");
+        ui.checkbox(&mut params.right_probe, "Dark right of the spine")
+            .on_hover_text("Also require a dark outline right of the spine, so wide bright areas don't match. Leave off for arrow cursors");
+        ui.end_row();
+        ui.label("Bottom margin (px):");
+        ui.add(egui::DragValue::new(&mut params.margin).range(0..=400));
+        ui.end_row();
+        ui.label("Reference height:");
+        ui.add(egui::DragValue::new(&mut params.reference_height).range(0..=4320))
+            .on_hover_text("Frame height the lengths above were set for, 0 to use them as-is at every resolution");
+        ui.end_row();
+        ui.label("Blur radius:");
+        ui.add(egui::DragValue::new(&mut params.blur_radius).range(0..=4))
+            .on_hover_text("Smooth the frame before detection to suppress compression noise, 0 for off");
+        ui.end_row();
+        ui.label("Blur strength:");
+        ui.add_enabled(params.blur_radius > 0, egui::Slider::new(&mut params.blur_strength, 0.0..=1.0));
+        ui.end_row();
+        ui.label("Downsample:");
+        ui.add(egui::DragValue::new(&mut params.downsample).range(1..=4).prefix("1/"))
+            .on_hover_text("Look for bright pixels on a coarser grid first and only test around them at full resolution, 1 for off. Faster on large frames");
+        ui.end_row();
+    });
+}
 
-                 ui.painter().image(
-                    tex.id(),
-                    rect,
-                    egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
-                    egui::Color32::WHITE,
-                 );
+/// The detection closest in time to `t`. Cheap on long traces as long as
+/// they are in time order, which appended detections are.
+fn nearest_in_time(points: &[TracePoint], t: f64) -> Option<&TracePoint> {
+    let i = points.partition_point(|p| p.time < t);
+    let before = i.checked_sub(1).and_then(|i| points.get(i));
+    match (before, points.get(i)) {
+        (Some(a), Some(b)) => Some(if t - a.time <= b.time - t { a } else { b }),
+        (a, b) => a.or(b),
+    }
+}
 
-                 if !self.positions.is_empty() {
+/// Takes all the space left in `ui`, fills it with `fill` and returns the
+/// largest rect of `frame_size`'s aspect centred in it, plus its scale.
+/// Everything mapping between screen and frame coordinates starts from
+/// this rect.
+fn letterbox(ui: &mut egui::Ui, frame_size: egui::Vec2, fill: egui::Color32) -> (egui::Rect, f32) {
+    let (panel, _) = ui.allocate_exact_size(ui.available_size(), egui::Sense::hover());
+    ui.painter().rect_filled(panel, 0.0, fill);
+    let scale = (panel.width() / frame_size.x).min(panel.height() / frame_size.y);
+    (egui::Rect::from_center_size(panel.center(), frame_size * scale), scale)
+}
 
-                     let scale_factor = display_size.x / tex_size.x;
+/// Length of a measurement in frame pixels, and in playfield units when
+/// calibrated.
+fn measure_label([a, b]: [[f64; 2]; 2], calibration: Option<&Calibration>) -> String {
+    let mut text = format!("{:.1} px", (b[0] - a[0]).hypot(b[1] - a[1]));
+    if let Some(c) = calibration {
+        let (a, b) = (c.transform.apply(a), c.transform.apply(b));
+        text += &format!(" / {:.2} units", (b[0] - a[0]).hypot(b[1] - a[1]));
+    }
+    text
+}
 
-                     let points: Vec<egui::Pos2> = self.positions.iter().map(|p| {
-                         rect.min + egui::vec2(p[0] * scale_factor, p[1] * scale_factor)
-                     }).collect();
+/// Indices into `shown` that the trail must not connect into from the
+/// point before, because a deleted range started between them or they are
+/// in different recording segments. `cuts` are the first frames of deleted
+/// ranges in ascending order.
+fn trail_cuts(cuts: &[u64], shown: &[TracePoint]) -> Vec<usize> {
+    (1..shown.len())
+        .filter(|&i| {
+            let at = cuts.partition_point(|&c| c <= shown[i - 1].frame);
+            shown[i - 1].segment != shown[i].segment || cuts.get(at).is_some_and(|&c| c <= shown[i].frame)
+        })
+        .collect()
+}
 
-                     for p in &points {
-                         ui.painter().circle_filled(*p, 5.0 * scale_factor, egui::Color32::RED);
-                     }
+/// Indices into `shown` of the first point after each gap in detection.
+fn trail_gaps(shown: &[TracePoint]) -> Vec<usize> {
+    kinematics::contiguous_runs(shown).iter().skip(1).map(|run| run.start).collect()
+}
 
-                     if points.len() > 1 {
-                        ui.painter().add(egui::Shape::line(
-                            points,
-                            egui::Stroke::new(3.0 * scale_factor, egui::Color32::RED),
-                        ));
-                     }
-                }
-            } else {
-                ui.centered_and_justified(|ui| {
-                    ui.label("Load a video file...");
-                });
-            }
-        });
+/// Vertices from `points[i]` to `points[i + 1]` along a centripetal
+/// Catmull-Rom spline, which neither overshoots nor loops where the points
+/// are unevenly spaced. `smooth(k)` tells whether the curve carries on from
+/// point `k - 1` into `k`; where it doesn't, the end runs straight on
+/// instead of bending towards a point across a gap or cut.
+fn spline_segment(points: &[egui::Pos2], i: usize, smooth: impl Fn(usize) -> bool) -> Vec<egui::Pos2> {
+    let (p1, p2) = (points[i], points[i + 1]);
+    let steps = ((p2 - p1).length() / SPLINE_STEP).ceil().min(64.0) as usize;
+    if steps < 2 {
+        return vec![p1, p2];
     }
-}
+    let p0 = if i > 0 && smooth(i) { points[i - 1] } else { p1 + (p1 - p2) };
+    let p3 = if i + 2 < points.len() && smooth(i + 2) { points[i + 2] } else { p2 + (p2 - p1) };
 
-struct VideoWorker {
-    rx: Receiver<AppCommand>,
-    tx: Sender<AppEvent>,
-    current_process: Option<Child>,
-    current_reader: Option<BufReader<ChildStdout>>,
-    current_file: Option<PathBuf>,
-    width: u32,
-    height: u32,
-    duration: f64,
+    // knots spaced by the square root of the distance between the points
+    let knot = |a: egui::Pos2, b: egui::Pos2| (b - a).length().sqrt().max(1e-3);
+    let t1 = knot(p0, p1);
+    let t2 = t1 + knot(p1, p2);
+    let t3 = t2 + knot(p2, p3);
+    let mix = |a: egui::Pos2, b: egui::Pos2, from: f32, to: f32, t: f32| a.lerp(b, (t - from) / (to - from));
+    (0..=steps)
+        .map(|k| {
+            let t = egui::lerp(t1..=t2, k as f32 / steps as f32);
+            let (a1, a2, a3) = (mix(p0, p1, 0.0, t1, t), mix(p1, p2, t1, t2, t), mix(p2, p3, t2, t3, t));
+            let (b1, b2) = (mix(a1, a2, 0.0, t2, t), mix(a2, a3, t1, t3, t));
+            mix(b1, b2, t1, t2, t)
+        })
+        .collect()
 }
 
-impl VideoWorker {
-    fn new(rx: Receiver<AppCommand>, tx: Sender<AppEvent>) -> Self {
-        Self {
-            rx,
-            tx,
-            current_process: None,
-            current_reader: None,
-            current_file: None,
-            width: 0,
-            height: 0,
-            duration: 0.0,
+/// The whole of `points` as one curve, straight across the segments that
+/// `smooth` says not to carry on into.
+fn spline_line(points: &[egui::Pos2], smooth: impl Fn(usize) -> bool) -> Vec<egui::Pos2> {
+    let mut line = vec![points[0]];
+    for i in 0..points.len() - 1 {
+        match smooth(i + 1) {
+            true => line.extend(spline_segment(points, i, &smooth).into_iter().skip(1)),
+            false => line.push(points[i + 1]),
         }
     }
+    line
+}
+
+/// `cuts` are ascending indices of points the line does not lead into, see
+/// [`trail_cuts`]; `gaps` those it leads into straight, see [`trail_gaps`].
+fn draw_trail(painter: &egui::Painter, points: &[egui::Pos2], cuts: &[usize], gaps: &[usize], style: &OverlayStyle, scale: f32) {
+    let (radius, width) = (style.point_radius * scale, style.line_width * scale);
+    let lines = style.render.lines() && points.len() > 1;
+    let markers = || points.iter().enumerate().filter(|(i, _)| style.marker_at(*i)).map(|(_, p)| *p);
+    let pieces = || {
+        let bounds = std::iter::once(0).chain(cuts.iter().copied()).chain(std::iter::once(points.len()));
+        bounds.clone().zip(bounds.skip(1)).filter(|(a, b)| b - a > 1).map(|(a, b)| match style.spline {
+            true => spline_line(&points[a..b], |k| gaps.binary_search(&(a + k)).is_err()),
+            false => points[a..b].to_vec(),
+        })
+    };
 
-    fn run(&mut self) {
-        loop {
-            match self.rx.recv() {
-                Ok(cmd) => match cmd {
-                    AppCommand::LoadFile(path) => {
-                        self.load_file(path);
-                    },
-                    AppCommand::Step => {
-                        self.read_next_frame();
-                    },
-                    AppCommand::Seek(t) => {
-                        self.seek(t);
-                    },
-                    AppCommand::Play => {},
-                    AppCommand::Pause => {},
-                },
-                Err(_) => break,
+    if style.halo {
+        if lines {
+            for piece in pieces() {
+                painter.add(egui::Shape::line(piece, style.halo_stroke(width, 1.0)));
             }
         }
+        for p in markers() {
+            painter.circle_filled(p, radius + HALO_WIDTH, HALO_COLOR);
+        }
     }
+    for p in markers() {
+        painter.circle_filled(p, radius, style.point_color);
+    }
+    if lines {
+        for piece in pieces() {
+            painter.add(egui::Shape::line(piece, egui::Stroke::new(width, style.line_color)));
+        }
+    }
+}
 
-    fn load_file(&mut self, path: PathBuf) {
-
-        match probe_file(&path) {
-            Ok((dur, w, h)) => {
-                self.duration = dur;
-                self.width = w;
-                self.height = h;
-                self.current_file = Some(path.clone());
+/// Gradient bar in the bottom-left corner of the video, labelled at its
+/// ends and in the middle.
+fn draw_legend(painter: &egui::Painter, video: egui::Rect, color: impl Fn(f32) -> egui::Color32, [lo, mid, hi]: [String; 3]) {
+    const STEPS: usize = 32;
+    let bar = egui::Rect::from_min_size(video.left_bottom() + egui::vec2(10.0, -34.0), egui::vec2(160.0, 10.0));
+    painter.rect_filled(bar.expand(4.0).with_max_y(bar.max.y + 18.0), 4.0, egui::Color32::from_black_alpha(160));
+    let step = bar.width() / STEPS as f32;
+    for i in 0..STEPS {
+        let cell = egui::Rect::from_min_size(bar.min + egui::vec2(i as f32 * step, 0.0), egui::vec2(step + 0.5, bar.height()));
+        painter.rect_filled(cell, 0.0, color(i as f32 / (STEPS - 1) as f32));
+    }
+    let font = egui::FontId::proportional(11.0);
+    painter.text(bar.left_bottom() + egui::vec2(0.0, 2.0), egui::Align2::LEFT_TOP, lo, font.clone(), egui::Color32::WHITE);
+    painter.text(bar.center_bottom() + egui::vec2(0.0, 2.0), egui::Align2::CENTER_TOP, mid, font.clone(), egui::Color32::WHITE);
+    painter.text(bar.right_bottom() + egui::vec2(0.0, 2.0), egui::Align2::RIGHT_TOP, hi, font, egui::Color32::WHITE);
+}
 
-                let _ = self.tx.send(AppEvent::Metadata {
-                    duration: dur,
-                    width: w,
-                    height: h,
-                });
+/// How much of the trail the overlay draws. Everything stays stored and
+/// exportable either way.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TrailWindow {
+    All,
+    /// The newest n points, 0 for all.
+    Points(usize),
+    /// Points up to this many seconds older than the newest, 0 for all.
+    Seconds(f64),
+}
 
-                self.start_ffmpeg(0.0);
+impl TrailWindow {
+    fn label(&self) -> &'static str {
+        match self {
+            TrailWindow::All => "Whole trail",
+            TrailWindow::Points(_) => "Last N points",
+            TrailWindow::Seconds(_) => "Last T seconds",
+        }
+    }
 
-                self.read_next_frame();
-            },
-            Err(e) => {
-                let _ = self.tx.send(AppEvent::Error(e));
+    /// The tail of `points` inside the window. A slice and a binary search,
+    /// so it stays cheap however long the trace gets.
+    fn apply<'a>(&self, points: &'a [TracePoint]) -> &'a [TracePoint] {
+        match *self {
+            TrailWindow::Points(n) if n > 0 => &points[points.len().saturating_sub(n)..],
+            TrailWindow::Seconds(t) if t > 0.0 => {
+                let Some(newest) = points.last() else { return points };
+                let start = points.partition_point(|p| p.time < newest.time - t);
+                &points[start..]
             }
+            _ => points,
         }
     }
+}
 
-    fn start_ffmpeg(&mut self, start_time: f64) {
-        if let Some(mut child) = self.current_process.take() {
-             let _ = child.kill();
-             let _ = child.wait();
-        }
-        self.current_reader = None;
+/// How long, in video time, the follow view keeps tracking the last
+/// detection before it backs out to the whole frame.
+const FOLLOW_LOST_AFTER: f64 = 1.0;
 
-        if let Some(path) = &self.current_file {
-            let binary = if cfg!(windows) { "ffmpeg" } else { "./ffmpeg" };
-            let mut cmd = Command::new(binary);
-            cmd.arg("-i").arg(path.to_str().unwrap());
+/// Side of the loupe in screen pixels, and screen pixels per frame pixel in it.
+const LOUPE_SIZE: f32 = 192.0;
+const LOUPE_ZOOM: f32 = 4.0;
 
-            if start_time > 0.0 {
-                cmd.arg("-ss").arg(&format!("{}", start_time));
-            }
+/// Longer side of the minimap in pixels.
+const MINIMAP_SIZE: usize = 200;
+/// The minimap is redrawn once the trace has grown by this many points, or
+/// when it changed otherwise or the frame moved on and it is this old.
+const MINIMAP_REBUILD_POINTS: usize = 32;
+const MINIMAP_REFRESH: Duration = Duration::from_secs(1);
+/// Most trail points drawn into the minimap; longer traces are thinned.
+const MINIMAP_MAX_POINTS: usize = 2000;
 
-            cmd.args(&[
-                "-f", "image2pipe",
-                "-pix_fmt", "rgba",
-                "-vcodec", "rawvideo",
-                "-"
-            ]);
-            cmd.stdout(Stdio::piped());
-            cmd.stderr(Stdio::null());
+/// The whole frame and trail, small, as last drawn for the minimap.
+struct Minimap {
+    texture: egui::TextureHandle,
+    /// Frame pixels per minimap pixel.
+    scale: f32,
+    /// Trace length and frame shown it was drawn from, and when.
+    points: usize,
+    frame: u64,
+    built: Instant,
+}
 
-            match cmd.spawn() {
-                Ok(mut child) => {
-                    if let Some(stdout) = child.stdout.take() {
-                        self.current_reader = Some(BufReader::new(stdout));
-                        self.current_process = Some(child);
-                    }
-                },
-                Err(e) => {
-                     let _ = self.tx.send(AppEvent::Error(format!("FFmpeg spawn error: {}", e)));
-                }
+/// Marks gaps in the trail: a wedge pointing out of the frame where the
+/// cursor left it, a cross where it was last seen otherwise. `leaving` is an
+/// exit the trace hasn't come back from yet.
+fn draw_gaps<'a>(painter: &egui::Painter, gaps: impl Iterator<Item = &'a Gap>, leaving: Option<(Edge, [f32; 2])>, to_screen: impl Fn(f32, f32) -> egui::Pos2, size: f32) {
+    let mut exits: Vec<(Edge, [f32; 2])> = leaving.into_iter().collect();
+    for gap in gaps {
+        match gap.exit {
+            Some(exit) => exits.push(exit),
+            None => {
+                let at = to_screen(gap.last_pos[0], gap.last_pos[1]);
+                let stroke = egui::Stroke::new(2.0, LOST_COLOR);
+                painter.line_segment([at - egui::vec2(size, size), at + egui::vec2(size, size)], stroke);
+                painter.line_segment([at - egui::vec2(size, -size), at + egui::vec2(size, -size)], stroke);
             }
         }
     }
+    for (edge, pos) in exits {
+        let out = match edge {
+            Edge::Left => egui::vec2(-1.0, 0.0),
+            Edge::Right => egui::vec2(1.0, 0.0),
+            Edge::Top => egui::vec2(0.0, -1.0),
+            Edge::Bottom => egui::vec2(0.0, 1.0),
+        };
+        let tip = to_screen(pos[0], pos[1]);
+        let (back, side) = (tip - out * size * 2.0, out.rot90() * size);
+        painter.add(egui::Shape::convex_polygon(vec![tip, back + side, back - side], EXIT_COLOR, egui::Stroke::new(1.0, HALO_COLOR)));
+    }
+}
 
-    fn seek(&mut self, time: f64) {
-        self.start_ffmpeg(time);
-        self.read_next_frame();
+/// `frame` scaled down to fit [`MINIMAP_SIZE`] and darkened, with the trail
+/// drawn over it; returns it with the frame pixels per minimap pixel.
+fn minimap_image(frame: &egui::ColorImage, points: &[TracePoint], color: egui::Color32) -> (egui::ColorImage, f32) {
+    let [w, h] = frame.size;
+    let scale = (w.max(h) as f32 / MINIMAP_SIZE as f32).max(1.0);
+    let (tw, th) = (((w as f32 / scale) as usize).max(1), ((h as f32 / scale) as usize).max(1));
+    let mut pixels = Vec::with_capacity(tw * th);
+    for y in 0..th {
+        let sy = (((y as f32 + 0.5) * scale) as usize).min(h - 1);
+        for x in 0..tw {
+            let sx = (((x as f32 + 0.5) * scale) as usize).min(w - 1);
+            let [r, g, b, _] = frame.pixels[sy * w + sx].to_array();
+            pixels.push(egui::Color32::from_rgb(r / 2, g / 2, b / 2));
+        }
     }
 
-    fn read_next_frame(&mut self) {
-        if self.width == 0 || self.height == 0 { return; }
+    let step = points.len().div_ceil(MINIMAP_MAX_POINTS).max(1);
+    let thinned: Vec<[f32; 2]> = points.iter().step_by(step).map(|p| [p.pos[0] / scale, p.pos[1] / scale]).collect();
+    let mut plot = |x: f32, y: f32| {
+        if x >= 0.0 && y >= 0.0 && (x as usize) < tw && (y as usize) < th {
+            pixels[y as usize * tw + x as usize] = color;
+        }
+    };
+    for pair in thinned.windows(2) {
+        let ([x0, y0], [x1, y1]) = (pair[0], pair[1]);
+        let n = (x1 - x0).abs().max((y1 - y0).abs()).ceil().max(1.0) as usize;
+        for k in 0..=n {
+            let f = k as f32 / n as f32;
+            plot(x0 + (x1 - x0) * f, y0 + (y1 - y0) * f);
+        }
+    }
+    if let [[x, y]] = thinned[..] {
+        plot(x, y);
+    }
+    (egui::ColorImage { size: [tw, th], pixels }, scale)
+}
 
-        if let Some(reader) = &mut self.current_reader {
-            let frame_size = (self.width * self.height * 4) as usize;
-            let mut buffer = vec![0u8; frame_size];
+/// Furthest the video view zooms in, manually or when following.
+const MAX_ZOOM: f32 = 16.0;
 
-            match reader.read_exact(&mut buffer) {
-                Ok(_) => {
+/// Centre (in frame pixels) and zoom of the video view, 1 showing the
+/// whole frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ViewCamera {
+    center: egui::Pos2,
+    zoom: f32,
+}
 
-                     let pos = find_position(&buffer, self.width as usize, self.height as usize);
+impl ViewCamera {
+    /// Seconds for the view to cover about two thirds of the way to its target.
+    const EASE_TIME: f32 = 0.25;
 
-                     if let Some(img) = RgbaImage::from_raw(self.width, self.height, buffer) {
-                         let _ = self.tx.send(AppEvent::FrameReady {
-                             image: img,
-                             width: self.width,
-                             height: self.height,
-                             position: pos,
-                         });
-                     }
-                },
-                Err(_e) => {
+    fn fit(frame_size: egui::Vec2) -> Self {
+        Self { center: (frame_size / 2.0).to_pos2(), zoom: 1.0 }
+    }
 
-                }
-            }
+    /// The part of a `frame_size` frame on screen, in frame pixels. Never
+    /// extends past the frame, so the image can't be panned out of view.
+    fn visible(&self, frame_size: egui::Vec2) -> egui::Rect {
+        let size = frame_size / self.zoom;
+        let min = (self.center.to_vec2() - size / 2.0).clamp(egui::Vec2::ZERO, frame_size - size);
+        egui::Rect::from_min_size(min.to_pos2(), size)
+    }
+
+    /// Moves part of the way to `target` for a frame `dt` seconds long, so
+    /// following the cursor doesn't jitter with every pixel of detection
+    /// noise. Returns whether it is still moving.
+    fn ease_towards(&mut self, target: ViewCamera, dt: f32) -> bool {
+        if self.center.distance(target.center) < 0.05 && (self.zoom - target.zoom).abs() < 0.001 {
+            *self = target;
+            return false;
         }
+        let k = 1.0 - (-dt / Self::EASE_TIME).exp();
+        self.center += (target.center - self.center) * k;
+        self.zoom += (target.zoom - self.zoom) * k;
+        true
     }
 }
 
-fn probe_file(path: &Path) -> Result<(f64, u32, u32), String> {
-    let binary = if cfg!(windows) { "ffmpeg" } else { "./ffmpeg" };
-    let output = Command::new(binary)
-        .arg("-i")
-        .arg(path.to_str().unwrap())
-        .stdout(Stdio::null())
-        .stderr(Stdio::piped())
-        .spawn()
-        .and_then(|c| c.wait_with_output())
-        .map_err(|e| e.to_string())?;
+/// One of several traces of the same video, such as a second pass or a
+/// hand-corrected copy. The active layer's points and cuts live in
+/// `positions` and `trim_cuts`, so everything that works on the trace works
+/// on it; the others wait here, drawn in their colour.
+struct TraceLayer {
+    name: String,
+    color: egui::Color32,
+    visible: bool,
+    points: Vec<TracePoint>,
+    trim_cuts: Vec<u64>,
+}
+
+impl TraceLayer {
+    fn new(name: String, color: egui::Color32, points: Vec<TracePoint>) -> Self {
+        Self { name, color, visible: true, points, trim_cuts: Vec::new() }
+    }
+}
 
-    let stderr = String::from_utf8_lossy(&output.stderr);
+/// A trace loaded from another tool, drawn on top of the video for comparison.
+struct ImportedTrace {
+    name: String,
+    points: Vec<TracePoint>,
+    color: egui::Color32,
+    visible: bool,
+    time_offset: f64,
+    time_scale: f64,
+    /// Added to the positions after scaling them by `pos_scale` about the
+    /// frame origin, to line the trace up with the video.
+    pos_shift: [f32; 2],
+    pos_scale: f32,
+    /// Provenance header found in the file, if it has one.
+    metadata: Vec<(String, String)>,
+    show_info: bool,
+}
 
-    let dur_regex = Regex::new(r"Duration: (\d{2}):(\d{2}):(\d{2}\.\d+)").unwrap();
-    let mut duration = 0.0;
-    if let Some(caps) = dur_regex.captures(&stderr) {
-        let h: f64 = caps[1].parse().unwrap_or(0.0);
-        let m: f64 = caps[2].parse().unwrap_or(0.0);
-        let s: f64 = caps[3].parse().unwrap_or(0.0);
-        duration = h * 3600.0 + m * 60.0 + s;
+impl ImportedTrace {
+    fn aligned_time(&self, t: f64) -> f64 {
+        t * self.time_scale + self.time_offset
     }
 
-    let res_regex = Regex::new(r"Video:.* (\d{3,})x(\d{3,})").unwrap();
-    let mut width = 0;
-    let mut height = 0;
-    if let Some(caps) = res_regex.captures(&stderr) {
-        width = caps[1].parse().unwrap_or(0);
-        height = caps[2].parse().unwrap_or(0);
+    fn aligned_pos(&self, pos: [f32; 2]) -> [f32; 2] {
+        [pos[0] * self.pos_scale + self.pos_shift[0], pos[1] * self.pos_scale + self.pos_shift[1]]
     }
 
-    if width > 0 && height > 0 {
-        Ok((duration, width, height))
-    } else {
-        Err("Could not parse video metadata".to_string())
+    /// The points placed in the video, their times scaled but not yet
+    /// offset, for [`align::distances`].
+    fn placed(&self) -> Vec<(f64, [f32; 2])> {
+        self.points.iter().map(|p| (p.time * self.time_scale, self.aligned_pos(p.pos))).collect()
     }
 }
 
-fn video_worker(rx: Receiver<AppCommand>, tx: Sender<AppEvent>) {
-    let mut worker = VideoWorker::new(rx, tx);
-    worker.run();
+/// Auto-align searches this many seconds either side of the current offset.
+const ALIGN_REACH: f64 = 2.0;
+
+/// Most Steps sent in one UI update during playback.
+const MAX_STEPS_PER_UPDATE: u64 = 4;
+
+/// Steps due `elapsed` seconds after the playback clock, at one per
+/// `interval`, capped at [`MAX_STEPS_PER_UPDATE`] and at `remaining`, with
+/// how many seconds the clock moves on by. `None` when playback fell too far
+/// behind to catch up, and the clock starts again from now.
+fn steps_due(elapsed: f64, interval: f64, remaining: Option<u64>) -> (u64, Option<f64>) {
+    let behind = elapsed / interval;
+    let due = (behind as u64).min(MAX_STEPS_PER_UPDATE).min(remaining.unwrap_or(u64::MAX));
+    let advance = (behind < (MAX_STEPS_PER_UPDATE + 1) as f64).then_some(due as f64 * interval);
+    (due, advance)
 }
 
-fn find_position(data: &[u8], width: usize, height: usize) -> Option<[f32; 2]> {
-    let stride = width * 4;
-    let lim_max = 210;
-    let lim_min = 90;
-    let lim_k_min = 130;
+/// Key a video's calibration is saved under: its path, as text so the
+/// settings stay valid JSON whatever the path holds.
+fn calibration_key(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
 
-    let scan_limit_y = height.saturating_sub(20);
-    let scan_limit_x = width.saturating_sub(1);
+/// The `monitor`-sized tile of the desktop holding `point`: where the monitor
+/// showing it is, if monitors sit side by side at the same size, as they
+/// mostly do.
+fn monitor_cell(point: egui::Pos2, monitor: egui::Vec2) -> egui::Rect {
+    let min = egui::pos2((point.x / monitor.x).floor() * monitor.x, (point.y / monitor.y).floor() * monitor.y);
+    egui::Rect::from_min_size(min, monitor)
+}
 
-    for y in 0..scan_limit_y {
-        for x in 1..scan_limit_x {
-            let i = (y * width + x) * 4;
-            if data[i] < lim_max || data[i+1] < lim_max || data[i+2] < lim_max {
-                continue;
-            }
-            if data[i+4] >= lim_k_min || data[i+5] >= lim_k_min || data[i+6] >= lim_k_min {
-                continue;
-            }
-            let mut spine_ok = true;
-            for j in 1..13 {
-                let idx = i + j * stride;
-                if data[idx] <= lim_max || data[idx+1] <= lim_max || data[idx+2] <= lim_max {
-                    spine_ok = false;
-                    break;
-                }
-            }
-            if !spine_ok { continue; }
-            let mut left_ok = true;
-            for j in 0..13 {
-                let idx = i + j * stride - 4;
-                if data[idx] >= lim_min || data[idx+1] >= lim_min || data[idx+2] >= lim_min {
-                    left_ok = false;
-                    break;
-                }
-            }
-            if !left_ok { continue; }
-            let mut diag_score = 0;
-            for d in 1..8 {
-                let d_idx = i + d * stride + d * 4;
-                if data[d_idx] > 160 || data[d_idx+1] > 160 || data[d_idx+2] > 160 {
-                    diag_score += 1;
-                }
-            }
-            if diag_score < 4 { continue; }
-            return Some([x as f32, y as f32]);
-        }
-    }
-    None
+/// Most earlier frames onion skinning shows, and so keeps.
+const MAX_ONION_FRAMES: usize = 8;
+
+/// Frames the worker keeps for stepping back without re-decoding, until
+/// changed in the View settings.
+const DEFAULT_FRAME_CACHE: usize = 30;
+
+/// A second video shown beside the main one, either following its
+/// transport or played on its own.
+struct CompareVideo {
+    path: PathBuf,
+    cmd_tx: Sender<AppCommand>,
+    event_rx: Receiver<AppEvent>,
+    texture: Option<egui::TextureHandle>,
+    duration: f64,
+    fps: f64,
+    current_time: f64,
+    current_frame: u64,
+    positions: Vec<TracePoint>,
+    /// Ran out of frames before the main video; the last frame stays up.
+    ended: bool,
+    /// Frames from before the last pause are dropped.
+    epoch: u64,
+    /// Play, pause, steps and seeks of the main video move this one too.
+    linked: bool,
+    /// This video's time minus the main video's at the same moment, so
+    /// linked seeks land on matching frames.
+    offset: f64,
+    /// Detection here is tuned separately from the main video's.
+    detection: DetectionParams,
+    /// Playing on its own while unlinked, and when it last stepped.
+    playing: bool,
+    last_step: Instant,
+    /// Above the main video rather than beside it.
+    stacked: bool,
 }
 
-fn main() -> eframe::Result<()> {
-    env_logger::init();
+impl CompareVideo {
+    fn open(path: PathBuf, detection: DetectionParams, scope: ScanScope, range: Option<[f64; 2]>, time: f64, frame_cache: usize) -> Self {
+        let (cmd_tx, cmd_rx) = unbounded();
+        let (event_tx, event_rx) = unbounded();
+        thread::spawn(move || video_worker(cmd_rx, event_tx));
+
+        let [start, end] = range.unwrap_or([0.0, f64::INFINITY]);
+        for cmd in [
+            AppCommand::SetDetection(detection),
+            AppCommand::SetScope(scope),
+            AppCommand::SetRange(start, end),
+            AppCommand::SetFrameCache(frame_cache),
+            AppCommand::LoadFile(path.clone()),
+            AppCommand::Seek(time),
+        ] {
+            let _ = cmd_tx.send(cmd);
+        }
+
+        Self {
+            path,
+            cmd_tx,
+            event_rx,
+            texture: None,
+            duration: 0.0,
+            fps: DEFAULT_FPS,
+            current_time: 0.0,
+            current_frame: 0,
+            positions: Vec::new(),
+            ended: false,
+            epoch: 0,
+            linked: true,
+            offset: 0.0,
+            detection,
+            playing: false,
+            last_step: Instant::now(),
+            stacked: false,
+        }
+    }
+
+    /// What a command for the main worker becomes for this one: transport
+    /// only while linked, with times shifted by `offset`. Its detection
+    /// settings are its own.
+    fn follow(&self, cmd: &AppCommand) -> Option<AppCommand> {
+        let shift = |t: f64| (t + self.offset).max(0.0);
+        match cmd {
+            AppCommand::SetDetection(_) => None,
+            AppCommand::SetScope(_) | AppCommand::SetFrameCache(_) | AppCommand::SetProbeTimeout(_) => Some(cmd.clone()),
+            _ if !self.linked => None,
+            AppCommand::Seek(t) => Some(AppCommand::Seek(shift(*t))),
+            AppCommand::Preview(t) => Some(AppCommand::Preview(shift(*t))),
+            AppCommand::SetRange(start, end) => Some(AppCommand::SetRange(shift(*start), shift(*end))),
+            _ => Some(cmd.clone()),
+        }
+    }
+
+    /// Stops playing on its own, dropping frames still in flight.
+    fn pause(&mut self) {
+        if self.playing {
+            self.epoch += 1;
+            let _ = self.cmd_tx.send(AppCommand::Pause { epoch: self.epoch, frame: self.current_frame });
+        }
+        self.playing = false;
+    }
+
+    /// Steps when due while playing on its own, at `speed` times its frame rate.
+    fn play(&mut self, ctx: &egui::Context, speed: f64) {
+        if !self.playing {
+            return;
+        }
+        if self.ended {
+            self.pause();
+            return;
+        }
+        let interval = 1.0 / (self.fps.max(1.0) * speed);
+        if self.last_step.elapsed().as_secs_f64() >= interval {
+            let _ = self.cmd_tx.send(AppCommand::Step);
+            self.last_step = Instant::now();
+        }
+        ctx.request_repaint();
+    }
+
+    /// Returns the errors the worker reported, for the app to show.
+    fn handle_events(&mut self, ctx: &egui::Context) -> Vec<String> {
+        let mut errors = Vec::new();
+        while let Ok(event) = self.event_rx.try_recv() {
+            match event {
+                AppEvent::FrameReady { epoch, .. } if epoch < self.epoch => {}
+                AppEvent::FrameReady { image, width, height, frame, time, position, angle, preview, .. } => {
+                    let color_image = egui::ColorImage::from_rgba_unmultiplied(
+                        [width as usize, height as usize],
+                        image.as_flat_samples().as_slice(),
+                    );
+                    self.texture = Some(ctx.load_texture("compare_frame", color_image, egui::TextureOptions::LINEAR));
+                    if let (Some(pos), false) = (position, preview) {
+                        self.positions.push(TracePoint::new(frame, time, pos).with_angle(angle));
+                    }
+                    self.current_time = time;
+                    self.current_frame = frame;
+                    self.ended = false;
+                }
+                AppEvent::Metadata { duration, fps, .. } => {
+                    self.duration = duration;
+                    self.fps = fps;
+                }
+                AppEvent::LoadStarted { .. } | AppEvent::Diagnosis { .. } | AppEvent::FrameCache { .. }
+                | AppEvent::Stats(_) | AppEvent::Log { .. } | AppEvent::Waveform { .. }
+                | AppEvent::RescanProgress(_) | AppEvent::Rescanned(_) | AppEvent::VideoStreams { .. } => {}
+                AppEvent::EndOfStream => self.ended = true,
+                AppEvent::Error(msg) => errors.push(msg),
+            }
+        }
+        errors
+    }
+}
+
+/// Audio peaks closer than this many waveform windows count as one.
+const PEAK_SPACING: usize = 10;
+/// How near a click on the waveform has to be to a peak to seek to it.
+const PEAK_PICK_PX: f64 = 8.0;
+
+/// ffmpeg log lines kept, oldest dropped first.
+const FFMPEG_LOG_LINES: usize = 2000;
+
+/// Errors kept for the error list, oldest dropped first.
+const ERROR_HISTORY: usize = 50;
+/// How long a non-fatal error shows before it fades out of view.
+const TOAST_SECS: f32 = 6.0;
+
+struct ErrorEntry {
+    at: Instant,
+    message: String,
+}
+
+/// How far the running Magic scan has got, for its progress bar.
+struct MagicProgress {
+    started: Instant,
+    /// Video time the scan started at.
+    from: f64,
+    frames: u64,
+    detections: usize,
+    /// The positions before the scan, put back if it is cancelled.
+    before: Vec<TracePoint>,
+}
+
+struct ImportDialog {
+    table: CsvTable,
+    mapping: ColumnMapping,
+}
+
+/// A reference point clicked on the frame and its known playfield position.
+struct RefPoint {
+    frame: [f64; 2],
+    playfield: [f64; 2],
+}
+
+/// A named moment in the video, marked while reviewing.
+#[derive(Clone, serde::Serialize)]
+struct Bookmark {
+    time: f64,
+    name: String,
+}
+
+/// A note on a stretch of the video, drawn as a band under the timeline.
+#[derive(Clone, serde::Serialize)]
+struct Annotation {
+    start: f64,
+    end: f64,
+    note: String,
+    color: [u8; 3],
+}
+
+/// The row each annotation's band goes in, so overlapping ones stack:
+/// in order of start, each takes the first row free by then.
+fn annotation_lanes(annotations: &[Annotation]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..annotations.len()).collect();
+    order.sort_by(|&a, &b| annotations[a].start.total_cmp(&annotations[b].start));
+    let mut lane_ends: Vec<f64> = Vec::new();
+    let mut lanes = vec![0; annotations.len()];
+    for i in order {
+        let a = &annotations[i];
+        match lane_ends.iter().position(|&end| end <= a.start) {
+            Some(lane) => {
+                lane_ends[lane] = a.end;
+                lanes[i] = lane;
+            }
+            None => {
+                lanes[i] = lane_ends.len();
+                lane_ends.push(a.end);
+            }
+        }
+    }
+    lanes
+}
+
+/// Points taken out of the trace in one go, for undo.
+struct TrimEdit {
+    removed: Vec<TracePoint>,
+    /// Entries added to `trim_cuts`.
+    cuts: Vec<u64>,
+}
+
+struct CalibrationTool {
+    points: Vec<RefPoint>,
+    model: TransformModel,
+    /// Whether clicks on the video add reference points.
+    picking: bool,
+}
+
+impl CalibrationTool {
+    fn fit(&self) -> Result<Calibration, String> {
+        let pairs: Vec<_> = self.points.iter().map(|p| (p.frame, p.playfield)).collect();
+        calibration::fit(self.model, &pairs)
+    }
+}
+
+struct VideoApp {
+
+    file_path: Option<PathBuf>,
+    speed: f64,
+    interval_ms: u64,
+    is_simulating: bool,
+    last_sim_time: Instant,
+    magic_step: MagicStep,
+    /// When the current or last Magic run started, and the step and
+    /// interval it ran with, for timestamping and describing its samples.
+    magic_run: Option<(Instant, MagicStep, u64)>,
+    magic_progress: Option<MagicProgress>,
+
+    /// Where the timeline slider was dragged to, until its frame is shown.
+    scrub_target: Option<f64>,
+    /// The time of the [`AppCommand::Preview`] in flight. Only one is sent
+    /// at a time, so dragging skips frames rather than queueing seeks.
+    preview_sent: Option<f64>,
+    /// What was detected on the frame last shown by scrubbing. It is only
+    /// added to the positions when the user keeps it.
+    preview_point: Option<TracePoint>,
+
+    is_playing: bool,
+    last_play_frame: Instant,
+    /// When set, playback pauses by itself after this many more frames.
+    play_steps_remaining: Option<u64>,
+    /// Bumped on every pause, see `pause_playback`.
+    play_epoch: u64,
+    play_to_time: f64,
+    play_frame_count: u64,
+    /// In and out points being edited; the worker only gets them on "Set range".
+    range: [f64; 2],
+    /// What the worker was last sent, for export metadata.
+    applied_range: Option<[f64; 2]>,
+
+    texture: Option<egui::TextureHandle>,
+    prev_texture: Option<egui::TextureHandle>,
+    /// Upload frames with NEAREST filtering, so zoomed-in pixels stay
+    /// square instead of blurring into each other.
+    sharp_pixels: bool,
+    /// The current frame's pixels, shared with `texture`, for the loupe and
+    /// the pixel readout.
+    frame_image: Option<Arc<egui::ColorImage>>,
+    /// The current frame again with NEAREST filtering, uploaded on demand.
+    loupe_texture: Option<egui::TextureHandle>,
+    loupe: bool,
+    /// Small overview of the whole frame and trail with the part zoomed
+    /// into marked, for panning.
+    show_minimap: bool,
+    minimap: Option<Minimap>,
+    /// Tooltip with the frame pixel under the mouse, its colour and the
+    /// distance to the nearest detection.
+    pixel_readout: bool,
+    /// Frames the worker keeps for stepping back, and how many it holds.
+    frame_cache: usize,
+    frame_cache_used: usize,
+    /// The file being probed and since when, until its metadata or an
+    /// error arrives.
+    loading: Option<(PathBuf, Instant)>,
+    /// Seconds the worker waits for a probe before giving up.
+    probe_timeout: f64,
+    /// The loaded file's video streams, and the one decoded.
+    video_streams: Vec<VideoStream>,
+    video_stream: usize,
+    /// Timings overlay in the corner of the video (F12).
+    show_stats: bool,
+    /// Everything drawn over the video at once (O), over `layers`.
+    show_overlays: bool,
+    layers: Layers,
+    worker_stats: WorkerStats,
+    /// Smoothed time between UI frames in milliseconds.
+    ui_frame_ms: f64,
+    /// Frames that arrived but were replaced by a newer one before drawing.
+    frames_not_drawn: u64,
+    /// Frame pixel under the mouse while it is over the video.
+    hover_frame_pos: Option<egui::Pos2>,
+    /// Cross-fade from the previous frame to the current one over each
+    /// playback step, so slow motion doesn't stutter.
+    blend_frames: bool,
+    /// Faint copies of the frames before the current one over it, for
+    /// judging motion while stepping. Left out during playback and Magic.
+    onion_skin: bool,
+    onion_frames: usize,
+    /// Opacity of the previous frame; each earlier one gets `onion_falloff`
+    /// times the opacity of the one after it.
+    onion_opacity: f32,
+    onion_falloff: f32,
+    /// Recently shown frames, newest last, at most one per frame number.
+    onion_history: VecDeque<(u64, Arc<egui::ColorImage>)>,
+    /// The composite for the current frame, and the frame and settings it
+    /// was made with.
+    onion_texture: Option<((u64, usize, f32, f32), egui::TextureHandle)>,
+    display_mode: DisplayMode,
+    diff_gain: f32,
+    diff_threshold: u8,
+    /// The frame shown before the current one, which differences are taken
+    /// against, with its number.
+    diff_from: Option<(u64, Arc<egui::ColorImage>)>,
+    /// The difference image for the current frame, and the frame and
+    /// settings it was made with.
+    diff_texture: Option<((u64, DisplayMode, f32, u8), egui::TextureHandle)>,
+    current_frame_size: [u32; 2],
+    video_duration: f64,
+    video_fps: f64,
+    current_time: f64, 
+    current_frame: u64,
+
+    positions: Vec<TracePoint>,
+    /// Indices into `positions` picked in the table.
+    selected: BTreeSet<usize>,
+    /// Where a shift-click range selection starts.
+    select_anchor: Option<usize>,
+    show_table: bool,
+    table: TableView,
+    /// Frame range for selecting in the table.
+    select_frames: [u64; 2],
+    /// Whether clicks on the trail select a range of points to delete.
+    trimming: bool,
+    /// First point of the range while picking the second.
+    trim_from: Option<usize>,
+    /// The trace's points by position, for finding the one under the mouse.
+    point_grid: PointGrid,
+    /// The point the trail's context menu was opened on.
+    menu_point: Option<usize>,
+    /// Join the ends of a deleted range instead of leaving a break.
+    stitch_trims: bool,
+    /// First frame of each deleted range the trail doesn't reconnect
+    /// across, in ascending order.
+    trim_cuts: Vec<u64>,
+    trim_undo: Vec<TrimEdit>,
+    trace_layers: Vec<TraceLayer>,
+    active_layer: usize,
+    /// Export every layer to its own file instead of only the active one.
+    export_all_layers: bool,
+    /// Names of the recording segments, indexed by `TracePoint::segment`.
+    segments: Vec<String>,
+    /// Segment new detections go into. While segments exist and none is
+    /// recording, detections are not kept.
+    recording: Option<u32>,
+    show_segments: bool,
+    /// Named moments of the loaded video in time order, ticked on the
+    /// timeline.
+    bookmarks: Vec<Bookmark>,
+    show_bookmarks: bool,
+    /// Notes on time ranges, in the order they were made.
+    annotations: Vec<Annotation>,
+    show_annotations: bool,
+    /// Export one file per segment instead of one with a segment column.
+    export_split_segments: bool,
+    /// Whether clicks on the video place measurement endpoints.
+    measuring: bool,
+    /// First endpoint of the measurement being placed, in frame pixels.
+    measure_from: Option<[f64; 2]>,
+    /// Pairs of endpoints in frame pixels, so they stay put across frames.
+    measurements: Vec<[[f64; 2]; 2]>,
+    show_plots: bool,
+    show_speed_plot: bool,
+    speed_series: SpeedSeries,
+    /// Speeds above this are shaded and listed as bursts.
+    speed_threshold: f64,
+    shade_bursts: bool,
+    show_histogram: bool,
+    histogram: SpeedHistogram,
+    histogram_bins: usize,
+    histogram_log: bool,
+    show_direction: bool,
+    direction: DirectionAnalysis,
+    direction_params: DirectionParams,
+    mark_turns: bool,
+    gap_markers: bool,
+    /// Pixels from an edge within which a detection before a gap means the
+    /// cursor left the frame.
+    edge_margin: f32,
+    gap_analysis: GapAnalysis,
+    show_flicks: bool,
+    /// Jerk in px/s³ from which a change of acceleration counts as a flick.
+    jerk_threshold: f64,
+    flick_analysis: FlickAnalysis,
+    show_strokes: bool,
+    strokes: Strokes,
+    stroke_params: StrokeParams,
+    show_waveform: bool,
+    /// Time and level of the loaded file's audio, [`audio::WAVEFORM_RATE`]
+    /// per second and empty if it has no audio, once they have arrived.
+    waveform: Option<Vec<[f64; 2]>>,
+    waveform_requested: bool,
+    /// Peaks at least this fraction of the loudest window are marked.
+    peak_threshold: f32,
+    waveform_peaks: Vec<usize>,
+
+    /// Hand-placed ground-truth positions keyed by frame.
+    annotating: bool,
+    labels: BTreeMap<u64, TracePoint>,
+    /// Frames received and frames detection ran on since positions were
+    /// last cleared, for the run sidecar.
+    frames_seen: u64,
+    frames_scanned: u64,
+    /// Of those, the frames scanned while each segment was recording.
+    segment_scanned: Vec<u64>,
+    /// Whether those frames came in order from the in point with the same
+    /// settings throughout, so reaching the end makes a pass worth caching.
+    unbroken_pass: bool,
+    /// First and last of those frames, the stretch Rescan goes over.
+    traversed: Option<[u64; 2]>,
+    /// Every frame detection ran on among those, so the misses can be
+    /// stepped through; `positions` only has the hits.
+    frame_results: BTreeMap<u64, FrameResult>,
+    /// When the running rescan started and how far it has got, 0 to 1.
+    rescan: Option<(Instant, f64)>,
+    cache: Option<DetectionCache>,
+    /// A cached pass matching the opened file, until loaded or dismissed.
+    cache_offer: Option<CachedRun>,
+    /// SHA-256 of the loaded file for the run sidecar, or why it couldn't
+    /// be read. `None` while [`VideoApp::hash_source`] is still at it.
+    source_hash: Option<Result<String, String>>,
+    hash_tx: Sender<(PathBuf, Result<String, String>)>,
+    hash_rx: Receiver<(PathBuf, Result<String, String>)>,
+
+    /// Mirrors what the worker was last sent, for export metadata.
+    detection: DetectionParams,
+    /// Where the heuristic turned candidates down on a diagnosed frame.
+    diagnosis: Option<(u64, Rejections)>,
+    scope: ScanScope,
+
+    /// Zoom in on and pan after the detected cursor.
+    follow: bool,
+    follow_zoom: f32,
+    /// Set by Ctrl+scroll and middle-drag while not following, `None` to fit.
+    manual_view: Option<ViewCamera>,
+    /// What is on screen, easing towards the follow or manual view.
+    camera: Option<ViewCamera>,
+
+    overlay_mode: OverlayMode,
+    style: OverlayStyle,
+    grid: GridStyle,
+    heatmap: Heatmap,
+    /// Only re-uploaded when `heatmap` reports a change.
+    heatmap_texture: Option<egui::TextureHandle>,
+    heatmap_opacity: f32,
+    heatmap_cell: u32,
+    fade_trail: bool,
+    fade_window: f64,
+    trail_window: TrailWindow,
+    trail_color: TrailColor,
+    /// Scale the speed colours to the trace's own slowest and fastest segment.
+    speed_auto: bool,
+    speed_range: [f64; 2],
+
+    kinematics_window: usize,
+    export_kinematics: bool,
+
+    /// Median filter the shown and exported positions over this many
+    /// samples; `positions` itself stays raw.
+    median: bool,
+    median_window: usize,
+    median_cache: MedianCache,
+    simplify: bool,
+    simplify_epsilon: f32,
+    simplify_cache: SimplifyCache,
+    export_simplified: bool,
+    /// Fill gaps of up to `interpolate_max_gap` missing samples on export.
+    export_interpolated: bool,
+    interpolate_max_gap: u64,
+
+    imported: Vec<ImportedTrace>,
+    import_dialog: Option<ImportDialog>,
+    import_mappings: HashMap<String, ColumnMapping>,
+
+    /// The loaded video's calibration, also kept in `calibrations` so it
+    /// comes back when the video is opened again.
+    calibration: Option<Calibration>,
+    calibrations: HashMap<String, Calibration>,
+    calibration_tool: Option<CalibrationTool>,
+    export_playfield: bool,
+    /// Export positions as fractions of the frame size, unless playfield
+    /// coordinates are.
+    export_normalized: bool,
+    /// Show each export in the system file manager once written.
+    reveal_after_export: bool,
+
+    compare: Option<CompareVideo>,
+
+    /// Detections appended to a CSV as they arrive, for long unattended runs.
+    stream: Option<CsvStream>,
+
+    /// Recent errors, newest last.
+    errors: VecDeque<ErrorEntry>,
+    /// An error that leaves the app unable to work, shown until dismissed.
+    banner: Option<String>,
+    /// Something worth telling that isn't an error, shown as a toast.
+    notice: Option<(Instant, String)>,
+    show_errors: bool,
+    /// What ffmpeg wrote while decoding the current file.
+    ffmpeg_log: VecDeque<(LogLevel, String)>,
+    show_ffmpeg_log: bool,
+    /// Lines below this level are hidden in the log window.
+    log_filter: LogLevel,
+    /// For screen sharing: files go by their stem and paths in errors and
+    /// the log lose their folders. Only what is shown changes.
+    privacy: bool,
+
+    /// Where the last file dialog picked a file, to open the next one in.
+    last_dir: Option<PathBuf>,
+    /// What was last written, and a change since with when it was first
+    /// seen, see `save_settings_if_changed`.
+    saved_settings: Settings,
+    settings_changed: Option<(Instant, Settings)>,
+
+    start_fullscreen: bool,
+    window_checked: bool,
+    /// Size of the monitor the window is on, as last reported.
+    monitor_size: Option<egui::Vec2>,
+    /// Last title sent to the window, and when.
+    title: String,
+    title_updated: Instant,
+    /// Borderless fullscreen with only the video showing; the controls
+    /// slide in when the mouse nears the bottom edge.
+    presenting: bool,
+    /// Height of the controls panel when last shown, for that hover zone.
+    controls_height: f32,
+    /// The table, plots and analysis windows are in a window of their own,
+    /// e.g. on a second monitor.
+    detached: bool,
+
+    cmd_tx: Sender<AppCommand>,
+    event_rx: Receiver<AppEvent>,
+}
+
+impl VideoApp {
+    fn new(start_fullscreen: bool, storage: Option<&dyn eframe::Storage>) -> Self {
+        let (cmd_tx, cmd_rx) = unbounded();
+        let (event_tx, event_rx) = unbounded();
+
+        let (hash_tx, hash_rx) = unbounded();
+
+        let download_error = auto_download().err();
+
+        thread::spawn(move || {
+            video_worker(cmd_rx, event_tx);
+        });
+        let settings = Settings::load(storage);
+        for cmd in [
+            AppCommand::SetDetection(settings.detection),
+            AppCommand::SetFrameCache(settings.frame_cache),
+            AppCommand::SetProbeTimeout(Duration::from_secs_f64(settings.probe_timeout.max(0.0))),
+        ] {
+            let _ = cmd_tx.send(cmd);
+        }
+
+        let mut app = Self {
+            file_path: None,
+            speed: settings.speed,
+            interval_ms: settings.interval_ms,
+            is_simulating: false,
+            last_sim_time: Instant::now(),
+            magic_run: None,
+            magic_progress: None,
+            scrub_target: None,
+            preview_sent: None,
+            preview_point: None,
+            magic_step: settings.magic_step,
+            is_playing: false,
+            play_epoch: 0,
+            last_play_frame: Instant::now(),
+            play_steps_remaining: None,
+            play_to_time: 0.0,
+            play_frame_count: 60,
+            range: [0.0, 0.0],
+            applied_range: None,
+            texture: None,
+            prev_texture: None,
+            sharp_pixels: settings.sharp_pixels,
+            frame_image: None,
+            loupe_texture: None,
+            loupe: false,
+            show_minimap: false,
+            minimap: None,
+            pixel_readout: false,
+            frame_cache: settings.frame_cache,
+            loading: None,
+            probe_timeout: settings.probe_timeout,
+            video_streams: Vec::new(),
+            video_stream: 0,
+            show_stats: settings.show_stats,
+            show_overlays: settings.show_overlays,
+            layers: settings.layers,
+            worker_stats: WorkerStats::default(),
+            ui_frame_ms: 0.0,
+            frames_not_drawn: 0,
+            frame_cache_used: 0,
+            hover_frame_pos: None,
+            blend_frames: false,
+            onion_skin: false,
+            onion_frames: 3,
+            onion_opacity: 0.4,
+            onion_falloff: 0.6,
+            onion_history: VecDeque::new(),
+            onion_texture: None,
+            display_mode: DisplayMode::Normal,
+            diff_gain: 4.0,
+            diff_threshold: 24,
+            diff_from: None,
+            diff_texture: None,
+            current_frame_size: [0, 0],
+            video_duration: 0.0,
+            video_fps: DEFAULT_FPS,
+            current_time: 0.0,
+            current_frame: 0,
+            positions: Vec::new(),
+            selected: BTreeSet::new(),
+            select_anchor: None,
+            show_table: settings.show_table,
+            table: TableView::default(),
+            select_frames: [0, 0],
+            trimming: false,
+            trim_from: None,
+            point_grid: PointGrid::default(),
+            menu_point: None,
+            stitch_trims: false,
+            trim_cuts: Vec::new(),
+            trim_undo: Vec::new(),
+            trace_layers: vec![TraceLayer::new("Layer 1".to_string(), series_color(0), Vec::new())],
+            active_layer: 0,
+            export_all_layers: false,
+            segments: Vec::new(),
+            recording: None,
+            show_segments: settings.show_segments,
+            bookmarks: Vec::new(),
+            show_bookmarks: settings.show_bookmarks,
+            annotations: Vec::new(),
+            show_annotations: settings.show_annotations,
+            export_split_segments: false,
+            measuring: false,
+            measure_from: None,
+            measurements: Vec::new(),
+            show_plots: settings.show_plots,
+            show_speed_plot: settings.show_speed_plot,
+            speed_series: SpeedSeries::default(),
+            speed_threshold: 1000.0,
+            shade_bursts: true,
+            show_histogram: settings.show_histogram,
+            histogram: SpeedHistogram::default(),
+            histogram_bins: 40,
+            histogram_log: false,
+            show_direction: settings.show_direction,
+            direction: DirectionAnalysis::default(),
+            direction_params: DirectionParams::default(),
+            mark_turns: true,
+            gap_markers: true,
+            edge_margin: 24.0,
+            gap_analysis: GapAnalysis::default(),
+            show_flicks: false,
+            jerk_threshold: 500_000.0,
+            flick_analysis: FlickAnalysis::default(),
+            show_strokes: settings.show_strokes,
+            strokes: Strokes::default(),
+            stroke_params: StrokeParams::default(),
+            show_waveform: settings.show_waveform,
+            waveform: None,
+            waveform_requested: false,
+            peak_threshold: 0.5,
+            waveform_peaks: Vec::new(),
+            annotating: false,
+            labels: BTreeMap::new(),
+            frames_seen: 0,
+            frames_scanned: 0,
+            segment_scanned: Vec::new(),
+            source_hash: None,
+            hash_tx,
+            hash_rx,
+            unbroken_pass: true,
+            traversed: None,
+            frame_results: BTreeMap::new(),
+            rescan: None,
+            cache: eframe::storage_dir(APP_NAME).map(|dir| DetectionCache::new(dir.join("detection_cache"))),
+            cache_offer: None,
+            detection: settings.detection,
+            diagnosis: None,
+            scope: ScanScope::default(),
+            follow: false,
+            follow_zoom: 3.0,
+            manual_view: None,
+            camera: None,
+            overlay_mode: OverlayMode::Trail,
+            style: settings.style,
+            grid: settings.grid,
+            heatmap: Heatmap::default(),
+            heatmap_texture: None,
+            heatmap_opacity: 0.6,
+            heatmap_cell: 16,
+            fade_trail: false,
+            fade_window: 2.0,
+            trail_window: TrailWindow::All,
+            trail_color: TrailColor::Solid,
+            speed_auto: true,
+            speed_range: [0.0, 2000.0],
+            kinematics_window: 1,
+            export_kinematics: false,
+            median: false,
+            median_window: 5,
+            median_cache: MedianCache::default(),
+            simplify: false,
+            simplify_epsilon: 1.0,
+            simplify_cache: SimplifyCache::default(),
+            export_simplified: false,
+            export_interpolated: false,
+            interpolate_max_gap: 5,
+            imported: Vec::new(),
+            import_dialog: None,
+            import_mappings: settings.import_mappings.clone(),
+            calibrations: settings.calibrations.clone(),
+            calibration: None,
+            calibration_tool: None,
+            export_playfield: false,
+            export_normalized: false,
+            reveal_after_export: settings.reveal_after_export,
+            compare: None,
+            stream: None,
+            errors: VecDeque::new(),
+            banner: None,
+            notice: None,
+            show_errors: false,
+            ffmpeg_log: VecDeque::new(),
+            show_ffmpeg_log: settings.show_ffmpeg_log,
+            log_filter: LogLevel::Info,
+            privacy: settings.privacy,
+            detached: settings.detached,
+            last_dir: settings.last_dir.clone(),
+            monitor_size: settings.monitor_size,
+            saved_settings: settings,
+            settings_changed: None,
+            start_fullscreen,
+            window_checked: false,
+            title: APP_NAME.to_string(),
+            title_updated: Instant::now(),
+            presenting: false,
+            controls_height: 0.0,
+            cmd_tx,
+            event_rx,
+        };
+        if let Some(e) = download_error {
+            app.report_error(format!("Failed to download ffmpeg: {}", e), true);
+        }
+        app
+    }
+
+    /// Logs `message` and keeps it for the error list. Fatal errors stay in
+    /// a banner until dismissed, others show briefly as a toast.
+    fn report_error(&mut self, message: String, fatal: bool) {
+        eprintln!("{}", message);
+        if fatal {
+            self.banner = Some(message.clone());
+        }
+        if self.errors.len() == ERROR_HISTORY {
+            self.errors.pop_front();
+        }
+        self.errors.push_back(ErrorEntry { at: Instant::now(), message });
+    }
+
+    fn show_notices(&mut self, ctx: &egui::Context) {
+        let private = self.privacy;
+        if let Some(message) = &self.banner {
+            let message = privacy::shown(message, private);
+            let mut dismiss = false;
+            egui::TopBottomPanel::top("error_banner")
+                .frame(egui::Frame::side_top_panel(&ctx.style()).fill(ctx.style().visuals.error_fg_color.gamma_multiply(0.25)))
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.colored_label(ui.visuals().error_fg_color, "⚠");
+                        ui.add(egui::Label::new(message.as_ref()).selectable(true).wrap());
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            dismiss = ui.button("Dismiss").clicked();
+                            if ui.button("Copy").clicked() {
+                                ui.ctx().copy_text(message.to_string());
+                            }
+                        });
+                    });
+                });
+            if dismiss {
+                self.banner = None;
+            }
+        }
+
+        // the newest error, unless the banner already shows it, or a newer notice
+        let error = self.errors.back()
+            .filter(|e| self.banner.as_ref() != Some(&e.message))
+            .map(|e| (e.at, e.message.as_str(), true));
+        let notice = self.notice.as_ref().map(|(at, message)| (*at, message.as_str(), false));
+        let toast = error.into_iter().chain(notice)
+            .max_by_key(|(at, _, _)| *at)
+            .map(|(at, message, is_error)| (message, is_error, at.elapsed().as_secs_f32()))
+            .filter(|(_, _, age)| *age < TOAST_SECS);
+        if let Some((message, is_error, age)) = toast {
+            let mut open_list = false;
+            egui::Area::new(egui::Id::new("toast"))
+                .anchor(egui::Align2::RIGHT_TOP, [-12.0, 12.0])
+                .order(egui::Order::Foreground)
+                .show(ctx, |ui| {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.set_max_width(360.0);
+                        let message = privacy::shown(message, private);
+                        if is_error {
+                            ui.colored_label(ui.visuals().error_fg_color, message);
+                            open_list = ui.small_button("All errors").clicked();
+                        } else {
+                            ui.label(message);
+                        }
+                    });
+                });
+            self.show_errors |= open_list;
+            ctx.request_repaint_after(Duration::from_secs_f32(TOAST_SECS - age));
+        }
+
+        if !self.show_errors {
+            return;
+        }
+        let mut open = true;
+        let mut clear = false;
+        egui::Window::new("Errors")
+            .open(&mut open)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                if self.errors.is_empty() {
+                    ui.label("No errors.");
+                    return;
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Copy all").clicked() {
+                        let text: Vec<_> = self.errors.iter().map(|e| privacy::shown(&e.message, private)).collect();
+                        ui.ctx().copy_text(text.join("\n"));
+                    }
+                    clear = ui.button("Clear").clicked();
+                });
+                ui.separator();
+                egui::ScrollArea::vertical().max_height(300.0).stick_to_bottom(true).show(ui, |ui| {
+                    for entry in &self.errors {
+                        ui.horizontal_top(|ui| {
+                            ui.weak(format!("{:>4} s ago", entry.at.elapsed().as_secs()));
+                            ui.add(egui::Label::new(privacy::shown(&entry.message, private)).selectable(true).wrap());
+                        });
+                    }
+                });
+            });
+        if clear {
+            self.errors.clear();
+        }
+        self.show_errors = open;
+    }
+
+    fn handle_events(&mut self, ctx: &egui::Context) {
+        let mut frames = 0u64;
+        while let Ok(event) = self.event_rx.try_recv() {
+            match event {
+                // requested before the last pause
+                AppEvent::FrameReady { epoch, .. } if epoch < self.play_epoch => {}
+                AppEvent::FrameReady { image, width, height, frame, time, position, angle, preview, .. } => {
+                    frames += 1;
+                    self.current_frame_size = [width, height];
+
+                    let color_image = Arc::new(egui::ColorImage::from_rgba_unmultiplied(
+                        [width as usize, height as usize],
+                        image.as_flat_samples().as_slice(),
+                    ));
+
+                    // kept around to blend from, see `blend_frames`
+                    self.prev_texture = self.texture.take();
+                    self.texture = Some(ctx.load_texture(
+                        "video_frame",
+                        color_image.clone(),
+                        self.frame_filter(),
+                    ));
+                    // only while on, full frames add up
+                    if self.onion_skin {
+                        self.onion_history.retain(|(f, _)| *f != frame);
+                        if self.onion_history.len() > MAX_ONION_FRAMES {
+                            self.onion_history.pop_front();
+                        }
+                        self.onion_history.push_back((frame, color_image.clone()));
+                    } else {
+                        self.onion_history.clear();
+                    }
+                    // a frame shown again would only differ from itself
+                    if let Some(shown) = self.frame_image.replace(color_image).filter(|_| frame != self.current_frame) {
+                        self.diff_from = Some((self.current_frame, shown));
+                    }
+                    self.loupe_texture = None;
+
+                    if preview {
+                        if self.scrub_target == self.preview_sent.take() {
+                            self.scrub_target = None;
+                        }
+                        self.preview_point = position.map(|pos| TracePoint::new(frame, time, pos).with_angle(angle));
+                        // whatever is recorded next no longer follows on from the pass so far
+                        self.unbroken_pass = false;
+                        self.current_time = time;
+                        self.current_frame = frame;
+                        continue;
+                    }
+                    self.preview_point = None;
+
+                    let expected = match self.frames_seen {
+                        0 => (self.applied_range.map_or(0.0, |r| r[0]) * self.video_fps).round() as u64,
+                        _ => self.current_frame + 1,
+                    };
+                    // the frame paused on, shown again after a pause rewound the worker
+                    let reshown = self.frames_seen > 0 && frame == self.current_frame;
+                    if !reshown {
+                        if frame != expected {
+                            self.unbroken_pass = false;
+                        }
+                        self.traversed = Some(match self.traversed {
+                            Some([first, last]) => [first.min(frame), last.max(frame)],
+                            None => [frame, frame],
+                        });
+                        self.frames_seen += 1;
+                        if self.scope.scans(frame) {
+                            self.frames_scanned += 1;
+                            if let Some(k) = self.recording {
+                                self.segment_scanned[k as usize] += 1;
+                            }
+                            self.frame_results.insert(frame, FrameResult { time, detected: position.is_some() });
+                        }
+                    }
+                    let gated = !self.segments.is_empty() && self.recording.is_none();
+                    if let Some(progress) = self.magic_progress.as_mut().filter(|_| self.is_simulating && !reshown) {
+                        progress.frames += 1;
+                        progress.detections += (position.is_some() && !gated) as usize;
+                    }
+                    if let (Some(pos), false) = (position, gated) {
+                        let sampled_at = self.magic_run
+                            .filter(|_| self.is_simulating)
+                            .map(|(started, _, _)| started.elapsed().as_secs_f64());
+                        let point = TracePoint::new(frame, time, pos).with_angle(angle).with_sampled_at(sampled_at).with_segment(self.recording);
+                        self.record_position(point);
+                        // the row for a frame shown again is already in the stream
+                        if let Some(Err(e)) = self.stream.as_mut().filter(|_| !reshown).map(|s| s.push(&point)) {
+                            self.stream = None;
+                            self.report_error(format!("Streaming stopped: {}", e), false);
+                        }
+                    }
+
+                    self.current_time = time;
+                    self.current_frame = frame;
+                }
+                AppEvent::LoadStarted { path } => {
+                    self.loading = Some((path, Instant::now()));
+                }
+                AppEvent::VideoStreams { streams, selected } => {
+                    self.video_streams = streams;
+                    self.video_stream = selected;
+                }
+                AppEvent::Metadata { duration, width, height, fps } => {
+                    self.loading = None;
+                    self.waveform = None;
+                    self.waveform_requested = false;
+                    self.waveform_peaks.clear();
+                    self.video_duration = duration;
+                    self.video_fps = fps;
+                    self.current_frame_size = [width, height];
+                    self.prev_texture = None;
+                    self.onion_history.clear();
+                    self.onion_texture = None;
+                    self.frame_image = None;
+                    self.minimap = None;
+                    self.diff_from = None;
+                    self.diff_texture = None;
+                    self.range = [0.0, duration];
+                    self.applied_range = None;
+                    self.current_time = 0.0;
+                    self.current_frame = 0;
+                    let key = if self.cache.is_some() { self.cache_key() } else { None };
+                    self.cache_offer = key
+                        .and_then(|key| self.cache.as_ref()?.load(&key))
+                        .filter(|run| !run.points.is_empty());
+                }
+                AppEvent::Diagnosis { frame, rejections } => {
+                    self.diagnosis = Some((frame, rejections));
+                }
+                AppEvent::FrameCache { cached } => {
+                    self.frame_cache_used = cached;
+                }
+                AppEvent::Stats(stats) => {
+                    self.worker_stats = stats;
+                }
+                AppEvent::RescanProgress(done) => {
+                    if let Some((_, progress)) = &mut self.rescan {
+                        *progress = done;
+                    }
+                }
+                AppEvent::Rescanned(points) => {
+                    self.rescan = None;
+                    self.apply_rescan(points);
+                }
+                AppEvent::Waveform { path, levels } => {
+                    // one asked for before another file was opened
+                    if self.file_path.as_ref() == Some(&path) {
+                        let rate = audio::WAVEFORM_RATE as f64;
+                        self.waveform = Some(levels.iter().enumerate().map(|(i, l)| [i as f64 / rate, *l as f64]).collect());
+                        self.find_peaks();
+                    }
+                }
+                AppEvent::Log { level, line } => {
+                    if self.ffmpeg_log.len() == FFMPEG_LOG_LINES {
+                        self.ffmpeg_log.pop_front();
+                    }
+                    self.ffmpeg_log.push_back((level, line));
+                }
+                AppEvent::EndOfStream => {
+                    self.scrub_target = None;
+                    self.preview_sent = None;
+                    if let Some(progress) = self.magic_progress.take().filter(|_| self.is_simulating) {
+                        let message = format!(
+                            "Magic scan finished: {} detections in {} frames, {:.0} s",
+                            progress.detections, progress.frames, progress.started.elapsed().as_secs_f64(),
+                        );
+                        self.notice = Some((Instant::now(), message));
+                    }
+                    self.is_playing = false;
+                    self.play_steps_remaining = None;
+                    self.is_simulating = false;
+                    if self.unbroken_pass && self.frames_seen > 0 {
+                        self.store_cache();
+                    }
+                }
+                AppEvent::Error(msg) => {
+                    self.loading = None;
+                    self.rescan = None;
+                    self.scrub_target = None;
+                    self.preview_sent = None;
+                    // the worker only gives up on a file it cannot read at all
+                    self.report_error(msg, true);
+                }
+            }
+        }
+        // only the newest frame of a batch gets drawn
+        self.frames_not_drawn += frames.saturating_sub(1);
+
+        while let Ok((path, hash)) = self.hash_rx.try_recv() {
+            // a file opened before the current one
+            if self.file_path.as_ref() != Some(&path) {
+                continue;
+            }
+            if let Err(e) = &hash {
+                self.report_error(format!("Cannot hash {}: {}", path.display(), e), false);
+            }
+            self.source_hash = Some(hash);
+        }
+    }
+
+    /// Hashes `path` for the run sidecar on a thread of its own, so a long
+    /// video doesn't hold up the UI and each export doesn't read it again.
+    fn hash_source(&mut self, path: &Path) {
+        self.source_hash = None;
+        let (tx, path) = (self.hash_tx.clone(), path.to_path_buf());
+        thread::spawn(move || {
+            let hash = export::sha256_file(&path).map_err(|e| e.to_string());
+            let _ = tx.send((path, hash));
+        });
+    }
+
+    /// Where the current positions come from, in pixels and unprocessed.
+    fn provenance(&self) -> Provenance {
+        Provenance {
+            source: self.file_path.clone(),
+            duration: self.video_duration,
+            size: self.current_frame_size,
+            fps: self.video_fps,
+            params: self.detection,
+            scope: self.scope,
+            range: self.applied_range,
+            calibration: None,
+            normalized: false,
+            processing: Vec::new(),
+        }
+    }
+
+    fn start_stream(&mut self) {
+        let Some(path) = self.pick_export_path("_live", ExportFormat::Csv) else { return };
+        match CsvStream::create(&path, &self.provenance()) {
+            Ok(stream) => self.stream = Some(stream),
+            Err(e) => self.report_error(format!("Cannot stream to {}: {:#}", path.display(), e), false),
+        }
+    }
+
+    fn stop_stream(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            if let Err(e) = stream.finish() {
+                self.report_error(format!("Closing the stream failed: {}", e), false);
+            }
+        }
+    }
+
+    fn settings(&self) -> Settings {
+        Settings {
+            speed: self.speed,
+            interval_ms: self.interval_ms,
+            magic_step: self.magic_step,
+            detection: self.detection,
+            frame_cache: self.frame_cache,
+            probe_timeout: self.probe_timeout,
+            last_dir: self.last_dir.clone(),
+            reveal_after_export: self.reveal_after_export,
+            sharp_pixels: self.sharp_pixels,
+            style: self.style,
+            grid: self.grid,
+            show_table: self.show_table,
+            show_speed_plot: self.show_speed_plot,
+            show_plots: self.show_plots,
+            show_histogram: self.show_histogram,
+            show_direction: self.show_direction,
+            show_strokes: self.show_strokes,
+            show_waveform: self.show_waveform,
+            show_segments: self.show_segments,
+            show_bookmarks: self.show_bookmarks,
+            show_annotations: self.show_annotations,
+            show_ffmpeg_log: self.show_ffmpeg_log,
+            privacy: self.privacy,
+            show_stats: self.show_stats,
+            show_overlays: self.show_overlays,
+            layers: self.layers,
+            detached: self.detached,
+            monitor_size: self.monitor_size,
+            import_mappings: self.import_mappings.clone(),
+            calibrations: self.calibrations.clone(),
+        }
+    }
+
+    /// Writes the settings once they have stayed put for
+    /// [`SETTINGS_SAVE_DELAY`] after a change, so dragging a slider saves
+    /// once and a crash loses little.
+    fn save_settings_if_changed(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let settings = self.settings();
+        if settings == self.saved_settings {
+            self.settings_changed = None;
+            return;
+        }
+        match &self.settings_changed {
+            Some((since, pending)) if *pending == settings => {
+                if since.elapsed() < SETTINGS_SAVE_DELAY {
+                    return;
+                }
+                if let Some(storage) = frame.storage_mut() {
+                    eframe::App::save(self, storage);
+                    storage.flush();
+                }
+            }
+            _ => {
+                self.settings_changed = Some((Instant::now(), settings));
+                ctx.request_repaint_after(SETTINGS_SAVE_DELAY);
+            }
+        }
+    }
+
+    /// A file dialog opening where the last one picked a file.
+    fn file_dialog(&self) -> rfd::FileDialog {
+        match &self.last_dir {
+            Some(dir) => rfd::FileDialog::new().set_directory(dir),
+            None => rfd::FileDialog::new(),
+        }
+    }
+
+    /// Notes the directory of a file picked in a dialog, for the next one.
+    fn picked(&mut self, path: Option<PathBuf>) -> Option<PathBuf> {
+        if let Some(dir) = path.as_deref().and_then(Path::parent) {
+            self.last_dir = Some(dir.to_path_buf());
+        }
+        path
+    }
+
+    /// Shows a file just exported in the system file manager, if asked to.
+    fn exported(&mut self, path: &Path) {
+        if !self.reveal_after_export {
+            return;
+        }
+        if let Err(e) = reveal(path) {
+            self.report_error(format!("Cannot show {} in the file manager: {}", path.display(), e), false);
+        }
+    }
+
+    /// Asks where to save an export, suggesting `<video stem><suffix>.<ext>`.
+    fn pick_export_path(&mut self, suffix: &str, format: ExportFormat) -> Option<PathBuf> {
+        let stem = self.file_path.as_ref()
+            .and_then(|p| p.file_stem())
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "trace".to_string());
+
+        let path = self.file_dialog()
+            .add_filter(format.label(), &[format.extension()])
+            .set_file_name(format!("{}{}.{}", stem, suffix, format.extension()))
+            .save_file();
+        self.picked(path)
+    }
+
+    fn export(&mut self, content: ExportContent, format: ExportFormat) {
+        let suffix = if content == ExportContent::KinematicsOnly { "_kinematics" } else { "" };
+        let Some(path) = self.pick_export_path(suffix, format) else { return };
+
+        let written = if self.export_all_layers && self.trace_layers.len() > 1 {
+            // `<name>_<layer>.<ext>` next to the picked file
+            let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+            let active = self.active_layer;
+            let mut last_written = None;
+            for k in 0..self.trace_layers.len() {
+                let name = &self.trace_layers[k].name;
+                let mut part: String = name.chars().map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' }).collect();
+                if self.trace_layers.iter().filter(|l| l.name == *name).count() > 1 {
+                    part += &format!("_{}", k + 1);
+                }
+                let path = path.with_file_name(format!("{}_{}.{}", stem, part, format.extension()));
+                self.swap_layer(k);
+                last_written = self.write_export(&path, content, format).or(last_written);
+            }
+            self.swap_layer(active);
+            last_written
+        } else {
+            self.write_export(&path, content, format)
+        };
+        // one folder for all the parts
+        if let Some(path) = written {
+            self.exported(&path);
+        }
+    }
+
+    /// Writes the active layer's trace to `path`, or to files next to it
+    /// when split by segment. Returns the last file written, if any.
+    fn write_export(&mut self, path: &Path, content: ExportContent, format: ExportFormat) -> Option<PathBuf> {
+        let simplified = self.simplify && self.export_simplified;
+        // a simplified trace has gaps on purpose
+        let interpolate = self.export_interpolated && !simplified;
+        let mut processing = Vec::new();
+        if self.median {
+            processing.push(("median_window", self.median_window.into()));
+        }
+        if simplified {
+            processing.push(("simplify_epsilon", self.simplify_epsilon.into()));
+        }
+        if interpolate {
+            processing.push(("interpolate_max_gap", self.interpolate_max_gap.into()));
+        }
+        if content != ExportContent::Positions {
+            processing.push(("kinematics_window", self.kinematics_window.into()));
+        }
+        if self.trace_layers.len() > 1 {
+            processing.push(("layer", self.trace_layers[self.active_layer].name.as_str().into()));
+        }
+        if let Some((_, step, interval_ms)) = self.magic_run.filter(|_| self.positions.iter().any(|p| p.sampled_at.is_some())) {
+            processing.push(("magic_sampling", serde_json::json!({ "clock": step.clock(), "interval_ms": interval_ms })));
+        }
+        let hash = match &self.source_hash {
+            Some(hash) => hash.as_ref().ok().cloned(),
+            None if self.file_path.is_some() => {
+                self.notice = Some((Instant::now(), "The video is still being hashed, so the run sidecar leaves its hash out".to_string()));
+                None
+            }
+            None => None,
+        };
+        let split = self.export_split_segments && !self.segments.is_empty();
+        if !self.segments.is_empty() && !split {
+            processing.push(("segments", self.segments.clone().into()));
+        }
+        if !self.bookmarks.is_empty() {
+            processing.push(("bookmarks", serde_json::json!(self.bookmarks)));
+        }
+        if !self.annotations.is_empty() {
+            processing.push(("annotations", serde_json::json!(self.annotations)));
+        }
+        if self.show_flicks {
+            self.flick_analysis.update(&self.positions, self.kinematics_window, self.jerk_threshold);
+            processing.push(("jerk_threshold", self.jerk_threshold.into()));
+            processing.push(("flicks", serde_json::json!(self.flick_analysis.flicks())));
+        }
+        self.gap_analysis.update(&self.positions, self.current_frame_size, self.edge_margin);
+        if !self.gap_analysis.gaps().is_empty() {
+            processing.push(("edge_margin", self.edge_margin.into()));
+            processing.push(("gaps", serde_json::json!(self.gap_analysis.gaps())));
+        }
+        let calibration = self.calibration.filter(|_| self.export_playfield);
+        // playfield units already don't depend on the resolution
+        let normalized = self.export_normalized && calibration.is_none() && self.current_frame_size[0] > 0;
+        let base = Provenance { calibration, normalized, ..self.provenance() };
+
+        let points = if self.median {
+            self.median_cache.get(&self.positions, self.median_window)
+        } else {
+            &self.positions[..]
+        };
+        let points = if simplified {
+            self.simplify_cache.get(points, self.simplify_epsilon)
+        } else {
+            points
+        };
+        // map before differentiating so kinematics come out in playfield units
+        let mapped;
+        let points = match &calibration {
+            Some(c) => {
+                mapped = c.transform.map_points(points);
+                &mapped[..]
+            }
+            None if normalized => {
+                mapped = export::normalize(points, self.current_frame_size);
+                &mapped[..]
+            }
+            None => points,
+        };
+        // after mapping, so the filled points lie on straight lines in the exported units
+        let filled;
+        let mut interpolated = None;
+        let points = if interpolate {
+            let (points, flags) = export::interpolate_gaps(points, self.interpolate_max_gap);
+            filled = points;
+            interpolated = Some(flags);
+            &filled[..]
+        } else {
+            points
+        };
+        let flag_column = |table: &mut export::Table, flags: &[bool]| {
+            table.push_column("interpolated", flags.iter().map(|&f| serde_json::Value::from(f)));
+        };
+        if split {
+            // `<name>_<segment number>.<ext>` next to the picked file
+            let mut failed = Vec::new();
+            let mut last_written = None;
+            let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+            for (k, name) in self.segments.iter().enumerate() {
+                let indices: Vec<usize> = (0..points.len()).filter(|&i| points[i].segment == Some(k as u32)).collect();
+                if indices.is_empty() {
+                    continue;
+                }
+                let part: Vec<TracePoint> = indices.iter().map(|&i| points[i]).collect();
+                let path = path.with_file_name(format!("{}_{}.{}", stem, k + 1, format.extension()));
+                let mut processing = processing.clone();
+                processing.push(("segment", name.as_str().into()));
+                let provenance = Provenance { processing, ..base.clone() };
+                let mut table = export::build_table(&part, content, self.kinematics_window);
+                if normalized {
+                    table.label_normalized();
+                }
+                let mut detections = part.len();
+                if let Some(flags) = &interpolated {
+                    let flags: Vec<bool> = indices.iter().map(|&i| flags[i]).collect();
+                    detections -= flags.iter().filter(|f| **f).count();
+                    flag_column(&mut table, &flags);
+                }
+                let scanned = self.segment_scanned.get(k).copied().unwrap_or(0);
+                let stats = RunStats { frames: self.frames_seen, scanned, detections };
+                let written = export::write(&path, &table, format, &provenance)
+                    .and_then(|_| export::write_sidecar(&path, &provenance, &stats, hash.as_deref()));
+                match written {
+                    Ok(_) => last_written = Some(path),
+                    Err(e) => failed.push(format!("Export to {} failed: {:#}", path.display(), e)),
+                }
+            }
+            for message in failed {
+                self.report_error(message, false);
+            }
+            return last_written;
+        }
+        let provenance = Provenance { processing, ..base };
+        let mut table = export::build_table(points, content, self.kinematics_window);
+        if normalized {
+            table.label_normalized();
+        }
+        if let Some(flags) = &interpolated {
+            flag_column(&mut table, flags);
+        }
+        let stats = RunStats { frames: self.frames_seen, scanned: self.frames_scanned, detections: self.positions.len() };
+        let written = export::write(path, &table, format, &provenance)
+            .and_then(|_| export::write_sidecar(path, &provenance, &stats, hash.as_deref()));
+        match written {
+            Ok(_) => Some(path.to_path_buf()),
+            Err(e) => {
+                self.report_error(format!("Export to {} failed: {:#}", path.display(), e), false);
+                None
+            }
+        }
+    }
+
+    fn export_labels(&mut self, format: ExportFormat) {
+        let Some(path) = self.pick_export_path("_labels", format) else { return };
+
+        let provenance = Provenance { processing: vec![("annotation", "manual".into())], ..self.provenance() };
+        let points: Vec<TracePoint> = self.labels.values().copied().collect();
+        let table = export::build_table(&points, ExportContent::Positions, self.kinematics_window);
+        match export::write(&path, &table, format, &provenance) {
+            Ok(()) => self.exported(&path),
+            Err(e) => self.report_error(format!("Export to {} failed: {:#}", path.display(), e), false),
+        }
+    }
+
+    /// Arrow keys step, Enter labels the frame and advances, Delete
+    /// removes the frame's label.
+    fn handle_annotation_keys(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() || self.file_path.is_none() {
+            return;
+        }
+        let (next, prev, commit, delete) = ctx.input(|i| (
+            i.key_pressed(egui::Key::ArrowRight),
+            i.key_pressed(egui::Key::ArrowLeft),
+            i.key_pressed(egui::Key::Enter),
+            i.key_pressed(egui::Key::Delete) || i.key_pressed(egui::Key::Backspace),
+        ));
+
+        if delete {
+            self.labels.remove(&self.current_frame);
+        }
+        if commit && !self.labels.contains_key(&self.current_frame) {
+            // unchanged since the previous labelled frame, carry it over
+            if let Some(previous) = self.labels.range(..self.current_frame).next_back().map(|(_, p)| *p) {
+                self.labels.insert(self.current_frame, TracePoint { frame: self.current_frame, time: self.current_time, ..previous });
+            }
+        }
+        if next || commit {
+            self.send(AppCommand::Step);
+        } else if prev && self.current_frame > 0 {
+            self.send(AppCommand::StepBack);
+        }
+    }
+
+    /// Names the loaded file in the window title, with the time while
+    /// playing. The time only moves once a second, to spare the window
+    /// manager.
+    fn update_title(&mut self, ctx: &egui::Context) {
+        let running = self.is_playing || self.is_simulating;
+        if running && self.title_updated.elapsed() < Duration::from_secs(1) {
+            return;
+        }
+        let clock = |s: f64| format!("{}:{:02}", s as u64 / 60, s as u64 % 60);
+        let title = match &self.file_path {
+            None => APP_NAME.to_string(),
+            Some(path) => {
+                let name = privacy::file_name(path, self.privacy);
+                match running {
+                    true => format!("{} {} / {} – {}", name, clock(self.current_time), clock(self.video_duration), APP_NAME),
+                    false => format!("{} – {}", name, APP_NAME),
+                }
+            }
+        };
+        if title != self.title {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(title.clone()));
+            self.title = title;
+            self.title_updated = Instant::now();
+        }
+    }
+
+    /// eframe restores the saved geometry and clamps its size, but only fixes
+    /// the position on Windows. Once the first viewport info is in, shrink a
+    /// window bigger than its monitor and centre it there. egui gives the
+    /// monitor's size but not where it is, so the monitor is taken to be the
+    /// [`monitor_cell`] under the window's centre. Without a monitor size
+    /// there is nothing to go by and the window stays where it was put.
+    ///
+    /// A window saved on a monitor that has since been unplugged opens off
+    /// every screen, and is then said to be on the first monitor. That is
+    /// told by the monitor's size differing from the one saved with the
+    /// position, and the window is centred on the monitor at the desktop's
+    /// origin, where the first one usually is. An unplugged monitor the same
+    /// size as the first goes unnoticed.
+    fn check_window_geometry(&mut self, ctx: &egui::Context) {
+        let (outer_rect, monitor_size) = ctx.input(|i| (i.viewport().outer_rect, i.viewport().monitor_size));
+        let Some(outer_rect) = outer_rect else { return };
+        self.window_checked = true;
+
+        if self.start_fullscreen {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(true));
+            return;
+        }
+
+        let Some(monitor) = monitor_size else { return };
+        let gone = self.monitor_size.is_some_and(|saved| saved != monitor);
+        let too_big = outer_rect.width() > monitor.x || outer_rect.height() > monitor.y;
+        if !too_big && !gone {
+            return;
+        }
+        let mut size = outer_rect.size();
+        if too_big {
+            size = size.min(monitor * 0.9);
+            ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(size));
+        }
+        let screen = if gone { egui::Rect::from_min_size(egui::Pos2::ZERO, monitor) } else { monitor_cell(outer_rect.center(), monitor) };
+        ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(screen.min + (monitor - size) / 2.0));
+    }
+
+    /// Whether the analysis panels have room: presenting hides them, unless
+    /// they are in their own window.
+    fn analysis_shown(&self) -> bool {
+        self.detached || !self.presenting
+    }
+
+    fn analysis_toggles(&mut self, ui: &mut egui::Ui) {
+        ui.toggle_value(&mut self.show_table, "Positions table");
+        ui.toggle_value(&mut self.show_plots, "x/y plots");
+        ui.toggle_value(&mut self.show_speed_plot, "Speed plot");
+        ui.toggle_value(&mut self.show_histogram, "Speed histogram");
+        ui.toggle_value(&mut self.show_direction, "Direction");
+        ui.toggle_value(&mut self.show_strokes, "Strokes");
+        ui.toggle_value(&mut self.show_waveform, "Waveform");
+    }
+
+    fn show_analysis_panels(&mut self, ctx: &egui::Context) {
+        if self.show_histogram {
+            self.show_speed_histogram(ctx);
+        }
+        if self.show_direction {
+            self.show_direction(ctx);
+        }
+        if self.show_strokes {
+            self.show_strokes(ctx);
+        }
+        self.show_positions_table(ctx);
+        self.show_plots(ctx);
+        self.show_speed_plot(ctx);
+        self.show_waveform(ctx);
+    }
+
+    /// The table, plots and analysis windows, in the main window or, while
+    /// detached, in a second one drawn in the same frame, so they work on
+    /// the app's state directly. Closing that window docks them again.
+    fn show_analysis(&mut self, ctx: &egui::Context) {
+        if !self.detached {
+            self.show_analysis_panels(ctx);
+            return;
+        }
+        let viewport = egui::ViewportBuilder::default()
+            .with_title(format!("Analysis – {}", APP_NAME))
+            .with_inner_size([900.0, 700.0]);
+        ctx.show_viewport_immediate(egui::ViewportId::from_hash_of("analysis"), viewport, |ctx, class| {
+            // Embedded: the platform can't open another window
+            if class == egui::ViewportClass::Embedded || ctx.input(|i| i.viewport().close_requested()) {
+                self.detached = false;
+                return;
+            }
+            egui::TopBottomPanel::top("analysis_bar").show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Dock").on_hover_text("Move these back into the main window").clicked() {
+                        self.detached = false;
+                    }
+                    self.analysis_toggles(ui);
+                });
+            });
+            self.show_analysis_panels(ctx);
+            egui::CentralPanel::default().show(ctx, |_| {});
+        });
+    }
+
+    fn set_presenting(&mut self, ctx: &egui::Context, presenting: bool) {
+        self.presenting = presenting;
+        ctx.send_viewport_cmd(egui::ViewportCommand::Decorations(!presenting));
+        ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(presenting || self.start_fullscreen));
+    }
+
+    /// Sends a playback, seek or detection command to the worker, and to
+    /// the compare worker as far as it follows, see [`CompareVideo::follow`].
+    fn send(&self, cmd: AppCommand) {
+        if let Some(compare) = &self.compare {
+            if let Some(cmd) = compare.follow(&cmd) {
+                let _ = compare.cmd_tx.send(cmd);
+            }
+        }
+        let _ = self.cmd_tx.send(cmd);
+    }
+
+    fn open_compare(&mut self) {
+        let path = self.file_dialog().add_filter("Video", &["mp4"]).add_filter("Playlist", &playlist::EXTENSIONS).pick_file();
+        let Some(path) = self.picked(path) else { return };
+        self.compare = Some(CompareVideo::open(path, self.detection, self.scope, self.applied_range, self.current_time, self.frame_cache));
+    }
+
+    /// Linking, alignment, layout and detection of the compare video.
+    fn compare_controls(&mut self, ui: &mut egui::Ui) {
+        let main_time = self.current_time;
+        let main_playing = self.is_playing || self.is_simulating;
+        let Some(compare) = &mut self.compare else { return };
+        let mut resync = false;
+
+        if ui.checkbox(&mut compare.linked, "Link transport")
+            .on_hover_text("Play, pause, steps and seeks of the main video move this one too, offset by the time below")
+            .changed()
+        {
+            if compare.linked {
+                compare.pause();
+                resync = true;
+            } else if main_playing {
+                // carries on by itself from where the main playback left it
+                compare.playing = true;
+                compare.last_step = Instant::now();
+            }
+        }
+        ui.horizontal(|ui| {
+            ui.label("Offset:");
+            resync |= ui.add(egui::DragValue::new(&mut compare.offset).speed(0.01).suffix(" s"))
+                .on_hover_text("Compare video time minus main video time at matching moments")
+                .changed();
+            if ui.button("Align here")
+                .on_hover_text("Take the frames shown now as matching")
+                .clicked()
+            {
+                compare.offset = compare.current_time - main_time;
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut compare.stacked, false, "Side by side");
+            ui.selectable_value(&mut compare.stacked, true, "Stacked");
+        });
+        if resync && compare.linked && !main_playing {
+            let _ = compare.cmd_tx.send(AppCommand::Seek((main_time + compare.offset).max(0.0)));
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("Detection");
+            if ui.small_button("Copy from main").clicked() {
+                compare.detection = self.detection;
+            }
+        });
+        let before = compare.detection;
+        ui.push_id("compare_detection", |ui| detection_grid(ui, &mut compare.detection));
+        if compare.detection != before {
+            let _ = compare.cmd_tx.send(AppCommand::SetDetection(compare.detection));
+        }
+    }
+
+    /// The compare video with its trail. Once it runs out of frames it
+    /// holds the last one, so a shorter video is clamped to its end.
+    fn show_compare(&mut self, ui: &mut egui::Ui) {
+        let Some(compare) = &mut self.compare else { return };
+        let name = privacy::file_name(&compare.path, self.privacy);
+        let status = if compare.ended { " (ended)" } else { "" };
+        ui.label(format!("{}  frame {}  {:.3} s{}", name, compare.current_frame, compare.current_time, status));
+        if !compare.linked {
+            ui.horizontal(|ui| {
+                if ui.small_button("⏴").on_hover_text("Previous frame").clicked() {
+                    compare.pause();
+                    let _ = compare.cmd_tx.send(AppCommand::StepBack);
+                }
+                if ui.small_button(if compare.playing { "Pause" } else { "Play" }).clicked() {
+                    if compare.playing {
+                        compare.pause();
+                    } else {
+                        compare.playing = true;
+                        compare.last_step = Instant::now();
+                    }
+                }
+                if ui.small_button("⏵").on_hover_text("Next frame").clicked() {
+                    compare.pause();
+                    let _ = compare.cmd_tx.send(AppCommand::Step);
+                }
+                let mut t = compare.current_time;
+                ui.spacing_mut().slider_width = (ui.available_width() - 80.0).max(60.0);
+                if ui.add(egui::Slider::new(&mut t, 0.0..=compare.duration.max(0.0)).fixed_decimals(2).suffix(" s")).changed() {
+                    compare.pause();
+                    let _ = compare.cmd_tx.send(AppCommand::Seek(t));
+                }
+            });
+        }
+        let compare = &*compare;
+
+        let Some(tex) = &compare.texture else {
+            ui.centered_and_justified(|ui| ui.label("Loading..."));
+            return;
+        };
+        let tex_size = tex.size_vec2();
+        let (rect, scale) = letterbox(ui, tex_size, self.style.letterbox);
+        ui.painter().image(
+            tex.id(),
+            rect,
+            egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+            egui::Color32::WHITE,
+        );
+
+        let points: Vec<egui::Pos2> = compare.positions.iter()
+            .map(|p| rect.min + egui::vec2(p.pos[0], p.pos[1]) * scale)
+            .collect();
+        draw_trail(ui.painter(), &points, &[], &trail_gaps(&compare.positions), &self.style, scale);
+    }
+
+    /// Adds a detection keeping `positions` in frame order. A frame detected
+    /// again, e.g. after seeking back to it, replaces its earlier point
+    /// rather than appending a duplicate out of order.
+    fn record_position(&mut self, point: TracePoint) {
+        match self.positions.last() {
+            Some(last) if last.frame >= point.frame => {
+                match self.positions.binary_search_by_key(&point.frame, |p| p.frame) {
+                    Ok(i) => {
+                        // same length and last frame, so the caches can't tell
+                        self.positions[i] = point;
+                        self.forget_derived();
+                    }
+                    Err(i) => {
+                        // indices after `i` shift, so a selection no longer holds
+                        self.selected.clear();
+                        self.trim_from = None;
+                        self.positions.insert(i, point);
+                    }
+                }
+            }
+            _ => self.positions.push(point),
+        }
+    }
+
+    fn clear_positions(&mut self) {
+        self.selected.clear();
+        self.trim_from = None;
+        self.trim_cuts.clear();
+        self.trim_undo.clear();
+        self.segments.clear();
+        self.segment_scanned.clear();
+        self.recording = None;
+        self.positions.clear();
+        if let Some(compare) = &mut self.compare {
+            compare.positions.clear();
+        }
+        self.frames_seen = 0;
+        self.frames_scanned = 0;
+        self.unbroken_pass = true;
+        self.traversed = None;
+        self.frame_results.clear();
+    }
+
+    /// A new layer holding imported trace `i` as currently aligned, its
+    /// times moved onto the video's frames.
+    fn imported_to_layer(&mut self, i: usize) {
+        let trace = &self.imported[i];
+        let fps = if self.video_fps > 0.0 { self.video_fps } else { 60.0 };
+        let mut points: Vec<TracePoint> = trace.points.iter()
+            .map(|p| {
+                let time = trace.aligned_time(p.time);
+                TracePoint { frame: (time * fps).round().max(0.0) as u64, time, pos: trace.aligned_pos(p.pos), ..*p }
+            })
+            .collect();
+        points.sort_by_key(|p| p.frame);
+        points.dedup_by_key(|p| p.frame);
+        let layer = TraceLayer::new(trace.name.clone(), trace.color, points);
+        self.trace_layers.push(layer);
+    }
+
+    /// Puts layer `k` into `positions`, the active one back into its place.
+    fn swap_layer(&mut self, k: usize) {
+        let active = &mut self.trace_layers[self.active_layer];
+        active.points = std::mem::take(&mut self.positions);
+        active.trim_cuts = std::mem::take(&mut self.trim_cuts);
+        self.active_layer = k;
+        let layer = &mut self.trace_layers[k];
+        self.positions = std::mem::take(&mut layer.points);
+        self.trim_cuts = std::mem::take(&mut layer.trim_cuts);
+        self.forget_derived();
+    }
+
+    /// Makes layer `k` the one scans record into and edits apply to.
+    fn activate_layer(&mut self, k: usize) {
+        if k == self.active_layer {
+            return;
+        }
+        self.swap_layer(k);
+        // indices and removed points refer to the other layer
+        self.selected.clear();
+        self.trim_from = None;
+        self.trim_undo.clear();
+    }
+
+    fn layer_points(&self, k: usize) -> &[TracePoint] {
+        if k == self.active_layer { &self.positions } else { &self.trace_layers[k].points }
+    }
+
+    fn duplicate_layer(&mut self, k: usize) {
+        let points = self.layer_points(k).to_vec();
+        let trim_cuts = if k == self.active_layer { self.trim_cuts.clone() } else { self.trace_layers[k].trim_cuts.clone() };
+        let name = format!("{} copy", self.trace_layers[k].name);
+        let layer = TraceLayer { trim_cuts, ..TraceLayer::new(name, series_color(self.trace_layers.len()), points) };
+        self.trace_layers.insert(k + 1, layer);
+        if self.active_layer > k {
+            self.active_layer += 1;
+        }
+    }
+
+    /// Removes layer `k`, unless it is the last one. A removed active layer
+    /// hands over to its neighbour.
+    fn delete_layer(&mut self, k: usize) {
+        if self.trace_layers.len() < 2 {
+            return;
+        }
+        if k == self.active_layer {
+            self.activate_layer(if k > 0 { k - 1 } else { 1 });
+        }
+        self.trace_layers.remove(k);
+        if self.active_layer > k {
+            self.active_layer -= 1;
+        }
+    }
+
+    /// Adds layer `k`'s points to the active layer on the frames it has
+    /// none on; where both have one, the active layer's stays.
+    fn merge_layer(&mut self, k: usize) {
+        if k == self.active_layer {
+            return;
+        }
+        let have: BTreeSet<u64> = self.positions.iter().map(|p| p.frame).collect();
+        let added: Vec<TracePoint> = self.trace_layers[k].points.iter().filter(|p| !have.contains(&p.frame)).copied().collect();
+        let count = added.len();
+        self.positions.extend(added);
+        self.positions.sort_by_key(|p| p.frame);
+        self.selected.clear();
+        self.trim_from = None;
+        self.trim_undo.clear();
+        self.notice = Some((Instant::now(), format!("Merged {} points from {}", count, self.trace_layers[k].name)));
+    }
+
+    /// Drops everything worked out from `positions`, which caches only tell
+    /// apart by length and last frame, for when it was swapped for another
+    /// trace.
+    fn forget_derived(&mut self) {
+        self.median_cache = MedianCache::default();
+        self.simplify_cache.clear();
+        self.table.invalidate();
+        self.speed_series = SpeedSeries::default();
+        self.histogram = SpeedHistogram::default();
+        self.direction = DirectionAnalysis::default();
+        self.strokes = Strokes::default();
+        self.gap_analysis = GapAnalysis::default();
+        self.flick_analysis = FlickAnalysis::default();
+        self.point_grid.clear();
+        self.heatmap = Heatmap::default();
+        self.heatmap_texture = None;
+        self.minimap = None;
+    }
+
+    /// Detects again over the frames gone through so far, with the current
+    /// settings. The worker decodes them without showing each.
+    fn start_rescan(&mut self) {
+        let Some([first, last]) = self.traversed else { return };
+        self.pause_playback();
+        self.is_simulating = false;
+        self.rescan = Some((Instant::now(), 0.0));
+        let _ = self.cmd_tx.send(AppCommand::Rescan { start: first as f64 / self.video_fps, end: last as f64 / self.video_fps });
+    }
+
+    /// Replaces the positions in the rescanned stretch with `points`. While
+    /// there are segments, a point only goes back into the one that spanned
+    /// its frame before, as detections outside any aren't kept either.
+    fn apply_rescan(&mut self, points: Vec<TracePoint>) {
+        let Some([first, last]) = self.traversed else { return };
+        let mut spans: Vec<Option<[u64; 2]>> = vec![None; self.segments.len()];
+        for p in &self.positions {
+            if let Some(span) = p.segment.and_then(|k| spans.get_mut(k as usize)) {
+                *span = Some(span.map_or([p.frame, p.frame], |[a, b]| [a.min(p.frame), b.max(p.frame)]));
+            }
+        }
+        let segment_of = |frame: u64| spans.iter().position(|s| s.is_some_and(|[a, b]| (a..=b).contains(&frame)));
+        let found: Vec<TracePoint> = points.into_iter()
+            .filter_map(|p| match self.segments.is_empty() {
+                true => Some(p),
+                false => segment_of(p.frame).map(|k| p.with_segment(Some(k as u32))),
+            })
+            .collect();
+
+        for (frame, result) in self.frame_results.range_mut(first..=last) {
+            result.detected = found.iter().any(|p| p.frame == *frame);
+        }
+        let was = self.positions.iter().filter(|p| (first..=last).contains(&p.frame)).count();
+        let mut positions: Vec<TracePoint> = self.positions.iter().filter(|p| p.frame < first).copied().collect();
+        let now = found.len();
+        positions.extend(found);
+        positions.extend(self.positions.iter().filter(|p| p.frame > last).copied());
+        // indices and removed points refer to the old positions
+        self.selected.clear();
+        self.trim_from = None;
+        self.trim_undo.clear();
+        self.positions = positions;
+        // may have the same length and last frame as before
+        self.forget_derived();
+        self.notice = Some((Instant::now(), format!("Rescan found {} detections, {} before", now, was)));
+    }
+
+    /// Removes the selected points. Unless stitching, the trail breaks
+    /// where each contiguous run of them was.
+    fn delete_selected(&mut self) {
+        if self.selected.is_empty() {
+            return;
+        }
+        let mut edit = TrimEdit { removed: Vec::with_capacity(self.selected.len()), cuts: Vec::new() };
+        let mut previous = None;
+        for &i in &self.selected {
+            let p = self.positions[i];
+            if !self.stitch_trims && previous.is_none_or(|j| j + 1 != i) {
+                edit.cuts.push(p.frame);
+            }
+            edit.removed.push(p);
+            previous = Some(i);
+        }
+        let mut i = 0;
+        self.positions.retain(|_| {
+            i += 1;
+            !self.selected.contains(&(i - 1))
+        });
+        for &frame in &edit.cuts {
+            let at = self.trim_cuts.partition_point(|&c| c < frame);
+            self.trim_cuts.insert(at, frame);
+        }
+        self.trim_undo.push(edit);
+        self.selected.clear();
+        self.select_anchor = None;
+        self.trim_from = None;
+        // an edited trace is not a detection pass
+        self.unbroken_pass = false;
+    }
+
+    /// Puts back the points of the latest deletion, except frames that
+    /// were detected again since.
+    fn undo_trim(&mut self) {
+        let Some(edit) = self.trim_undo.pop() else { return };
+        for p in edit.removed {
+            if let Err(i) = self.positions.binary_search_by_key(&p.frame, |q| q.frame) {
+                self.positions.insert(i, p);
+            }
+        }
+        for frame in edit.cuts {
+            if let Some(at) = self.trim_cuts.iter().position(|&c| c == frame) {
+                self.trim_cuts.remove(at);
+            }
+        }
+        self.selected.clear();
+        self.select_anchor = None;
+        self.trim_from = None;
+    }
+
+    /// Key for the loaded file under the settings last sent to the worker.
+    fn cache_key(&mut self) -> Option<CacheKey> {
+        let path = self.file_path.clone()?;
+        CacheKey::new(&path, &self.detection, &self.scope, self.applied_range)
+            .map_err(|e| self.report_error(format!("Cannot key cache for {}: {}", path.display(), e), false))
+            .ok()
+    }
+
+    fn store_cache(&mut self) {
+        // once per pass, however often the end is hit
+        self.unbroken_pass = false;
+        if self.cache.is_none() {
+            return;
+        }
+        let Some(key) = self.cache_key() else { return };
+        let run = CachedRun {
+            key,
+            frames_seen: self.frames_seen,
+            frames_scanned: self.frames_scanned,
+            points: self.positions.clone(),
+            traversed: self.traversed,
+            frame_results: self.frame_results.clone(),
+            segments: self.segments.clone(),
+            segment_scanned: self.segment_scanned.clone(),
+        };
+        if let Some(Err(e)) = self.cache.as_ref().map(|cache| cache.store(&run)) {
+            self.report_error(format!("Caching detections failed: {:#}", e), false);
+        }
+    }
+
+    fn show_cache_offer(&mut self, ctx: &egui::Context) {
+        let Some(run) = &self.cache_offer else { return };
+
+        let mut open = true;
+        let mut load = false;
+        let mut dismiss = false;
+        egui::Window::new("Cached detections")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "This video was already analysed with the current settings: {} detections in {} frames.",
+                    run.points.len(), run.frames_seen,
+                ));
+                ui.horizontal(|ui| {
+                    load = ui.button("Load").clicked();
+                    dismiss = ui.button("Analyse again").clicked();
+                });
+            });
+
+        if load {
+            if let Some(run) = self.cache_offer.take() {
+                // trims, segments and frame results of whatever was there
+                // would point into the loaded pass
+                self.clear_positions();
+                self.positions = run.points;
+                self.frames_seen = run.frames_seen;
+                self.frames_scanned = run.frames_scanned;
+                self.traversed = run.traversed;
+                self.frame_results = run.frame_results;
+                self.segments = run.segments;
+                self.segment_scanned = run.segment_scanned;
+                // stepping on from here would append to the loaded pass
+                self.unbroken_pass = false;
+                // may have the same length and last frame as before
+                self.forget_derived();
+            }
+        }
+        if !open || dismiss {
+            self.cache_offer = None;
+        }
+    }
+
+    /// Seconds between Steps sent during playback, so that speed 1 plays
+    /// the source in real time.
+    fn play_interval(&self) -> f64 {
+        let fps = if self.video_fps > 0.0 { self.video_fps } else { DEFAULT_FPS };
+        1.0 / (fps * self.speed)
+    }
+
+    /// Starts playback that pauses by itself after `frames` more frames.
+    fn play_frames(&mut self, frames: u64) {
+        if frames == 0 {
+            return;
+        }
+        self.play_steps_remaining = Some(frames);
+        self.is_playing = true;
+        self.is_simulating = false;
+        self.last_play_frame = Instant::now();
+    }
+
+    fn pick_import(&mut self) {
+        let path = self.file_dialog().add_filter("Trace", &["csv", "tsv", "txt"]).pick_file();
+        let Some(path) = self.picked(path) else {
+            return;
+        };
+        match CsvTable::load(&path) {
+            Ok(table) => {
+                let mapping = self.import_mappings.get(&table.shape_key())
+                    .filter(|m| [m.time, m.x, m.y].iter().all(|c| *c < table.headers.len()))
+                    .cloned()
+                    .unwrap_or_else(|| table.guess_mapping());
+                self.import_dialog = Some(ImportDialog { table, mapping });
+            }
+            Err(e) => self.report_error(format!("Cannot import {}: {:#}", path.display(), e), false),
+        }
+    }
+
+    /// The distance from the detected trace to imported trace `i` at each
+    /// detection both cover, as it is aligned now.
+    fn export_distances(&mut self, i: usize) {
+        let Some(path) = self.pick_export_path("_distances", ExportFormat::Csv) else { return };
+
+        let trace = &self.imported[i];
+        let series = align::distances(&self.positions, &trace.placed(), trace.time_offset);
+        let processing = vec![
+            ("compared_with", trace.name.as_str().into()),
+            ("time_offset", trace.time_offset.into()),
+            ("time_scale", trace.time_scale.into()),
+            ("position_shift", serde_json::json!(trace.pos_shift)),
+            ("position_scale", trace.pos_scale.into()),
+        ];
+        let provenance = Provenance { processing, ..self.provenance() };
+        let table = export::Table {
+            columns: vec!["time", "distance"],
+            rows: series.into_iter().map(|(t, d)| vec![t.into(), d.into()]).collect(),
+        };
+        match export::write(&path, &table, ExportFormat::Csv, &provenance) {
+            Ok(()) => self.exported(&path),
+            Err(e) => self.report_error(format!("Export to {} failed: {:#}", path.display(), e), false),
+        }
+    }
+
+    fn show_trace_info(&mut self, ctx: &egui::Context) {
+        for (i, trace) in self.imported.iter_mut().enumerate() {
+            if !trace.show_info {
+                continue;
+            }
+            egui::Window::new(format!("Trace info: {}", trace.name))
+                .id(egui::Id::new(("trace_info", i)))
+                .open(&mut trace.show_info)
+                .show(ctx, |ui| {
+                    egui::Grid::new("metadata").num_columns(2).striped(true).show(ui, |ui| {
+                        for (key, value) in &trace.metadata {
+                            ui.label(key);
+                            ui.add(egui::Label::new(value).wrap());
+                            ui.end_row();
+                        }
+                    });
+                });
+        }
+    }
+
+    fn show_calibration(&mut self, ctx: &egui::Context) {
+        let Some(tool) = &mut self.calibration_tool else { return };
+
+        let mut open = true;
+        let mut apply = None;
+        let mut clear = false;
+
+        egui::Window::new("Calibration")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.toggle_value(&mut tool.picking, "Pick points")
+                        .on_hover_text("Click reference points on the frame, then enter their playfield coordinates");
+                    egui::ComboBox::from_id_salt("calibration_model")
+                        .selected_text(tool.model.label())
+                        .show_ui(ui, |ui| {
+                            for model in TransformModel::ALL {
+                                ui.selectable_value(&mut tool.model, model, model.label());
+                            }
+                        });
+                });
+
+                let mut remove = None;
+                egui::Grid::new("calibration_points").num_columns(4).striped(true).show(ui, |ui| {
+                    ui.label("#");
+                    ui.label("Frame (px)");
+                    ui.label("Playfield");
+                    ui.end_row();
+                    for (i, point) in tool.points.iter_mut().enumerate() {
+                        ui.label(format!("{}", i + 1));
+                        ui.label(format!("{:.1}, {:.1}", point.frame[0], point.frame[1]));
+                        ui.horizontal(|ui| {
+                            ui.add(egui::DragValue::new(&mut point.playfield[0]).speed(0.5));
+                            ui.add(egui::DragValue::new(&mut point.playfield[1]).speed(0.5));
+                        });
+                        if ui.small_button("✖").clicked() {
+                            remove = Some(i);
+                        }
+                        ui.end_row();
+                    }
+                });
+                if let Some(i) = remove {
+                    tool.points.remove(i);
+                }
+
+                let fit = tool.fit();
+                match &fit {
+                    Ok(_) if tool.points.len() == tool.model.min_points() => {
+                        ui.label("Exact fit, add a point to get a residual check");
+                    }
+                    Ok(c) => {
+                        ui.label(format!("Residual: {:.3} RMS, {:.3} max", c.rms_error, c.max_error));
+                    }
+                    Err(e) => {
+                        ui.colored_label(ui.visuals().warn_fg_color, e);
+                    }
+                }
+
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(fit.is_ok(), egui::Button::new("Apply")).clicked() {
+                        apply = fit.ok();
+                    }
+                    if ui.add_enabled(self.calibration.is_some(), egui::Button::new("Clear calibration")).clicked() {
+                        clear = true;
+                    }
+                });
+                if let Some(c) = &self.calibration {
+                    ui.label(format!("Active: {}, {:.3} RMS", c.model.label(), c.rms_error));
+                }
+            });
+
+        let key = self.file_path.as_deref().map(calibration_key);
+        if let Some(c) = apply {
+            self.calibration = Some(c);
+            if let Some(key) = key {
+                self.calibrations.insert(key, c);
+            }
+        } else if clear {
+            self.calibration = None;
+            self.export_playfield = false;
+            if let Some(key) = key {
+                self.calibrations.remove(&key);
+            }
+        }
+        if !open {
+            self.calibration_tool = None;
+        }
+    }
+
+    fn show_import_dialog(&mut self, ctx: &egui::Context) {
+        let private = self.privacy;
+        let Some(dialog) = &mut self.import_dialog else { return };
+
+        let mut open = true;
+        let mut confirmed = false;
+        let mut cancelled = false;
+
+        egui::Window::new("Import trace")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let headers = &dialog.table.headers;
+                let mapping = &mut dialog.mapping;
+
+                ui.label(privacy::shown(&dialog.table.path.display().to_string(), private));
+                ui.label(format!("{} rows", dialog.table.rows.len()));
+
+                egui::Grid::new("import_mapping").num_columns(2).show(ui, |ui| {
+                    for (label, column) in [("Time", &mut mapping.time), ("X", &mut mapping.x), ("Y", &mut mapping.y)] {
+                        ui.label(label);
+                        egui::ComboBox::from_id_salt(label)
+                            .selected_text(headers[*column].as_str())
+                            .show_ui(ui, |ui| {
+                                for (i, header) in headers.iter().enumerate() {
+                                    ui.selectable_value(column, i, header.as_str());
+                                }
+                            });
+                        ui.end_row();
+                    }
+
+                    ui.label("Time unit");
+                    ui.horizontal(|ui| {
+                        ui.radio_value(&mut mapping.time_unit, TimeUnit::Seconds, "Seconds");
+                        ui.radio_value(&mut mapping.time_unit, TimeUnit::Milliseconds, "Milliseconds");
+                    });
+                    ui.end_row();
+
+                    ui.label("Coordinates");
+                    ui.horizontal(|ui| {
+                        ui.radio_value(&mut mapping.coords, CoordSpace::Pixels, "Pixels");
+                        ui.radio_value(&mut mapping.coords, CoordSpace::Normalized, "Normalized");
+                    });
+                    ui.end_row();
+                });
+
+                ui.horizontal(|ui| {
+                    if ui.button("Import").clicked() {
+                        confirmed = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancelled = true;
+                    }
+                });
+            });
+
+        if confirmed {
+            let dialog = self.import_dialog.take().unwrap();
+            self.import_mappings.insert(dialog.table.shape_key(), dialog.mapping.clone());
+
+            let (points, skipped) = dialog.table.to_points(&dialog.mapping, self.current_frame_size, self.video_fps);
+            if skipped > 0 {
+                let message = format!("Import of {} skipped {} unparsable rows", dialog.table.path.display(), skipped);
+                self.report_error(message, false);
+            }
+            let name = dialog.table.path.file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            self.imported.push(ImportedTrace {
+                name,
+                points,
+                color: IMPORT_COLORS[self.imported.len() % IMPORT_COLORS.len()],
+                visible: true,
+                time_offset: 0.0,
+                time_scale: 1.0,
+                pos_shift: [0.0, 0.0],
+                pos_scale: 1.0,
+                metadata: dialog.table.metadata,
+                show_info: false,
+            });
+        } else if cancelled || !open {
+            self.import_dialog = None;
+        }
+    }
+
+    /// Stops playback. Steps sent during it may still be queued, so a new
+    /// epoch starts and the frames they bring are dropped; each worker
+    /// goes back to the frame its video is showing.
+    fn pause_playback(&mut self) {
+        if self.is_playing {
+            self.play_epoch += 1;
+            let epoch = self.play_epoch;
+            let _ = self.cmd_tx.send(AppCommand::Pause { epoch, frame: self.current_frame });
+            if let Some(compare) = self.compare.as_mut().filter(|c| c.linked) {
+                compare.epoch = epoch;
+                let _ = compare.cmd_tx.send(AppCommand::Pause { epoch, frame: compare.current_frame });
+            }
+        }
+        self.is_playing = false;
+        self.play_steps_remaining = None;
+    }
+
+    /// Stops playback and Magic and jumps to `time`, for seeking from the
+    /// table or the plots.
+    fn seek_paused(&mut self, time: f64) {
+        self.pause_playback();
+        self.is_simulating = false;
+        self.send(AppCommand::Seek(time));
+    }
+
+    /// Stops playback and Magic and shows the next or previous frame.
+    /// Stepping back within the worker's frame cache needs no re-decode.
+    fn step_paused(&mut self, forward: bool) {
+        self.pause_playback();
+        self.is_simulating = false;
+        self.send(if forward { AppCommand::Step } else { AppCommand::StepBack });
+    }
+
+    fn show_plots(&mut self, ctx: &egui::Context) {
+        let shown = self.show_plots && self.analysis_shown();
+        egui::TopBottomPanel::bottom("plots").resizable(true).default_height(180.0).show_animated(ctx, shown, |ui| {
+            let positions = &self.positions;
+            let full = match (positions.first(), positions.last()) {
+                (Some(first), Some(last)) => [first.time, last.time],
+                _ => [0.0, self.video_duration],
+            };
+            // zoom with Ctrl+scroll and pan by dragging, only while hovered,
+            // so the arrow keys and the video's own zoom keep working
+            let plot = egui_plot::Plot::new("xy_plot")
+                .legend(egui_plot::Legend::default())
+                .x_axis_label("time (s)")
+                .y_axis_label("px")
+                .show(ui, |plot_ui| {
+                    let range = if plot_ui.auto_bounds().x {
+                        full
+                    } else {
+                        let bounds = plot_ui.plot_bounds();
+                        [bounds.min()[0], bounds.max()[0]]
+                    };
+                    let columns = plot_ui.response().rect.width() as usize;
+                    for (axis, name) in [(0, "x(t)"), (1, "y(t)")] {
+                        let series = plot::min_max(positions, |p| [p.time, p.pos[axis] as f64], range, columns);
+                        plot_ui.line(egui_plot::Line::new(egui_plot::PlotPoints::new(series)).name(name));
+                    }
+                    plot_ui.vline(egui_plot::VLine::new(self.current_time).color(egui::Color32::from_rgb(255, 80, 80)));
+                    plot_ui.pointer_coordinate()
+                });
+            if plot.response.clicked() {
+                if let Some(at) = plot.inner {
+                    self.seek_paused(at.x.clamp(0.0, self.video_duration));
+                }
+            }
+        });
+    }
+
+    fn show_speed_plot(&mut self, ctx: &egui::Context) {
+        let shown = self.show_speed_plot && self.analysis_shown();
+        egui::TopBottomPanel::bottom("speed_plot").resizable(true).default_height(200.0).show_animated(ctx, shown, |ui| {
+            let transform = self.calibration.filter(|_| self.export_playfield).map(|c| c.transform);
+            let unit = if transform.is_some() { "units/s" } else { "px/s" };
+            self.speed_series.update(&self.positions, self.kinematics_window, transform, self.speed_threshold);
+
+            ui.horizontal(|ui| {
+                ui.label("Threshold:");
+                ui.add(egui::DragValue::new(&mut self.speed_threshold).speed(10.0).range(0.0..=f64::MAX).suffix(format!(" {}", unit)));
+                ui.checkbox(&mut self.shade_bursts, "Shade above");
+                ui.label(format!("{} bursts", self.speed_series.bursts().len()));
+                ui.add_enabled_ui(!self.speed_series.bursts().is_empty(), |ui| {
+                    for format in [ExportFormat::Csv, ExportFormat::Json] {
+                        if ui.button(format!("Export bursts {}", format.label())).clicked() {
+                            self.export_bursts(format);
+                        }
+                    }
+                });
+            });
+
+            let mut seek = None;
+            egui::SidePanel::right("bursts").resizable(true).default_width(220.0).show_inside(ui, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("burst_list").num_columns(3).striped(true).show(ui, |ui| {
+                        ui.strong("Start (s)");
+                        ui.strong("End (s)");
+                        ui.strong(format!("Peak ({})", unit));
+                        ui.end_row();
+                        for b in self.speed_series.bursts() {
+                            if ui.selectable_label(false, format!("{:.3}", b.start)).on_hover_text("Seek here").clicked() {
+                                seek = Some(b.start);
+                            }
+                            ui.label(format!("{:.3}", b.end));
+                            ui.label(format!("{:.0}", b.peak));
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+
+            let series = &self.speed_series;
+            let full = match (series.runs().first().and_then(|r| r.first()), series.runs().last().and_then(|r| r.last())) {
+                (Some(first), Some(last)) => [first[0], last[0]],
+                _ => [0.0, self.video_duration],
+            };
+            let color = egui::Color32::from_rgb(80, 160, 255);
+            let plot = egui_plot::Plot::new("speed_plot")
+                .x_axis_label("time (s)")
+                .y_axis_label(unit)
+                .include_y(0.0)
+                .show(ui, |plot_ui| {
+                    let range = if plot_ui.auto_bounds().x {
+                        full
+                    } else {
+                        let bounds = plot_ui.plot_bounds();
+                        [bounds.min()[0], bounds.max()[0]]
+                    };
+                    let visible = |start: f64, end: f64| end >= range[0] && start <= range[1];
+                    if self.shade_bursts {
+                        for b in series.bursts().iter().filter(|b| visible(b.start, b.end)) {
+                            let corners = vec![[b.start, 0.0], [b.end, 0.0], [b.end, b.peak], [b.start, b.peak]];
+                            plot_ui.polygon(egui_plot::Polygon::new(egui_plot::PlotPoints::new(corners))
+                                .fill_color(egui::Color32::from_rgba_unmultiplied(255, 120, 40, 50))
+                                .stroke(egui::Stroke::NONE));
+                        }
+                    }
+                    let columns = plot_ui.response().rect.width() as usize;
+                    // one line per unbroken run, so gaps show as breaks
+                    for run in series.runs().iter().filter(|r| visible(r[0][0], r[r.len() - 1][0])) {
+                        let points = plot::min_max(run, |s| *s, range, columns);
+                        plot_ui.line(egui_plot::Line::new(egui_plot::PlotPoints::new(points)).color(color));
+                    }
+                    if self.show_flicks {
+                        for flick in self.flick_analysis.flicks().iter().filter(|f| visible(f.time, f.time)) {
+                            plot_ui.vline(egui_plot::VLine::new(flick.time).color(FLICK_COLOR.gamma_multiply(0.6)));
+                        }
+                    }
+                    plot_ui.hline(egui_plot::HLine::new(self.speed_threshold).color(egui::Color32::from_rgb(255, 120, 40)));
+                    plot_ui.vline(egui_plot::VLine::new(self.current_time).color(egui::Color32::from_rgb(255, 80, 80)));
+                    plot_ui.pointer_coordinate()
+                });
+            if plot.response.clicked() {
+                seek = plot.inner.map(|at| at.x);
+            }
+            if let Some(time) = seek {
+                self.seek_paused(time.clamp(0.0, self.video_duration));
+            }
+        });
+    }
+
+    fn find_peaks(&mut self) {
+        let levels: Vec<f32> = self.waveform.iter().flatten().map(|p| p[1] as f32).collect();
+        let loudest = levels.iter().copied().fold(0.0, f32::max);
+        // a silent track has no peaks, however low the threshold
+        let min_level = (self.peak_threshold * loudest).max(f32::MIN_POSITIVE);
+        self.waveform_peaks = audio::peaks(&levels, min_level, PEAK_SPACING);
+    }
+
+    /// The audio levels along the timeline. Clicking seeks there, or to a
+    /// marked peak if one is close by.
+    fn show_waveform(&mut self, ctx: &egui::Context) {
+        let shown = self.show_waveform && self.analysis_shown();
+        if shown && !self.waveform_requested && self.file_path.is_some() && self.loading.is_none() {
+            self.waveform_requested = true;
+            let _ = self.cmd_tx.send(AppCommand::LoadWaveform);
+        }
+        egui::TopBottomPanel::bottom("waveform").resizable(true).default_height(140.0).show_animated(ctx, shown, |ui| {
+            let levels = match self.waveform.as_deref() {
+                None if self.waveform_requested => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Decoding audio...");
+                    });
+                    return;
+                }
+                None => {
+                    ui.label("Load a video file...");
+                    return;
+                }
+                Some([]) => {
+                    ui.label("This file has no audio track");
+                    return;
+                }
+                Some(levels) => levels,
+            };
+
+            let mut threshold = self.peak_threshold;
+            ui.horizontal(|ui| {
+                ui.label("Peak threshold:");
+                ui.add(egui::Slider::new(&mut threshold, 0.05..=1.0).step_by(0.01))
+                    .on_hover_text("Mark peaks at least this fraction of the loudest moment");
+                ui.label(format!("{} peaks", self.waveform_peaks.len()));
+            });
+
+            let full = [0.0, levels[levels.len() - 1][0]];
+            let color = egui::Color32::from_rgb(120, 200, 120);
+            let plot = egui_plot::Plot::new("waveform_plot")
+                .x_axis_label("time (s)")
+                .y_axis_label("RMS")
+                .include_y(0.0)
+                .allow_scroll(false)
+                .show(ui, |plot_ui| {
+                    let range = if plot_ui.auto_bounds().x {
+                        full
+                    } else {
+                        let bounds = plot_ui.plot_bounds();
+                        [bounds.min()[0], bounds.max()[0]]
+                    };
+                    let columns = plot_ui.response().rect.width() as usize;
+                    let points = plot::min_max(levels, |p| *p, range, columns);
+                    plot_ui.line(egui_plot::Line::new(egui_plot::PlotPoints::new(points)).color(color));
+                    let peaks: Vec<[f64; 2]> = self.waveform_peaks.iter()
+                        .map(|&i| levels[i])
+                        .filter(|p| (range[0]..=range[1]).contains(&p[0]))
+                        .collect();
+                    plot_ui.points(egui_plot::Points::new(peaks).radius(3.0).color(TURN_COLOR));
+                    plot_ui.vline(egui_plot::VLine::new(self.current_time).color(egui::Color32::from_rgb(255, 80, 80)));
+                    (plot_ui.pointer_coordinate(), plot_ui.transform().dvalue_dpos()[0].abs())
+                });
+
+            if plot.response.clicked() {
+                if let (Some(at), seconds_per_px) = plot.inner {
+                    let nearest = self.waveform_peaks.iter()
+                        .map(|&i| levels[i][0])
+                        .min_by(|a, b| (a - at.x).abs().total_cmp(&(b - at.x).abs()))
+                        .filter(|t| (t - at.x).abs() <= PEAK_PICK_PX * seconds_per_px);
+                    self.seek_paused(nearest.unwrap_or(at.x).clamp(0.0, self.video_duration));
+                }
+            }
+            if threshold != self.peak_threshold {
+                self.peak_threshold = threshold;
+                self.find_peaks();
+            }
+        });
+    }
+
+    /// Writes the stretches above the speed threshold, with the settings
+    /// that found them.
+    fn export_bursts(&mut self, format: ExportFormat) {
+        let Some(path) = self.pick_export_path("_bursts", format) else { return };
+
+        let processing = vec![
+            ("kinematics_window", self.kinematics_window.into()),
+            ("speed_threshold", self.speed_threshold.into()),
+        ];
+        let calibration = self.calibration.filter(|_| self.export_playfield);
+        let provenance = Provenance { calibration, processing, ..self.provenance() };
+        let table = export::Table {
+            columns: vec!["start", "end", "duration", "peak_speed"],
+            rows: self.speed_series.bursts()
+                .iter()
+                .map(|b| vec![b.start.into(), b.end.into(), (b.end - b.start).into(), b.peak.into()])
+                .collect(),
+        };
+        match export::write(&path, &table, format, &provenance) {
+            Ok(()) => self.exported(&path),
+            Err(e) => self.report_error(format!("Export to {} failed: {:#}", path.display(), e), false),
+        }
+    }
+
+    fn show_direction(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_direction;
+        let mut seek = None;
+        egui::Window::new("Direction").open(&mut open).default_width(320.0).show(ctx, |ui| {
+            egui::Grid::new("direction_settings").num_columns(2).show(ui, |ui| {
+                ui.label("Min segment (px):");
+                ui.add(egui::DragValue::new(&mut self.direction_params.min_length).speed(0.5).range(1.0..=500.0))
+                    .on_hover_text("Samples closer than this are merged, so jitter at rest doesn't count as turning");
+                ui.end_row();
+                ui.label("Turn cutoff (°):");
+                ui.add(egui::DragValue::new(&mut self.direction_params.turn_min).speed(1.0).range(1.0..=180.0));
+                ui.end_row();
+            });
+            ui.checkbox(&mut self.mark_turns, "Mark turning points on the trail");
+            self.direction.update(&self.positions, self.direction_params);
+
+            // share of distance travelled per direction, 0° to the right and
+            // clockwise as on screen
+            let size = 200.0;
+            let (rect, _) = ui.allocate_exact_size(egui::vec2(size, size), egui::Sense::hover());
+            let painter = ui.painter_at(rect);
+            let center = rect.center();
+            let rose = self.direction.rose();
+            let most = rose.iter().copied().fold(0.0, f64::max);
+            let radius = size / 2.0 - 4.0;
+            painter.circle_stroke(center, radius, ui.visuals().widgets.noninteractive.bg_stroke);
+            if most > 0.0 {
+                let width = std::f32::consts::TAU / ROSE_SECTORS as f32;
+                for (i, &distance) in rose.iter().enumerate() {
+                    let r = radius * (distance / most) as f32;
+                    let mid = i as f32 * width;
+                    let wedge = vec![
+                        center,
+                        center + egui::Vec2::angled(mid - width / 2.0) * r,
+                        center + egui::Vec2::angled(mid) * r,
+                        center + egui::Vec2::angled(mid + width / 2.0) * r,
+                    ];
+                    painter.add(egui::Shape::convex_polygon(wedge, TURN_COLOR.gamma_multiply(0.6), egui::Stroke::NONE));
+                }
+            }
+
+            ui.label(format!("{} turning points", self.direction.turns().len()));
+            egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                egui::Grid::new("turning_points").num_columns(2).striped(true).show(ui, |ui| {
+                    ui.strong("Time (s)");
+                    ui.strong("Turn");
+                    ui.end_row();
+                    for turn in self.direction.turns() {
+                        if ui.selectable_label(false, format!("{:.3}", turn.time)).on_hover_text("Seek here").clicked() {
+                            seek = Some(turn.time);
+                        }
+                        ui.label(format!("{:+.0}°", turn.turn));
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+        self.show_direction = open;
+        if let Some(time) = seek {
+            self.seek_paused(time);
+        }
+    }
+
+    /// Opens a new segment and records into it. Points from before the
+    /// first segment stay in the trace, outside any segment.
+    fn start_segment(&mut self) {
+        let k = self.segments.len() as u32;
+        self.segments.push(format!("Segment {}", k + 1));
+        self.segment_scanned.push(0);
+        self.recording = Some(k);
+        if self.trail_color == TrailColor::Solid {
+            self.trail_color = TrailColor::Segment;
+        }
+    }
+
+    fn show_segments(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_segments;
+        let mut seek = None;
+        egui::Window::new("Segments").open(&mut open).default_width(300.0).show(ctx, |ui| {
+            if self.segments.is_empty() {
+                ui.label("Start Segment keeps detections in a named segment; until then the trace is one piece.");
+            }
+            let mut counts = vec![0usize; self.segments.len()];
+            let mut starts = vec![None; self.segments.len()];
+            for p in &self.positions {
+                if let Some(k) = p.segment.map(|k| k as usize).filter(|&k| k < counts.len()) {
+                    counts[k] += 1;
+                    starts[k].get_or_insert(p.time);
+                }
+            }
+            egui::Grid::new("segment_list").num_columns(4).striped(true).show(ui, |ui| {
+                for (k, name) in self.segments.iter_mut().enumerate() {
+                    let (swatch, _) = ui.allocate_exact_size(egui::vec2(10.0, 10.0), egui::Sense::hover());
+                    ui.painter().rect_filled(swatch, 2.0, series_color(k));
+                    ui.add(egui::TextEdit::singleline(name).desired_width(120.0));
+                    ui.label(format!("{} points", counts[k]));
+                    if ui.add_enabled(starts[k].is_some(), egui::Button::new("Seek").small()).clicked() {
+                        seek = starts[k];
+                    }
+                    ui.end_row();
+                }
+            });
+            ui.add_enabled(!self.segments.is_empty(), egui::Checkbox::new(&mut self.export_split_segments, "Export one file per segment"))
+                .on_hover_text("Otherwise exports are one file with a segment column");
+        });
+        self.show_segments = open;
+        if let Some(time) = seek {
+            self.seek_paused(time);
+        }
+    }
+
+    /// Bookmarks the frame shown. Nothing to mark without a video.
+    fn add_bookmark(&mut self) {
+        if self.file_path.is_none() || self.loading.is_some() {
+            return;
+        }
+        let time = self.current_time;
+        let name = format!("Bookmark {}", self.bookmarks.len() + 1);
+        let at = self.bookmarks.partition_point(|b| b.time <= time);
+        self.bookmarks.insert(at, Bookmark { time, name });
+        self.notice = Some((Instant::now(), format!("Bookmarked {:.2} s", time)));
+    }
+
+    /// Seeks to the next or previous bookmark from the frame shown.
+    fn jump_to_bookmark(&mut self, forward: bool) {
+        // half a frame, so the bookmark just jumped to doesn't count again
+        let slack = 0.5 / self.video_fps.max(1.0);
+        let target = if forward {
+            self.bookmarks.iter().find(|b| b.time > self.current_time + slack)
+        } else {
+            self.bookmarks.iter().rev().find(|b| b.time < self.current_time - slack)
+        };
+        if let Some(time) = target.map(|b| b.time) {
+            self.seek_paused(time);
+        }
+    }
+
+    /// Seeks to the nearest frame after or before the current one that
+    /// detection ran on without finding the cursor.
+    fn jump_to_miss(&mut self, forward: bool) {
+        let target = if forward {
+            self.frame_results.range(self.current_frame + 1..).find(|(_, r)| !r.detected)
+        } else {
+            self.frame_results.range(..self.current_frame).rev().find(|(_, r)| !r.detected)
+        };
+        match target.map(|(_, r)| r.time) {
+            Some(time) => self.seek_paused(time),
+            None => {
+                let side = if forward { "after" } else { "before" };
+                self.notice = Some((Instant::now(), format!("No missed detection {} this frame", side)));
+            }
+        }
+    }
+
+    /// Annotates the in to out range with an empty note, to be filled in
+    /// the Annotations window.
+    fn add_annotation(&mut self) {
+        if self.file_path.is_none() || self.loading.is_some() {
+            return;
+        }
+        let [start, end] = self.range;
+        let color = series_color(self.annotations.len());
+        self.annotations.push(Annotation { start, end, note: String::new(), color: [color.r(), color.g(), color.b()] });
+        self.show_annotations = true;
+    }
+
+    fn show_annotations(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_annotations;
+        let mut seek = None;
+        let mut delete = None;
+        egui::Window::new("Annotations").open(&mut open).default_width(420.0).show(ctx, |ui| {
+            if ui.add_enabled(self.file_path.is_some(), egui::Button::new("Annotate in–out"))
+                .on_hover_text("Add a note on the range between the in and out points")
+                .clicked()
+            {
+                self.add_annotation();
+            }
+            if self.annotations.is_empty() {
+                ui.label("No annotations yet. Set the in and out points, then Annotate.");
+            }
+            let duration = self.video_duration.max(0.0);
+            egui::Grid::new("annotation_list").num_columns(5).striped(true).show(ui, |ui| {
+                for (i, annotation) in self.annotations.iter_mut().enumerate() {
+                    ui.color_edit_button_srgb(&mut annotation.color);
+                    ui.add(egui::TextEdit::singleline(&mut annotation.note).hint_text("note").desired_width(160.0));
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut annotation.start).speed(0.01).range(0.0..=annotation.end).suffix(" s"));
+                        ui.label("–");
+                        ui.add(egui::DragValue::new(&mut annotation.end).speed(0.01).range(annotation.start..=duration).suffix(" s"));
+                    });
+                    if ui.small_button("Seek").clicked() {
+                        seek = Some(annotation.start);
+                    }
+                    if ui.small_button("Delete").clicked() {
+                        delete = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+        self.show_annotations = open;
+        if let Some(i) = delete {
+            self.annotations.remove(i);
+        }
+        if let Some(time) = seek {
+            self.seek_paused(time);
+        }
+    }
+
+    /// The annotations as bands under the timeline, whose slider spans
+    /// `xs`. A band's edges drag to move its bounds; a click seeks to its
+    /// start.
+    fn annotation_bands(&mut self, ui: &mut egui::Ui, xs: egui::Rangef) {
+        let lanes = annotation_lanes(&self.annotations);
+        let rows = lanes.iter().max().map_or(0, |l| l + 1);
+        let (strip, _) = ui.allocate_exact_size(egui::vec2(ui.available_width(), rows as f32 * ANNOTATION_LANE), egui::Sense::hover());
+        let duration = self.video_duration.max(1e-9);
+        let to_x = |t: f64| egui::lerp(xs.min..=xs.max, (t / duration).clamp(0.0, 1.0) as f32);
+        let to_time = |x: f32| ((x - xs.min) / xs.span()).clamp(0.0, 1.0) as f64 * duration;
+
+        let mut seek = None;
+        for (i, annotation) in self.annotations.iter_mut().enumerate() {
+            let top = strip.top() + lanes[i] as f32 * ANNOTATION_LANE;
+            let left = to_x(annotation.start);
+            // a zero-length range still gets something to see and grab
+            let band = egui::Rect::from_min_max(egui::pos2(left, top), egui::pos2(to_x(annotation.end).max(left + 2.0), top + ANNOTATION_LANE - 1.0));
+            let [r, g, b] = annotation.color;
+            ui.painter().rect_filled(band, 1.0, egui::Color32::from_rgb(r, g, b).gamma_multiply(0.8));
+
+            let id = ui.id().with(("annotation", i));
+            let note = if annotation.note.is_empty() { "(no note)" } else { annotation.note.as_str() };
+            let body = ui.interact(band, id, egui::Sense::click())
+                .on_hover_text(format!("{}\n{:.2} – {:.2} s", note, annotation.start, annotation.end));
+            if body.clicked() {
+                seek = Some(annotation.start);
+            }
+            // after the body, so the edges win where they overlap it
+            for (side, x) in [(0, band.left()), (1, band.right())] {
+                let grip = egui::Rect::from_center_size(egui::pos2(x, band.center().y), egui::vec2(6.0, ANNOTATION_LANE));
+                let edge = ui.interact(grip, id.with(side), egui::Sense::drag())
+                    .on_hover_cursor(egui::CursorIcon::ResizeHorizontal);
+                if let Some(pos) = edge.interact_pointer_pos().filter(|_| edge.dragged()) {
+                    let t = to_time(pos.x);
+                    if side == 0 {
+                        annotation.start = t.min(annotation.end);
+                    } else {
+                        annotation.end = t.max(annotation.start);
+                    }
+                }
+            }
+        }
+        if let Some(time) = seek {
+            self.seek_paused(time);
+        }
+    }
+
+    fn show_bookmarks(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_bookmarks;
+        let mut seek = None;
+        let mut delete = None;
+        egui::Window::new("Bookmarks").open(&mut open).default_width(300.0).show(ctx, |ui| {
+            if ui.add_enabled(self.file_path.is_some(), egui::Button::new("Add at current frame"))
+                .on_hover_text("Or press B")
+                .clicked()
+            {
+                self.add_bookmark();
+            }
+            if self.bookmarks.is_empty() {
+                ui.label("No bookmarks yet. PgUp and PgDn jump between them.");
+            }
+            egui::Grid::new("bookmark_list").num_columns(4).striped(true).show(ui, |ui| {
+                for (i, bookmark) in self.bookmarks.iter_mut().enumerate() {
+                    ui.add(egui::TextEdit::singleline(&mut bookmark.name).desired_width(140.0));
+                    ui.label(format!("{:.2} s", bookmark.time));
+                    if ui.small_button("Seek").clicked() {
+                        seek = Some(bookmark.time);
+                    }
+                    if ui.small_button("Delete").clicked() {
+                        delete = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+        self.show_bookmarks = open;
+        if let Some(i) = delete {
+            self.bookmarks.remove(i);
+        }
+        if let Some(time) = seek {
+            self.seek_paused(time);
+        }
+    }
+
+    fn show_ffmpeg_log(&mut self, ctx: &egui::Context) {
+        let mut clear = false;
+        let private = self.privacy;
+        egui::Window::new("ffmpeg log")
+            .open(&mut self.show_ffmpeg_log)
+            .default_width(520.0)
+            .show(ctx, |ui| {
+                let shown: Vec<&(LogLevel, String)> = self.ffmpeg_log.iter().filter(|(level, _)| *level >= self.log_filter).collect();
+                ui.horizontal(|ui| {
+                    ui.label("Show:");
+                    for (level, label) in [(LogLevel::Info, "All"), (LogLevel::Warning, "Warnings"), (LogLevel::Error, "Errors")] {
+                        ui.selectable_value(&mut self.log_filter, level, label);
+                    }
+                    ui.separator();
+                    if ui.add_enabled(!shown.is_empty(), egui::Button::new("Copy all")).clicked() {
+                        let text: Vec<_> = shown.iter().map(|(_, line)| privacy::shown(line, private)).collect();
+                        ui.ctx().copy_text(text.join("\n"));
+                    }
+                    clear = ui.button("Clear").clicked();
+                });
+                ui.separator();
+                if shown.is_empty() {
+                    ui.label("Nothing logged yet.");
+                    return;
+                }
+                let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+                egui::ScrollArea::both()
+                    .max_height(360.0)
+                    .stick_to_bottom(true)
+                    .auto_shrink([false, true])
+                    .show_rows(ui, row_height, shown.len(), |ui, rows| {
+                        for (level, line) in &shown[rows] {
+                            let text = egui::RichText::new(privacy::shown(line, private)).monospace();
+                            let text = match level {
+                                LogLevel::Error => text.color(ui.visuals().error_fg_color),
+                                LogLevel::Warning => text.color(ui.visuals().warn_fg_color),
+                                LogLevel::Info => text,
+                            };
+                            ui.add(egui::Label::new(text).extend()).on_hover_text(level.label());
+                        }
+                    });
+            });
+        if clear {
+            self.ffmpeg_log.clear();
+        }
+    }
+
+    fn show_measurements(&mut self, ctx: &egui::Context) {
+        let mut open = self.measuring;
+        egui::Window::new("Measurements").open(&mut open).default_width(240.0).show(ctx, |ui| {
+            ui.label("Click two points on the video to measure between them.");
+            let mut remove = None;
+            egui::Grid::new("measurements").num_columns(2).striped(true).show(ui, |ui| {
+                for (i, &m) in self.measurements.iter().enumerate() {
+                    ui.label(format!("{}: {}", i + 1, measure_label(m, self.calibration.as_ref())));
+                    if ui.small_button("✖").clicked() {
+                        remove = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+            if let Some(i) = remove {
+                self.measurements.remove(i);
+            }
+            if ui.add_enabled(!self.measurements.is_empty(), egui::Button::new("Clear all")).clicked() {
+                self.measurements.clear();
+            }
+        });
+        if !open {
+            self.measuring = false;
+            self.measure_from = None;
+        }
+    }
+
+    fn show_strokes(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_strokes;
+        let mut seek = None;
+        egui::Window::new("Strokes").open(&mut open).default_width(420.0).show(ctx, |ui| {
+            let transform = self.calibration.filter(|_| self.export_playfield).map(|c| c.transform);
+            let (length_unit, speed_unit) = if transform.is_some() { ("units", "units/s") } else { ("px", "px/s") };
+            egui::Grid::new("stroke_settings").num_columns(2).show(ui, |ui| {
+                ui.label("Pause below:");
+                ui.add(egui::DragValue::new(&mut self.stroke_params.pause_speed).speed(5.0).range(0.0..=1e6).suffix(format!(" {}", speed_unit)));
+                ui.end_row();
+                ui.label("Pause at least:");
+                ui.add(egui::DragValue::new(&mut self.stroke_params.pause_min).speed(0.01).range(0.0..=60.0).suffix(" s"))
+                    .on_hover_text("Shorter hesitations stay part of the stroke; detection gaps always end one");
+                ui.end_row();
+            });
+            self.strokes.update(&self.positions, transform, self.stroke_params);
+
+            ui.horizontal(|ui| {
+                ui.label(format!("{} strokes", self.strokes.strokes().len()));
+                if ui.add_enabled(!self.strokes.strokes().is_empty(), egui::Button::new("Export CSV")).clicked() {
+                    self.export_strokes();
+                }
+                ui.selectable_value(&mut self.trail_color, TrailColor::Stroke, "Colour trail");
+            });
+            egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                egui::Grid::new("stroke_list").num_columns(6).striped(true).show(ui, |ui| {
+                    ui.label("");
+                    ui.strong("Start (s)");
+                    ui.strong("Duration (s)");
+                    ui.strong(format!("Length ({})", length_unit));
+                    ui.strong(format!("Peak ({})", speed_unit));
+                    ui.strong("Straightness");
+                    ui.end_row();
+                    for (i, stroke) in self.strokes.strokes().iter().enumerate() {
+                        let (swatch, _) = ui.allocate_exact_size(egui::vec2(10.0, 10.0), egui::Sense::hover());
+                        ui.painter().rect_filled(swatch, 2.0, series_color(i));
+                        if ui.selectable_label(false, format!("{:.3}", stroke.start)).on_hover_text("Seek here").clicked() {
+                            seek = Some(stroke.start);
+                        }
+                        ui.label(format!("{:.3}", stroke.duration()));
+                        ui.label(format!("{:.1}", stroke.length));
+                        ui.label(format!("{:.0}", stroke.peak_speed));
+                        ui.label(format!("{:.2}", stroke.straightness));
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+        self.show_strokes = open;
+        if let Some(time) = seek {
+            self.seek_paused(time);
+        }
+    }
+
+    fn export_strokes(&mut self) {
+        let Some(path) = self.pick_export_path("_strokes", ExportFormat::Csv) else { return };
+
+        let processing = vec![
+            ("pause_speed", self.stroke_params.pause_speed.into()),
+            ("pause_min", self.stroke_params.pause_min.into()),
+        ];
+        let calibration = self.calibration.filter(|_| self.export_playfield);
+        let provenance = Provenance { calibration, processing, ..self.provenance() };
+        let table = export::Table {
+            columns: vec!["stroke", "start", "end", "duration", "length", "peak_speed", "straightness"],
+            rows: self.strokes.strokes()
+                .iter()
+                .enumerate()
+                .map(|(i, s)| vec![
+                    i.into(), s.start.into(), s.end.into(), s.duration().into(),
+                    s.length.into(), s.peak_speed.into(), s.straightness.into(),
+                ])
+                .collect(),
+        };
+        match export::write(&path, &table, ExportFormat::Csv, &provenance) {
+            Ok(()) => self.exported(&path),
+            Err(e) => self.report_error(format!("Export to {} failed: {:#}", path.display(), e), false),
+        }
+    }
+
+    fn show_speed_histogram(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_histogram;
+        egui::Window::new("Speed histogram").open(&mut open).default_size([420.0, 300.0]).show(ctx, |ui| {
+            let transform = self.calibration.filter(|_| self.export_playfield).map(|c| c.transform);
+            let unit = if transform.is_some() { "units/s" } else { "px/s" };
+            self.histogram.update(&self.positions, transform, self.histogram_bins);
+
+            ui.horizontal(|ui| {
+                ui.label("Bins:");
+                ui.add(egui::DragValue::new(&mut self.histogram_bins).range(2..=500));
+                ui.checkbox(&mut self.histogram_log, "Log scale");
+                if ui.add_enabled(self.histogram.stats().is_some(), egui::Button::new("Export CSV")).clicked() {
+                    self.export_histogram();
+                }
+            });
+            match self.histogram.stats() {
+                Some(s) => ui.label(format!(
+                    "{} segments   median {:.0}   p95 {:.0}   max {:.0} {}",
+                    s.segments, s.median, s.p95, s.max, unit,
+                )),
+                None => ui.label("No segments yet"),
+            };
+
+            let log = self.histogram_log;
+            let width = self.histogram.bin_width();
+            let bars = self.histogram.bins()
+                .map(|(start, count)| {
+                    let height = if log { (count as f64 + 1.0).log10() } else { count as f64 };
+                    egui_plot::Bar::new(start + width / 2.0, height).width(width)
+                })
+                .collect();
+            egui_plot::Plot::new("speed_histogram")
+                .x_axis_label(unit)
+                .y_axis_label(if log { "log10(1 + segments)" } else { "segments" })
+                .include_y(0.0)
+                .allow_drag(false)
+                .show(ui, |plot_ui| {
+                    plot_ui.bar_chart(egui_plot::BarChart::new(bars).color(egui::Color32::from_rgb(80, 160, 255)));
+                });
+        });
+        self.show_histogram = open;
+    }
+
+    fn export_histogram(&mut self) {
+        let Some(path) = self.pick_export_path("_speed_histogram", ExportFormat::Csv) else { return };
+
+        let processing = vec![("histogram_bins", self.histogram_bins.into())];
+        let calibration = self.calibration.filter(|_| self.export_playfield);
+        let provenance = Provenance { calibration, processing, ..self.provenance() };
+        let width = self.histogram.bin_width();
+        let table = export::Table {
+            columns: vec!["bin_start", "bin_end", "segments"],
+            rows: self.histogram.bins().map(|(start, count)| vec![start.into(), (start + width).into(), count.into()]).collect(),
+        };
+        match export::write(&path, &table, ExportFormat::Csv, &provenance) {
+            Ok(()) => self.exported(&path),
+            Err(e) => self.report_error(format!("Export to {} failed: {:#}", path.display(), e), false),
+        }
+    }
+
+    fn show_positions_table(&mut self, ctx: &egui::Context) {
+        let shown = self.show_table && self.analysis_shown();
+        egui::SidePanel::left("positions_table").resizable(true).default_width(420.0).show_animated(ctx, shown, |ui| {
+            self.table.window = self.kinematics_window;
+            self.table.update(&self.positions);
+            ui.label(format!(
+                "{} points, {} listed, {} selected",
+                self.positions.len(), self.table.rows().len(), self.selected.len(),
+            ));
+
+            ui.horizontal(|ui| {
+                let mut filtered = self.table.filter.is_some();
+                ui.checkbox(&mut filtered, "Time range");
+                let mut range = self.table.filter.unwrap_or([0.0, self.video_duration]);
+                ui.add_enabled_ui(filtered, |ui| {
+                    ui.add(egui::DragValue::new(&mut range[0]).speed(0.01).range(0.0..=f64::MAX).suffix(" s"));
+                    ui.label("to");
+                    let from = range[0];
+                    ui.add(egui::DragValue::new(&mut range[1]).speed(0.01).range(from..=f64::MAX).suffix(" s"));
+                });
+                self.table.filter = filtered.then_some(range);
+            });
+
+            ui.horizontal(|ui| {
+                ui.add_enabled_ui(!self.selected.is_empty(), |ui| {
+                    if ui.button("Copy").on_hover_text("Copy the selected rows as CSV").clicked() {
+                        let mut text = String::from("index,frame,time,x,y,angle,speed\n");
+                        for &i in &self.selected {
+                            let p = &self.positions[i];
+                            let angle = p.angle.map(|a| a.to_string()).unwrap_or_default();
+                            let speed = self.table.speed(i).map(|s| s.to_string()).unwrap_or_default();
+                            text += &format!("{},{},{},{},{},{},{}\n", i, p.frame, p.time, p.pos[0], p.pos[1], angle, speed);
+                        }
+                        ui.ctx().copy_text(text);
+                    }
+                    if ui.button("Delete").on_hover_text("Remove the selected points from the trace").clicked() {
+                        self.delete_selected();
+                    }
+                    if ui.button("Select none").clicked() {
+                        self.selected.clear();
+                    }
+                });
+                if ui.add_enabled(!self.trim_undo.is_empty(), egui::Button::new("Undo")).on_hover_text("Put back the last deleted points").clicked() {
+                    self.undo_trim();
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Frames:");
+                ui.add(egui::DragValue::new(&mut self.select_frames[0]));
+                ui.label("to");
+                let from = self.select_frames[0];
+                ui.add(egui::DragValue::new(&mut self.select_frames[1]).range(from..=u64::MAX));
+                if ui.button("Select").on_hover_text("Select every point in this frame range").clicked() {
+                    let [from, to] = self.select_frames;
+                    let start = self.positions.partition_point(|p| p.frame < from);
+                    let end = self.positions.partition_point(|p| p.frame <= to);
+                    self.selected = (start..end).collect();
+                    self.select_anchor = None;
+                }
+            });
+            ui.separator();
+
+            let mut sort_by = None;
+            let mut clicked = None;
+            let view = &self.table;
+            let positions = &self.positions;
+            let selected = &self.selected;
+            egui_extras::TableBuilder::new(ui)
+                .striped(true)
+                .resizable(true)
+                .sense(egui::Sense::click())
+                .cell_layout(egui::Layout::right_to_left(egui::Align::Center))
+                .columns(egui_extras::Column::auto().at_least(40.0), SortColumn::ALL.len() - 1)
+                .column(egui_extras::Column::remainder())
+                .header(20.0, |mut header| {
+                    for column in SortColumn::ALL {
+                        header.col(|ui| {
+                            let arrow = match (view.sort == column, view.descending) {
+                                (false, _) => "",
+                                (true, false) => " ⏶",
+                                (true, true) => " ⏷",
+                            };
+                            if ui.selectable_label(view.sort == column, format!("{}{}", column.label(), arrow)).clicked() {
+                                sort_by = Some(column);
+                            }
+                        });
+                    }
+                })
+                .body(|body| {
+                    // only the rows scrolled into view are laid out
+                    body.rows(18.0, view.rows().len(), |mut row| {
+                        let i = view.rows()[row.index()];
+                        let p = &positions[i];
+                        row.set_selected(selected.contains(&i));
+                        row.col(|ui| { ui.label(i.to_string()); });
+                        row.col(|ui| { ui.label(format!("{:.3}", p.time)); });
+                        row.col(|ui| { ui.label(format!("{:.1}", p.pos[0])); });
+                        row.col(|ui| { ui.label(format!("{:.1}", p.pos[1])); });
+                        row.col(|ui| { ui.label(p.angle.map(|a| format!("{:.0}°", a)).unwrap_or_default()); });
+                        row.col(|ui| { ui.label(view.speed(i).map(|s| format!("{:.0}", s)).unwrap_or_default()); });
+                        if row.response().clicked() {
+                            clicked = Some(row.index());
+                        }
+                    });
+                });
+
+            if let Some(column) = sort_by {
+                if self.table.sort == column {
+                    self.table.descending = !self.table.descending;
+                } else {
+                    self.table.sort = column;
+                    self.table.descending = false;
+                }
+            }
+            if let Some(row) = clicked {
+                let i = self.table.rows()[row];
+                let modifiers = ui.input(|i| i.modifiers);
+                if modifiers.shift {
+                    // a range in display order, from the last plain click
+                    let anchor = self.select_anchor
+                        .and_then(|a| self.table.rows().iter().position(|&r| r == a))
+                        .unwrap_or(row);
+                    let (from, to) = (anchor.min(row), anchor.max(row));
+                    self.selected.extend(&self.table.rows()[from..=to]);
+                } else if modifiers.command {
+                    if !self.selected.remove(&i) {
+                        self.selected.insert(i);
+                    }
+                    self.select_anchor = Some(i);
+                } else {
+                    self.selected = BTreeSet::from([i]);
+                    self.select_anchor = Some(i);
+                    self.seek_paused(self.positions[i].time);
+                }
+            }
+        });
+    }
+
+    /// A magnified crop around this frame's detection, or around the mouse
+    /// when there is none, with the centre pixel marked.
+    fn show_loupe(&mut self, ctx: &egui::Context) {
+        if !self.loupe {
+            return;
+        }
+        let Some(image) = &self.frame_image else { return };
+        let texture = self.loupe_texture.get_or_insert_with(|| {
+            ctx.load_texture("loupe_frame", image.clone(), egui::TextureOptions::NEAREST)
+        });
+        let frame_size = egui::vec2(image.size[0] as f32, image.size[1] as f32);
+        let detection = nearest_in_time(&self.positions, self.current_time)
+            .filter(|p| p.frame == self.current_frame)
+            .map(|p| egui::pos2(p.pos[0], p.pos[1]));
+        let target = match (detection, self.hover_frame_pos) {
+            (Some(p), _) => Some((p, "Detection")),
+            (None, Some(p)) => Some((p, "Mouse")),
+            (None, None) => None,
+        };
+
+        let mut open = true;
+        egui::Window::new("Loupe")
+            .open(&mut open)
+            .resizable(false)
+            .collapsible(false)
+            .pivot(egui::Align2::RIGHT_TOP)
+            .default_pos(ctx.screen_rect().right_top() + egui::vec2(-8.0, 8.0))
+            .show(ctx, |ui| {
+                let (rect, _) = ui.allocate_exact_size(egui::Vec2::splat(LOUPE_SIZE), egui::Sense::hover());
+                let painter = ui.painter_at(rect);
+                painter.rect_filled(rect, 0.0, egui::Color32::BLACK);
+                let Some((center, source)) = target else {
+                    ui.label("No detection on this frame");
+                    return;
+                };
+
+                // centre of the pixel the target is on
+                let pixel = egui::pos2(center.x.floor(), center.y.floor());
+                let crop = egui::Rect::from_center_size(pixel + egui::Vec2::splat(0.5), egui::Vec2::splat(LOUPE_SIZE / LOUPE_ZOOM));
+                let to_loupe = |p: egui::Pos2| rect.min + (p - crop.min) * LOUPE_ZOOM;
+                // only the part inside the frame, rather than smearing its edge pixels
+                let shown = crop.intersect(egui::Rect::from_min_size(egui::Pos2::ZERO, frame_size));
+                if shown.is_positive() {
+                    let uv = egui::Rect::from_min_max((shown.min.to_vec2() / frame_size).to_pos2(), (shown.max.to_vec2() / frame_size).to_pos2());
+                    painter.image(texture.id(), egui::Rect::from_min_max(to_loupe(shown.min), to_loupe(shown.max)), uv, egui::Color32::WHITE);
+                }
+
+                let stroke = egui::Stroke::new(1.0, self.style.now_color);
+                let cell = egui::Rect::from_min_size(to_loupe(pixel), egui::Vec2::splat(LOUPE_ZOOM));
+                painter.rect_stroke(cell.expand(1.0), 0.0, stroke);
+                let c = cell.center();
+                for (from, to) in [
+                    (egui::pos2(rect.min.x, c.y), egui::pos2(cell.min.x - 3.0, c.y)),
+                    (egui::pos2(cell.max.x + 3.0, c.y), egui::pos2(rect.max.x, c.y)),
+                    (egui::pos2(c.x, rect.min.y), egui::pos2(c.x, cell.min.y - 3.0)),
+                    (egui::pos2(c.x, cell.max.y + 3.0), egui::pos2(c.x, rect.max.y)),
+                ] {
+                    painter.line_segment([from, to], stroke);
+                }
+                ui.label(format!("{}: {}, {}", source, pixel.x, pixel.y));
+            });
+        if !open {
+            self.loupe = false;
+        }
+    }
+
+    /// Redraws the minimap when it has fallen behind, see
+    /// [`MINIMAP_REBUILD_POINTS`].
+    fn update_minimap(&mut self, ctx: &egui::Context) {
+        let Some(image) = &self.frame_image else { return };
+        let len = self.positions.len();
+        let (stale, behind) = match &self.minimap {
+            None => (true, false),
+            Some(m) => {
+                let behind = len != m.points || self.current_frame != m.frame;
+                let stale = len < m.points || len >= m.points + MINIMAP_REBUILD_POINTS || (behind && m.built.elapsed() >= MINIMAP_REFRESH);
+                (stale, behind)
+            }
+        };
+        if !stale {
+            if behind {
+                ctx.request_repaint_after(MINIMAP_REFRESH);
+            }
+            return;
+        }
+        let (thumbnail, scale) = minimap_image(image, &self.positions, self.style.line_color);
+        let texture = ctx.load_texture("minimap", thumbnail, egui::TextureOptions::LINEAR);
+        self.minimap = Some(Minimap { texture, scale, points: len, frame: self.current_frame, built: Instant::now() });
+    }
+
+    fn show_minimap(&mut self, ctx: &egui::Context) {
+        if !self.show_minimap {
+            return;
+        }
+        self.update_minimap(ctx);
+        let Some(minimap) = &self.minimap else { return };
+        let frame_size = egui::vec2(self.current_frame_size[0] as f32, self.current_frame_size[1] as f32);
+        let visible = self.camera.map_or(egui::Rect::from_min_size(egui::Pos2::ZERO, frame_size), |c| c.visible(frame_size));
+
+        let mut open = true;
+        let mut pan_to = None;
+        egui::Window::new("Minimap")
+            .open(&mut open)
+            .resizable(false)
+            .collapsible(false)
+            .pivot(egui::Align2::RIGHT_BOTTOM)
+            .default_pos(ctx.screen_rect().right_bottom() + egui::vec2(-8.0, -8.0))
+            .show(ctx, |ui| {
+                let (rect, response) = ui.allocate_exact_size(minimap.texture.size_vec2(), egui::Sense::click_and_drag());
+                let painter = ui.painter_at(rect);
+                let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                painter.image(minimap.texture.id(), rect, uv, egui::Color32::WHITE);
+                let to_minimap = |p: egui::Pos2| rect.min + p.to_vec2() / minimap.scale;
+                painter.rect_stroke(
+                    egui::Rect::from_min_max(to_minimap(visible.min), to_minimap(visible.max)),
+                    0.0,
+                    egui::Stroke::new(1.5, egui::Color32::WHITE),
+                );
+                if let Some(pos) = response.interact_pointer_pos().filter(|_| response.clicked() || response.dragged()) {
+                    pan_to = Some(((pos - rect.min) * minimap.scale).to_pos2());
+                }
+            });
+        if !open {
+            self.show_minimap = false;
+        }
+        if let Some(center) = pan_to {
+            // panning by hand takes over from following the cursor
+            self.follow = false;
+            let mut view = self.camera.unwrap_or(ViewCamera::fit(frame_size));
+            view.center = center;
+            view.center = view.visible(frame_size).center();
+            self.manual_view = Some(view);
+            self.camera = Some(view);
+        }
+    }
+
+    fn onion_active(&self) -> bool {
+        self.onion_skin && !self.is_playing && !self.is_simulating
+    }
+
+    fn frame_filter(&self) -> egui::TextureOptions {
+        if self.sharp_pixels {
+            egui::TextureOptions::NEAREST
+        } else {
+            egui::TextureOptions::LINEAR
+        }
+    }
+
+    /// Uploads the frame views again after the filtering changed.
+    fn refilter_frames(&mut self) {
+        let filter = self.frame_filter();
+        if let (Some(texture), Some(image)) = (&mut self.texture, &self.frame_image) {
+            texture.set(image.clone(), filter);
+        }
+        // the previous frame is only blended from for a moment
+        self.prev_texture = None;
+        self.onion_texture = None;
+        self.diff_texture = None;
+    }
+
+    /// Blends the frames before the current one into a copy of it, nearest
+    /// on top, and uploads that when the frame or the settings changed.
+    fn update_onion(&mut self, ctx: &egui::Context) {
+        let key = (self.current_frame, self.onion_frames, self.onion_opacity, self.onion_falloff);
+        if !self.onion_active() || self.onion_texture.as_ref().is_some_and(|(k, _)| *k == key) {
+            return;
+        }
+        self.onion_texture = None;
+        let Some(current) = &self.frame_image else { return };
+        let ghosts: Vec<&egui::ColorImage> = (1..=self.onion_frames as u64)
+            .filter_map(|k| self.current_frame.checked_sub(k))
+            .filter_map(|f| self.onion_history.iter().find(|(h, _)| *h == f))
+            .map(|(_, image)| &**image)
+            .filter(|image| image.size == current.size)
+            .collect();
+        if ghosts.is_empty() {
+            return;
+        }
+
+        let mut composite = (**current).clone();
+        for (k, ghost) in ghosts.iter().enumerate().rev() {
+            let alpha = self.onion_opacity * self.onion_falloff.powi(k as i32);
+            for (out, under) in composite.pixels.iter_mut().zip(&ghost.pixels) {
+                let [r, g, b, a] = [0, 1, 2, 3].map(|c| {
+                    let (o, u) = (out[c] as f32, under[c] as f32);
+                    (o + (u - o) * alpha).round() as u8
+                });
+                *out = egui::Color32::from_rgba_premultiplied(r, g, b, a);
+            }
+        }
+        let texture = ctx.load_texture("onion_skin", composite, self.frame_filter());
+        self.onion_texture = Some((key, texture));
+    }
+
+    /// Makes the difference image of the current frame against the one
+    /// shown before it, when the frame or the settings changed.
+    fn update_difference(&mut self, ctx: &egui::Context) {
+        let key = (self.current_frame, self.display_mode, self.diff_gain, self.diff_threshold);
+        if self.display_mode == DisplayMode::Normal || self.diff_texture.as_ref().is_some_and(|(k, _)| *k == key) {
+            return;
+        }
+        self.diff_texture = None;
+        let (Some(current), Some((_, previous))) = (&self.frame_image, &self.diff_from) else { return };
+        if previous.size != current.size {
+            return;
+        }
+
+        let pixels = current.pixels.iter().zip(&previous.pixels)
+            .map(|(now, before)| {
+                let change = [0, 1, 2].map(|c| now[c].abs_diff(before[c]));
+                match self.display_mode {
+                    DisplayMode::Threshold if change.iter().any(|&d| d >= self.diff_threshold) => egui::Color32::WHITE,
+                    DisplayMode::Threshold => egui::Color32::BLACK,
+                    _ => {
+                        let [r, g, b] = change.map(|d| (d as f32 * self.diff_gain).min(255.0) as u8);
+                        egui::Color32::from_rgb(r, g, b)
+                    }
+                }
+            })
+            .collect();
+        let image = egui::ColorImage { size: current.size, pixels };
+        let texture = ctx.load_texture("frame_difference", image, self.frame_filter());
+        self.diff_texture = Some((key, texture));
+    }
+
+    /// The main video with its overlays, filling `ui`.
+    fn show_video(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
+        if let Some((path, started)) = &self.loading {
+            let name = privacy::file_name(path, self.privacy);
+            let elapsed = started.elapsed().as_secs();
+            ui.centered_and_justified(|ui| {
+                ui.horizontal_centered(|ui| {
+                    ui.spinner();
+                    ui.label(format!("Opening {}... {} s", name, elapsed));
+                });
+            });
+            ctx.request_repaint_after(Duration::from_millis(250));
+            return;
+        }
+        self.update_onion(ctx);
+        self.update_difference(ctx);
+        if let Some(tex) = &self.texture {
+             let tex_size = tex.size_vec2();
+             let (rect, scale) = letterbox(ui, tex_size, self.style.letterbox);
+             let response = ui.interact(rect, ui.id().with("video"), egui::Sense::click_and_drag());
+             let painter = ui.painter_at(rect);
+             let picking = self.calibration_tool.as_ref().is_some_and(|t| t.picking);
+
+             let fit = ViewCamera::fit(tex_size);
+             let zoom_delta = if response.hovered() { ui.input(|i| i.zoom_delta()) } else { 1.0 };
+             if self.follow {
+                 self.follow_zoom = (self.follow_zoom * zoom_delta).clamp(1.0, MAX_ZOOM);
+             } else {
+                 let mut view = self.camera.unwrap_or(fit);
+                 let before = view;
+                 if let (true, Some(mouse)) = (zoom_delta != 1.0, response.hover_pos()) {
+                     // keep the frame pixel under the mouse where it is
+                     let visible = view.visible(tex_size);
+                     let anchor = visible.min + (mouse - rect.min) / (scale * view.zoom);
+                     view.zoom = (view.zoom * zoom_delta).clamp(1.0, MAX_ZOOM);
+                     let size = tex_size / view.zoom;
+                     view.center = anchor - (mouse - rect.min) / (scale * view.zoom) + size / 2.0;
+                 }
+                 if response.dragged_by(egui::PointerButton::Middle) {
+                     view.center -= response.drag_delta() / (scale * view.zoom);
+                 }
+                 if view != before {
+                     // store it clamped so panning past an edge doesn't build up
+                     view.center = view.visible(tex_size).center();
+                     self.manual_view = Some(view);
+                     self.camera = Some(view);
+                 }
+                 if response.double_clicked() && !self.annotating && !picking {
+                     self.manual_view = None;
+                 }
+             }
+
+             let target = match nearest_in_time(&self.positions, self.current_time) {
+                 Some(p) if self.follow && (self.current_time - p.time).abs() <= FOLLOW_LOST_AFTER => {
+                     ViewCamera { center: egui::pos2(p.pos[0], p.pos[1]), zoom: self.follow_zoom }
+                 }
+                 // lost, or not following: back to the manual view or the whole frame
+                 _ => self.manual_view.unwrap_or(fit),
+             };
+             let camera = self.camera.get_or_insert(fit);
+             if camera.ease_towards(target, ui.input(|i| i.stable_dt).min(0.1)) {
+                 ctx.request_repaint();
+             }
+             // everything drawn or clicked on the video goes through this one transform
+             let visible = camera.visible(tex_size);
+             let origin = visible.min.to_vec2();
+             let scale_factor = scale * camera.zoom;
+             let to_screen = |x: f32, y: f32| rect.min + (egui::vec2(x, y) - origin) * scale_factor;
+
+             let uv = egui::Rect::from_min_max((visible.min.to_vec2() / tex_size).to_pos2(), (visible.max.to_vec2() / tex_size).to_pos2());
+             let blend_from = self.prev_texture.as_ref()
+                 .filter(|prev| self.blend_frames && self.is_playing && !self.is_simulating && prev.size() == tex.size())
+                 .filter(|_| self.display_mode == DisplayMode::Normal);
+             if let Some(prev) = blend_from {
+                 // the new frame fades in over the step it arrived in; one step
+                 // behind, but without the hold-and-jump of plain slow motion
+                 let t = (self.last_play_frame.elapsed().as_secs_f64() / self.play_interval()).clamp(0.0, 1.0) as f32;
+                 painter.image(prev.id(), rect, uv, egui::Color32::WHITE);
+                 painter.image(tex.id(), rect, uv, egui::Color32::WHITE.gamma_multiply(t));
+             } else {
+                 let difference = self.diff_texture.as_ref().filter(|_| self.display_mode != DisplayMode::Normal).map(|(_, t)| t);
+                 let onion = self.onion_texture.as_ref().filter(|_| self.onion_active()).map(|(_, t)| t);
+                 let shown = difference.or(onion).unwrap_or(tex);
+                 painter.image(shown.id(), rect, uv, egui::Color32::WHITE);
+             }
+
+             let layers = if self.show_overlays { self.layers } else { Layers::NONE };
+             if self.overlay_mode.heatmap() && layers.heatmap {
+                 let size = [tex_size.x as u32, tex_size.y as u32];
+                 if self.heatmap.update(&self.positions, size, self.heatmap_cell) || self.heatmap_texture.is_none() {
+                     let [cols, rows] = self.heatmap.size();
+                     let image = egui::ColorImage::from_rgba_unmultiplied([cols, rows], &self.heatmap.to_rgba());
+                     self.heatmap_texture = Some(ctx.load_texture("heatmap", image, egui::TextureOptions::LINEAR));
+                 }
+                 if let Some(heat) = &self.heatmap_texture {
+                     // the grid can overhang the frame by part of a cell
+                     let [cols, rows] = self.heatmap.size();
+                     let cell = self.heatmap_cell as f32 * scale_factor;
+                     let heat_rect = egui::Rect::from_min_size(to_screen(0.0, 0.0), egui::vec2(cols as f32, rows as f32) * cell);
+                     painter.image(
+                         heat.id(),
+                         heat_rect,
+                         egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                         egui::Color32::from_white_alpha((self.heatmap_opacity * 255.0) as u8),
+                     );
+                 }
+             }
+
+             if self.grid.shown && layers.grid {
+                 let grid = self.grid;
+                 let stroke = egui::Stroke::new(1.0, grid.color);
+                 let font = egui::FontId::proportional(11.0);
+                 let xs = grid.lines(0, tex_size.x, visible.min.x, visible.max.x);
+                 let ys = grid.lines(1, tex_size.y, visible.min.y, visible.max.y);
+                 // at most one line per few screen pixels, or it's just a wash
+                 if xs.len() as f32 * 4.0 <= rect.width() && ys.len() as f32 * 4.0 <= rect.height() {
+                     for &x in &xs {
+                         let (top, bottom) = (to_screen(x, visible.min.y), to_screen(x, visible.max.y));
+                         painter.line_segment([top, bottom], stroke);
+                         if grid.labels {
+                             painter.text(top + egui::vec2(2.0, 1.0), egui::Align2::LEFT_TOP, format!("{:.0}", x), font.clone(), grid.color.to_opaque());
+                         }
+                     }
+                     for &y in &ys {
+                         let (left, right) = (to_screen(visible.min.x, y), to_screen(visible.max.x, y));
+                         painter.line_segment([left, right], stroke);
+                         if grid.labels {
+                             painter.text(left + egui::vec2(2.0, 1.0), egui::Align2::LEFT_TOP, format!("{:.0}", y), font.clone(), grid.color.to_opaque());
+                         }
+                     }
+                 }
+             }
+
+             if self.gap_markers {
+                 self.gap_analysis.update(&self.positions, self.current_frame_size, self.edge_margin);
+             }
+             if self.show_flicks {
+                 self.flick_analysis.update(&self.positions, self.kinematics_window, self.jerk_threshold);
+             }
+             let shown = if self.median {
+                 self.median_cache.get(&self.positions, self.median_window)
+             } else {
+                 &self.positions[..]
+             };
+             let shown = if self.simplify {
+                 self.simplify_cache.get(shown, self.simplify_epsilon)
+             } else {
+                 shown
+             };
+             let shown = self.trail_window.apply(shown);
+
+             for layer in self.trace_layers.iter().filter(|l| l.visible && layers.trail && self.overlay_mode.trail()) {
+                 let points: Vec<egui::Pos2> = layer.points.iter().map(|p| to_screen(p.pos[0], p.pos[1])).collect();
+                 let style = OverlayStyle { point_color: layer.color, line_color: layer.color, ..self.style };
+                 draw_trail(&painter, &points, &trail_cuts(&layer.trim_cuts, &layer.points), &trail_gaps(&layer.points), &style, scale_factor);
+             }
+
+             let trail_shown = layers.trail && self.trace_layers[self.active_layer].visible;
+             if self.overlay_mode.trail() && (trail_shown || layers.markers) && !shown.is_empty() {
+
+                 let style = self.style;
+                 let radius = style.point_radius * scale_factor;
+                 let width = style.line_width * scale_factor;
+
+                 let points: Vec<egui::Pos2> = shown.iter().map(|p| to_screen(p.pos[0], p.pos[1])).collect();
+                 let cuts = trail_cuts(&self.trim_cuts, shown);
+                 let joined = |i: usize| cuts.binary_search(&(i + 1)).is_err();
+
+                 if trail_shown && (self.fade_trail || self.trail_color != TrailColor::Solid) {
+                     let alpha: Vec<f32> = if self.fade_trail {
+                         // relative to the playhead while running, to the newest point when paused
+                         let now = if self.is_playing || self.is_simulating {
+                             self.current_time
+                         } else {
+                             shown.last().map_or(self.current_time, |p| p.time)
+                         };
+                         shown.iter()
+                             .map(|p| (1.0 - (now - p.time) / self.fade_window).clamp(0.0, 1.0) as f32)
+                             .collect()
+                     } else {
+                         vec![1.0; shown.len()]
+                     };
+
+                     let speeds = kinematics::segment_speeds(shown);
+                     let speed_range = if self.speed_auto {
+                         speeds.iter().flatten().fold([f64::INFINITY, 0.0], |[lo, hi], s| [lo.min(*s), hi.max(*s)])
+                     } else {
+                         self.speed_range
+                     };
+                     let time_range = shown.first().zip(shown.last()).map_or([0.0, 0.0], |(a, b)| [a.time, b.time]);
+                     let [old, new] = style.time_colors;
+                     if self.trail_color == TrailColor::Stroke {
+                         let transform = self.calibration.filter(|_| self.export_playfield).map(|c| c.transform);
+                         self.strokes.update(&self.positions, transform, self.stroke_params);
+                     }
+                     // colour of segment `i`, between shown points `i` and `i + 1`
+                     let segment_color = |i: usize| match (self.trail_color, speeds.get(i).copied().flatten()) {
+                         (_, None) => egui::Color32::GRAY,
+                         (TrailColor::Solid, Some(_)) => style.line_color,
+                         (TrailColor::Speed, Some(s)) => {
+                             let [lo, hi] = speed_range;
+                             speed_color(((s - lo) / (hi - lo).max(f64::EPSILON)).clamp(0.0, 1.0) as f32)
+                         }
+                         (TrailColor::Stroke, Some(_)) => {
+                             let mid = (shown[i].time + shown[i + 1].time) / 2.0;
+                             self.strokes.at(mid).map_or(egui::Color32::GRAY, series_color)
+                         }
+                         (TrailColor::Segment, Some(_)) => shown[i].segment.map_or(style.line_color, |k| series_color(k as usize)),
+                         (TrailColor::Time, Some(_)) => {
+                             let [first, last] = time_range;
+                             let mid = (shown[i].time + shown[i + 1].time) / 2.0;
+                             old.lerp_to_gamma(new, ((mid - first) / (last - first).max(f64::EPSILON)).clamp(0.0, 1.0) as f32)
+                         }
+                     };
+
+                     // the curve doesn't bend towards points across a gap or cut
+                     let smooth = |k: usize| joined(k - 1) && speeds[k - 1].is_some();
+                     let curve = |i: usize| match style.spline && speeds[i].is_some() {
+                         true => spline_segment(&points, i, smooth),
+                         false => vec![points[i], points[i + 1]],
+                     };
+
+                     let lines = style.render.lines();
+                     if style.halo {
+                         if lines {
+                             for i in (0..points.len().saturating_sub(1)).filter(|&i| joined(i)) {
+                                 let a = (alpha[i] + alpha[i + 1]) / 2.0;
+                                 if a > 0.0 {
+                                     painter.add(egui::Shape::line(curve(i), style.halo_stroke(width, a)));
+                                 }
+                             }
+                         }
+                         for (_, (p, a)) in points.iter().zip(&alpha).enumerate().filter(|(i, (_, a))| **a > 0.0 && style.marker_at(*i)) {
+                             painter.circle_filled(*p, radius + HALO_WIDTH, HALO_COLOR.gamma_multiply(*a));
+                         }
+                     }
+                     for (i, (p, a)) in points.iter().zip(&alpha).enumerate().filter(|(i, (_, a))| **a > 0.0 && style.marker_at(*i)) {
+                         // a point takes the colour of the segment leading into it
+                         let color = if self.trail_color == TrailColor::Solid { style.point_color } else { segment_color(i.saturating_sub(1)) };
+                         painter.circle_filled(*p, radius, color.gamma_multiply(*a));
+                     }
+                     for (i, segment) in points.windows(2).enumerate().filter(|(i, _)| lines && joined(*i)) {
+                         let a = (alpha[i] + alpha[i + 1]) / 2.0;
+                         if a <= 0.0 {
+                             continue;
+                         }
+                         let stroke = egui::Stroke::new(width, segment_color(i).gamma_multiply(a));
+                         if speeds[i].is_some() {
+                             painter.add(egui::Shape::line(curve(i), stroke));
+                         } else {
+                             // a gap between detections, not movement
+                             let dash = 6.0 * scale_factor;
+                             painter.extend(egui::Shape::dashed_line(segment, stroke, dash, dash));
+                         }
+                     }
+
+                     if self.trail_color == TrailColor::Speed && speed_range[0] <= speed_range[1] {
+                         let [lo, hi] = speed_range;
+                         draw_legend(&painter, rect, speed_color, [format!("{:.0}", lo), "px/s".into(), format!("{:.0}", hi)]);
+                     }
+                     if self.trail_color == TrailColor::Time && time_range[0] < time_range[1] {
+                         let [first, last] = time_range;
+                         draw_legend(&painter, rect, |t| old.lerp_to_gamma(new, t), [format!("{:.2} s", first), "time".into(), format!("{:.2} s", last)]);
+                     }
+                 } else if trail_shown {
+                     draw_trail(&painter, &points, &cuts, &trail_gaps(shown), &style, scale_factor);
+                 }
+
+                 let now = nearest_in_time(&self.positions, self.current_time);
+                 if let (true, Some(now)) = (style.now_marker && layers.markers, now) {
+                     let at = to_screen(now.pos[0], now.pos[1]);
+                     let ring = (style.point_radius * 2.0 + 4.0) * scale_factor;
+                     let stroke = egui::Stroke::new(2.0, style.now_color);
+                     if style.halo {
+                         painter.circle_stroke(at, ring, egui::Stroke::new(2.0 + 2.0 * HALO_WIDTH, HALO_COLOR));
+                     }
+                     painter.circle_stroke(at, ring, stroke);
+                     if style.now_crosshair {
+                         for (from, to) in [
+                             (egui::pos2(rect.min.x, at.y), at - egui::vec2(ring, 0.0)),
+                             (at + egui::vec2(ring, 0.0), egui::pos2(rect.max.x, at.y)),
+                             (egui::pos2(at.x, rect.min.y), at - egui::vec2(0.0, ring)),
+                             (at + egui::vec2(0.0, ring), egui::pos2(at.x, rect.max.y)),
+                         ] {
+                             painter.line_segment([from, to], egui::Stroke::new(1.0, style.now_color.gamma_multiply(0.7)));
+                         }
+                     }
+                 }
+
+                 if style.point_labels && layers.markers {
+                     let font = egui::FontId::monospace(11.0);
+                     let offset = egui::vec2(radius + 3.0, -(radius + 3.0));
+                     // zoomed in, most of the trail is off the panel
+                     let view = painter.clip_rect();
+                     for (i, (p, at)) in shown.iter().zip(&points).enumerate() {
+                         if !style.label_at(i) || !view.contains(*at) {
+                             continue;
+                         }
+                         let text = match style.label_frames {
+                             true => format!("{:.1}, {:.1} #{}", p.pos[0], p.pos[1], p.frame),
+                             false => format!("{:.1}, {:.1}", p.pos[0], p.pos[1]),
+                         };
+                         let galley = painter.layout_no_wrap(text, font.clone(), egui::Color32::WHITE);
+                         let at = egui::Align2::LEFT_BOTTOM.anchor_size(*at + offset, galley.size());
+                         painter.rect_filled(at.expand(2.0), 2.0, egui::Color32::from_black_alpha(170));
+                         painter.galley(at.min, galley, egui::Color32::WHITE);
+                     }
+                 }
+
+                 if self.show_direction && self.mark_turns && layers.markers {
+                     self.direction.update(&self.positions, self.direction_params);
+                     let (first, last) = (shown[0].time, shown[shown.len() - 1].time);
+                     let size = (style.point_radius + 3.0) * scale_factor;
+                     for turn in self.direction.turns().iter().filter(|t| (first..=last).contains(&t.time)) {
+                         let at = to_screen(turn.pos[0], turn.pos[1]);
+                         let diamond = [egui::vec2(0.0, -size), egui::vec2(size, 0.0), egui::vec2(0.0, size), egui::vec2(-size, 0.0)]
+                             .map(|d| at + d)
+                             .to_vec();
+                         painter.add(egui::Shape::convex_polygon(diamond, TURN_COLOR, egui::Stroke::new(1.0, HALO_COLOR)));
+                     }
+                 }
+
+                 if self.gap_markers && layers.markers {
+                     let frames = shown[0].frame..=shown[shown.len() - 1].frame;
+                     let gaps = self.gap_analysis.gaps().iter().filter(|g| frames.contains(&g.after_frame));
+                     // gone since the last detection, which was at an edge
+                     let leaving = self.positions.last()
+                         .filter(|p| frames.contains(&p.frame) && self.current_frame > p.frame + self.scope.every_n.max(1))
+                         .and_then(|p| gaps::near_edge(p.pos, self.current_frame_size, self.edge_margin));
+                     draw_gaps(&painter, gaps, leaving, to_screen, (style.point_radius + 3.0) * scale_factor);
+                 }
+
+                 if self.show_flicks && layers.markers {
+                     let (first, last) = (shown[0].time, shown[shown.len() - 1].time);
+                     let size = (style.point_radius + 3.0) * scale_factor;
+                     for flick in self.flick_analysis.flicks().iter().filter(|f| (first..=last).contains(&f.time)) {
+                         let at = to_screen(flick.pos[0], flick.pos[1]);
+                         painter.circle_stroke(at, size, egui::Stroke::new(2.0 + 2.0 * HALO_WIDTH, HALO_COLOR));
+                         painter.circle_stroke(at, size, egui::Stroke::new(2.0, FLICK_COLOR));
+                         for k in 0..8 {
+                             let ray = egui::Vec2::angled(k as f32 * std::f32::consts::FRAC_PI_4);
+                             painter.line_segment([at + ray * size * 1.3, at + ray * size * 1.9], egui::Stroke::new(2.0, FLICK_COLOR));
+                         }
+                     }
+                 }
+
+                 for p in self.selected.iter().filter(|_| layers.markers).filter_map(|&i| self.positions.get(i)) {
+                     let at = to_screen(p.pos[0], p.pos[1]);
+                     let ring = (style.point_radius + 4.0) * scale_factor;
+                     painter.circle_stroke(at, ring, egui::Stroke::new(2.0, egui::Color32::from_rgb(255, 0, 255)));
+                 }
+
+                 if let Some((last, angle)) = now.filter(|_| layers.markers).and_then(|p| Some((p, p.angle?))) {
+                     let from = to_screen(last.pos[0], last.pos[1]);
+                     let dir = egui::Vec2::angled(angle.to_radians());
+                     painter.arrow(
+                         from - dir * 20.0 * scale_factor,
+                         dir * 20.0 * scale_factor,
+                         egui::Stroke::new(2.0 * scale_factor, egui::Color32::YELLOW),
+                     );
+                 }
+            }
+
+             for trace in self.imported.iter().filter(|t| t.visible && layers.trail) {
+                 // only draw up to the playhead so the time alignment is visible
+                 let points: Vec<egui::Pos2> = trace.points.iter()
+                     .filter(|p| self.video_duration <= 0.0 || trace.aligned_time(p.time) <= self.current_time)
+                     .map(|p| {
+                         let [x, y] = trace.aligned_pos(p.pos);
+                         to_screen(x, y)
+                     })
+                     .collect();
+
+                 for p in &points {
+                     painter.circle_filled(*p, 3.0 * scale_factor, trace.color);
+                 }
+                 if points.len() > 1 {
+                     painter.add(egui::Shape::line(points, egui::Stroke::new(2.0 * scale_factor, trace.color)));
+                 }
+             }
+
+             let to_frame = |pos: egui::Pos2| {
+                 let p = (pos - rect.min) / scale_factor + origin;
+                 [p.x as f64, p.y as f64]
+             };
+             self.hover_frame_pos = response.hover_pos().map(|pos| {
+                 let [x, y] = to_frame(pos);
+                 egui::pos2(x as f32, y as f32)
+             });
+             // where a click places a point by hand
+             let grid = self.grid;
+             let place = |pos: egui::Pos2| {
+                 let at = to_frame(pos);
+                 if grid.snap { grid.snap(at, tex_size.into()) } else { at }
+             };
+
+             if self.measuring && !self.annotating && !picking && response.clicked() {
+                 if let Some(pos) = response.interact_pointer_pos() {
+                     let at = place(pos);
+                     match self.measure_from.take() {
+                         Some(from) => self.measurements.push([from, at]),
+                         None => self.measure_from = Some(at),
+                     }
+                 }
+             }
+             let pending = self.measure_from.zip(response.hover_pos().map(place)).filter(|_| self.measuring);
+             for (i, &[a, b]) in self.measurements.iter().chain(&pending.map(|(a, b)| [a, b])).enumerate().filter(|_| layers.annotations) {
+                 let (from, to) = (to_screen(a[0] as f32, a[1] as f32), to_screen(b[0] as f32, b[1] as f32));
+                 painter.line_segment([from, to], egui::Stroke::new(2.0 + 2.0 * HALO_WIDTH, HALO_COLOR));
+                 painter.line_segment([from, to], egui::Stroke::new(2.0, MEASURE_COLOR));
+                 for end in [from, to] {
+                     painter.circle_filled(end, 3.0, MEASURE_COLOR);
+                 }
+                 let number = if i < self.measurements.len() { format!("{}: ", i + 1) } else { String::new() };
+                 let galley = painter.layout_no_wrap(format!("{}{}", number, measure_label([a, b], self.calibration.as_ref())), egui::FontId::proportional(13.0), MEASURE_COLOR);
+                 let label = egui::Align2::CENTER_BOTTOM.anchor_size(from.lerp(to, 0.5) - egui::vec2(0.0, 4.0), galley.size());
+                 painter.rect_filled(label.expand(2.0), 3.0, egui::Color32::from_black_alpha(160));
+                 painter.galley(label.min, galley, MEASURE_COLOR);
+             }
+
+             if self.trimming && !self.annotating && !picking && response.clicked() {
+                 if let Some(pos) = response.interact_pointer_pos() {
+                     // the closest point on the shown trail, if the click is near one
+                     let (first, last) = shown.first().zip(shown.last()).map_or((0.0, -1.0), |(a, b)| (a.time, b.time));
+                     let nearest = self.positions.iter()
+                         .enumerate()
+                         .filter(|(_, p)| (first..=last).contains(&p.time))
+                         .map(|(i, p)| (i, to_screen(p.pos[0], p.pos[1]).distance(pos)))
+                         .filter(|(_, d)| *d <= 12.0)
+                         .min_by(|a, b| a.1.total_cmp(&b.1))
+                         .map(|(i, _)| i);
+                     match (nearest, self.trim_from.take()) {
+                         (Some(i), Some(from)) => self.selected = (from.min(i)..=from.max(i)).collect(),
+                         (Some(i), None) => {
+                             self.selected = BTreeSet::from([i]);
+                             self.trim_from = Some(i);
+                         }
+                         (None, _) => self.selected.clear(),
+                     }
+                 }
+             }
+
+             if self.annotating && !picking {
+                 if response.clicked() {
+                     if let Some(pos) = response.interact_pointer_pos() {
+                         let [x, y] = place(pos);
+                         let label = TracePoint::new(self.current_frame, self.current_time, [x as f32, y as f32]);
+                         self.labels.insert(self.current_frame, label);
+                     }
+                 }
+                 if let Some(label) = self.labels.get(&self.current_frame).filter(|_| layers.annotations) {
+                     let at = to_screen(label.pos[0], label.pos[1]);
+                     let stroke = egui::Stroke::new(2.0, egui::Color32::from_rgb(0, 255, 255));
+                     painter.line_segment([at - egui::vec2(8.0, 0.0), at + egui::vec2(8.0, 0.0)], stroke);
+                     painter.line_segment([at - egui::vec2(0.0, 8.0), at + egui::vec2(0.0, 8.0)], stroke);
+                 }
+             }
+
+             if let Some(tool) = &mut self.calibration_tool {
+                 if tool.picking && response.clicked() {
+                     if let Some(pos) = response.interact_pointer_pos() {
+                         tool.points.push(RefPoint { frame: place(pos), playfield: [0.0, 0.0] });
+                     }
+                 }
+                 for (i, point) in tool.points.iter().enumerate() {
+                     let at = to_screen(point.frame[0] as f32, point.frame[1] as f32);
+                     painter.circle_stroke(at, 6.0, egui::Stroke::new(2.0, egui::Color32::LIGHT_GREEN));
+                     painter.text(
+                         at + egui::vec2(8.0, -8.0),
+                         egui::Align2::LEFT_BOTTOM,
+                         format!("{}", i + 1),
+                         egui::FontId::proportional(14.0),
+                         egui::Color32::LIGHT_GREEN,
+                     );
+                 }
+             }
+
+             if let Some(point) = self.preview_point.as_ref().filter(|_| layers.markers) {
+                 let at = to_screen(point.pos[0], point.pos[1]);
+                 painter.circle_stroke(at, 9.0, egui::Stroke::new(2.0 + 2.0 * HALO_WIDTH, HALO_COLOR));
+                 painter.circle_stroke(at, 9.0, egui::Stroke::new(2.0, PREVIEW_COLOR));
+                 painter.text(
+                     at + egui::vec2(11.0, -11.0),
+                     egui::Align2::LEFT_BOTTOM,
+                     "preview",
+                     egui::FontId::proportional(13.0),
+                     PREVIEW_COLOR,
+                 );
+             }
+
+             // the trail point under the mouse: its time on hover, a seek on
+             // click and a menu on right click, unless a tool owns the clicks
+             let mut point_tip = None;
+             let trail_live = self.overlay_mode.trail() && (layers.trail || layers.markers) && self.trace_layers[self.active_layer].visible;
+             if trail_live && !self.annotating && !self.measuring && !self.trimming && !picking {
+                 let span = shown.first().zip(shown.last()).map(|(a, b)| a.time..=b.time);
+                 self.point_grid.update(&self.positions);
+                 let hovered = response.hover_pos().zip(span).and_then(|(pos, span)| {
+                     let [x, y] = to_frame(pos);
+                     let reach = HOVER_REACH / scale_factor;
+                     self.point_grid.nearest(&self.positions, [x as f32, y as f32], reach, |p| span.contains(&p.time))
+                 });
+                 if response.secondary_clicked() {
+                     self.menu_point = hovered;
+                 }
+                 if let Some(i) = hovered {
+                     let p = self.positions[i];
+                     let at = to_screen(p.pos[0], p.pos[1]);
+                     let ring = (self.style.point_radius + 4.0) * scale_factor;
+                     painter.circle_stroke(at, ring, egui::Stroke::new(2.0 + 2.0 * HALO_WIDTH, HALO_COLOR));
+                     painter.circle_stroke(at, ring, egui::Stroke::new(2.0, egui::Color32::WHITE));
+                     // enough neighbours either side for the same speed the table shows
+                     let k = self.kinematics_window.max(1);
+                     let around = &self.positions[i.saturating_sub(2 * k)..(i + 2 * k + 1).min(self.positions.len())];
+                     let speed = kinematics::compute(around, k)[i - i.saturating_sub(2 * k)].speed();
+                     let speed = speed.map_or("speed unknown".to_string(), |s| format!("{:.0} px/s", s));
+                     point_tip = Some(format!("point {}: {:.3} s, frame {}\n{}", i, p.time, p.frame, speed));
+                     if response.clicked() {
+                         self.seek_paused(p.time);
+                     }
+                 }
+                 response.context_menu(|ui| {
+                     let Some(p) = self.menu_point.and_then(|i| self.positions.get(i).copied()) else {
+                         ui.close_menu();
+                         return;
+                     };
+                     let i = self.menu_point.unwrap_or_default();
+                     ui.label(format!("Point {}, frame {}", i, p.frame));
+                     if ui.button(format!("Go to {:.3} s", p.time)).clicked() {
+                         self.seek_paused(p.time);
+                         ui.close_menu();
+                     }
+                     if ui.button("Select").on_hover_text("Select it in the table").clicked() {
+                         self.selected = BTreeSet::from([i]);
+                         self.select_anchor = Some(i);
+                         ui.close_menu();
+                     }
+                     if ui.button("Copy position").clicked() {
+                         ui.ctx().copy_text(format!("{}, {}", p.pos[0], p.pos[1]));
+                         ui.close_menu();
+                     }
+                     ui.separator();
+                     if ui.button("Delete").on_hover_text("Remove it from the trace; Undo in the table puts it back").clicked() {
+                         self.selected = BTreeSet::from([i]);
+                         self.delete_selected();
+                         ui.close_menu();
+                     }
+                 });
+             }
+
+             if self.style.stamp {
+                 let [w, h] = self.current_frame_size.map(|v| v as f32);
+                 let frame = egui::Rect::from_min_max(to_screen(0.0, 0.0), to_screen(w, h));
+                 let size = self.style.stamp_size * scale_factor;
+                 let t = self.current_time;
+                 let text = format!("{}:{:02}:{:06.3}  #{}", (t / 3600.0) as u64, (t / 60.0) as u64 % 60, t % 60.0, self.current_frame);
+                 let galley = painter.layout_no_wrap(text, egui::FontId::monospace(size), egui::Color32::WHITE);
+                 let align = self.style.stamp_corner.align();
+                 let at = align.anchor_size(align.pos_in_rect(&frame.shrink(size * 0.6)), galley.size());
+                 painter.rect_filled(at.expand(size * 0.25), size * 0.15, egui::Color32::from_black_alpha(190));
+                 painter.galley(at.min, galley, egui::Color32::WHITE);
+             }
+
+             if self.show_stats {
+                 let s = &self.worker_stats;
+                 let text = format!(
+                     "decode   {:6.1} fps\nread     {:6.2} ms\ndetect   {:6.2} ms\nui frame {:6.2} ms\nnot drawn {:5}\nqueued   {:3} ev {:3} cmd",
+                     s.decode_fps, s.read_ms, s.detect_ms, self.ui_frame_ms, self.frames_not_drawn,
+                     self.event_rx.len(), self.cmd_tx.len(),
+                 );
+                 let galley = painter.layout_no_wrap(text, egui::FontId::monospace(12.0), egui::Color32::WHITE);
+                 let at = egui::Align2::RIGHT_TOP.anchor_size(rect.right_top() + egui::vec2(-8.0, 8.0), galley.size());
+                 painter.rect_filled(at.expand(4.0), 3.0, egui::Color32::from_black_alpha(170));
+                 painter.galley(at.min, galley, egui::Color32::WHITE);
+             }
+
+             if self.pixel_readout || self.calibration.is_some() || self.calibration_tool.is_some() {
+                 if let Some(pos) = response.hover_pos() {
+                     let frame = to_frame(pos);
+                     let mut text = format!("pixels: {:.1}, {:.1}", frame[0], frame[1]);
+                     if let Some(c) = &self.calibration {
+                         let [x, y] = c.transform.apply(frame);
+                         text += &format!("\nplayfield: {:.1}, {:.1}", x, y);
+                     }
+                     if self.pixel_readout {
+                         let [x, y] = frame.map(|v| v.floor());
+                         let pixel = self.frame_image.as_ref()
+                             .filter(|image| x >= 0.0 && y >= 0.0 && (x as usize) < image.size[0] && (y as usize) < image.size[1])
+                             .map(|image| image[(x as usize, y as usize)].to_array());
+                         if let Some([r, g, b, a]) = pixel {
+                             text += &format!("\nRGBA: {}, {}, {}, {}", r, g, b, a);
+                         }
+                         let nearest = self.positions.iter()
+                             .map(|p| (p, (p.pos[0] as f64 - frame[0]).hypot(p.pos[1] as f64 - frame[1])))
+                             .min_by(|a, b| a.1.total_cmp(&b.1));
+                         if let Some((p, distance)) = nearest {
+                             text += &format!("\nnearest detection: {:.1} px (frame {})", distance, p.frame);
+                         }
+                     }
+                     if let Some(tip) = point_tip {
+                         text += &format!("\n{}", tip);
+                     }
+                     response.on_hover_text_at_pointer(text);
+                 }
+             } else if let Some(tip) = point_tip {
+                 response.on_hover_text_at_pointer(tip);
+             }
+        } else {
+            ui.centered_and_justified(|ui| {
+                ui.label("Load a video file...");
+            });
+        }
+    }
+}
+
+impl eframe::App for VideoApp {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        let settings = self.settings();
+        eframe::set_value(storage, SETTINGS_KEY, &settings);
+        self.saved_settings = settings;
+        self.settings_changed = None;
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.stop_stream();
+    }
+
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        if !self.window_checked {
+            self.check_window_geometry(ctx);
+        }
+        if self.window_checked {
+            if let Some(monitor) = ctx.input(|i| i.viewport().monitor_size) {
+                self.monitor_size = Some(monitor);
+            }
+        }
+        self.handle_events(ctx);
+        if let (None, Some(t)) = (self.preview_sent, self.scrub_target) {
+            self.preview_sent = Some(t);
+            self.send(AppCommand::Preview(t));
+        }
+        if let Some(Err(e)) = self.stream.as_mut().map(|s| s.flush_if_due()) {
+            self.stream = None;
+            self.report_error(format!("Streaming stopped: {}", e), false);
+        }
+        let compare_errors = self.compare.as_mut().map(|c| c.handle_events(ctx)).unwrap_or_default();
+        if let Some(compare) = &mut self.compare {
+            compare.play(ctx, self.speed);
+        }
+        for e in compare_errors {
+            self.report_error(format!("Compare video: {}", e), false);
+        }
+        self.show_notices(ctx);
+        self.update_title(ctx);
+        self.save_settings_if_changed(ctx, frame);
+        self.show_import_dialog(ctx);
+        self.show_trace_info(ctx);
+        self.show_calibration(ctx);
+        if self.measuring {
+            self.show_measurements(ctx);
+        }
+        if self.show_segments {
+            self.show_segments(ctx);
+        }
+        if self.show_bookmarks {
+            self.show_bookmarks(ctx);
+        }
+        if self.show_annotations {
+            self.show_annotations(ctx);
+        }
+        if self.show_ffmpeg_log {
+            self.show_ffmpeg_log(ctx);
+        }
+        self.show_cache_offer(ctx);
+        if self.annotating {
+            self.handle_annotation_keys(ctx);
+        } else if !ctx.wants_keyboard_input() {
+            if ctx.input(|i| i.key_pressed(egui::Key::Delete) || i.key_pressed(egui::Key::Backspace)) {
+                self.delete_selected();
+            }
+            if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::Z)) {
+                self.undo_trim();
+            }
+            if self.file_path.is_some() {
+                let (next, prev) = ctx.input(|i| (i.key_pressed(egui::Key::ArrowRight), i.key_pressed(egui::Key::ArrowLeft)));
+                if next || prev {
+                    self.step_paused(next);
+                }
+            }
+        }
+        if !ctx.wants_keyboard_input() && ctx.input(|i| i.key_pressed(egui::Key::O)) {
+            self.show_overlays = !self.show_overlays;
+        }
+        if !ctx.wants_keyboard_input() && ctx.input(|i| i.key_pressed(egui::Key::T)) {
+            self.style.render = self.style.render.next();
+        }
+        if !ctx.wants_keyboard_input() {
+            let (mark, next, prev) = ctx.input(|i| (
+                i.key_pressed(egui::Key::B),
+                i.key_pressed(egui::Key::PageDown),
+                i.key_pressed(egui::Key::PageUp),
+            ));
+            if mark {
+                self.add_bookmark();
+            }
+            if next || prev {
+                self.jump_to_bookmark(next);
+            }
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::F12)) {
+            self.show_stats = !self.show_stats;
+        }
+        if self.show_stats {
+            smooth_stat(&mut self.ui_frame_ms, ctx.input(|i| i.unstable_dt) as f64 * 1000.0);
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::F11)) {
+            self.set_presenting(ctx, !self.presenting);
+        } else if self.presenting && ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.set_presenting(ctx, false);
+        }
+
+        if self.is_simulating {
+            if self.last_sim_time.elapsed().as_millis() as u64 >= self.interval_ms {
+                let cmd = match self.magic_step {
+                    MagicStep::Frame => AppCommand::Step,
+                    MagicStep::Interval => AppCommand::Advance(self.interval_ms as f64 / 1000.0),
+                };
+                self.send(cmd);
+                self.last_sim_time = Instant::now();
+            }
+            ctx.request_repaint();
+        }
+
+        if self.is_playing && !self.is_simulating {
+             let target_dt = self.play_interval();
+             if self.play_steps_remaining == Some(0) {
+                 // counted in Steps sent rather than frames received, so
+                 // frames still in flight can't make us overshoot
+                 self.is_playing = false;
+                 self.play_steps_remaining = None;
+             } else {
+                 // Steps fall due on the video's clock rather than once per
+                 // UI frame, so a source faster than the display still plays
+                 // in real time; if even that can't keep up, it slows down
+                 let elapsed = self.last_play_frame.elapsed().as_secs_f64();
+                 let (due, advance) = steps_due(elapsed, target_dt, self.play_steps_remaining);
+                 for _ in 0..due {
+                     self.send(AppCommand::Step);
+                 }
+                 if let Some(remaining) = &mut self.play_steps_remaining {
+                     *remaining -= due;
+                 }
+                 match advance {
+                     Some(advance) => self.last_play_frame += Duration::from_secs_f64(advance),
+                     None => self.last_play_frame = Instant::now(),
+                 }
+             }
+             ctx.request_repaint();
+        }
+
+        let show_controls = !self.presenting || ctx.input(|i| {
+            let hover_zone = self.controls_height.max(48.0);
+            i.pointer.hover_pos().is_some_and(|p| p.y >= i.screen_rect().max.y - hover_zone)
+        });
+        let controls = egui::TopBottomPanel::bottom("controls").show_animated(ctx, show_controls, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button(if self.presenting { "Exit presentation" } else { "Present" })
+                    .on_hover_text("Fullscreen with only the video showing (F11, Esc to leave)")
+                    .clicked()
+                {
+                    self.set_presenting(ctx, !self.presenting);
+                }
+                ui.toggle_value(&mut self.show_overlays, "👁")
+                    .on_hover_text("Show everything drawn over the video (O); the data stays either way");
+
+                if ui.button("Open File").clicked() {
+                    let path = self.file_dialog().add_filter("Video", &["mp4"]).add_filter("Playlist", &playlist::EXTENSIONS).pick_file();
+                    if let Some(path) = self.picked(path) {
+                        // its header describes the old file
+                        self.stop_stream();
+                        self.file_path = Some(path.clone());
+                        self.hash_source(&path);
+                        self.calibration = self.calibrations.get(&calibration_key(&path)).copied();
+                        self.clear_positions();
+                        self.bookmarks.clear();
+                        self.annotations.clear();
+                        // the other layers traced the old file
+                        let active = self.trace_layers.swap_remove(self.active_layer);
+                        self.trace_layers = vec![active];
+                        self.active_layer = 0;
+                        // the frame size may differ, start from the whole frame
+                        self.manual_view = None;
+                        self.camera = None;
+                        self.is_playing = false;
+                        self.play_steps_remaining = None;
+                        self.send(AppCommand::SetRange(0.0, f64::INFINITY));
+                        self.ffmpeg_log.clear();
+                        let _ = self.cmd_tx.send(AppCommand::SetVideoStream(None));
+                        let _ = self.cmd_tx.send(AppCommand::LoadFile(path));
+                    }
+                }
+
+                self.analysis_toggles(ui);
+                if ui.button(if self.detached { "Dock" } else { "Detach" })
+                    .on_hover_text("Show the table, plots and analysis windows in a window of their own, or back in this one")
+                    .clicked()
+                {
+                    self.detached = !self.detached;
+                }
+                ui.toggle_value(&mut self.show_ffmpeg_log, "ffmpeg log");
+                ui.toggle_value(&mut self.privacy, "🕶")
+                    .on_hover_text("Privacy mode, for screen sharing: show files by name only and hide folders in errors and the log");
+                if !self.errors.is_empty() {
+                    ui.toggle_value(&mut self.show_errors, format!("Errors ({})", self.errors.len()));
+                }
+
+                if ui.button("Import Trace").clicked() {
+                    self.pick_import();
+                }
+
+                if self.compare.is_some() {
+                    if ui.button("Close Compare").clicked() {
+                        self.compare = None;
+                    }
+                } else if ui.add_enabled(self.file_path.is_some(), egui::Button::new("Compare..."))
+                    .on_hover_text("Open a second video to step side by side with this one")
+                    .clicked()
+                {
+                    self.open_compare();
+                }
+
+                let loaded = self.loading.is_none() && self.rescan.is_none();
+                if ui.add_enabled(loaded, egui::Button::new(if self.is_playing { "Pause" } else { "Play" })).clicked() {
+                    if self.is_playing {
+                        self.pause_playback();
+                    } else {
+                        self.is_playing = true;
+                        self.play_steps_remaining = None;
+                        self.last_play_frame = Instant::now();
+                        self.is_simulating = false;
+                    }
+                }
+
+                ui.add_enabled_ui(self.file_path.is_some() && loaded, |ui| {
+                    if ui.button("⏴").on_hover_text("Previous frame (←)").clicked() {
+                        self.step_paused(false);
+                    }
+                    if ui.button("⏵").on_hover_text("Next frame (→)").clicked() {
+                        self.step_paused(true);
+                    }
+                });
+
+                ui.label("Speed:");
+                ui.add(egui::Slider::new(&mut self.speed, 0.07..=2.0).step_by(0.01));
+
+                // fixed while Magic runs, so its samples share one schedule
+                ui.add_enabled_ui(!self.is_simulating, |ui| {
+                    ui.label("Interval (ms):");
+                    ui.add(egui::DragValue::new(&mut self.interval_ms).speed(10).range(1..=10000));
+
+                    egui::ComboBox::from_id_salt("magic_step")
+                        .selected_text(self.magic_step.label())
+                        .show_ui(ui, |ui| {
+                            for step in [MagicStep::Frame, MagicStep::Interval] {
+                                ui.selectable_value(&mut self.magic_step, step, step.label());
+                            }
+                        })
+                        .response
+                        .on_hover_text("Whether each Magic tick samples the next frame or skips ahead by the interval in video time");
+                });
+
+                if ui.add_enabled(loaded, egui::Button::new(if self.is_simulating { "Stop Magic" } else { "Magic" })).clicked() {
+                    self.is_simulating = !self.is_simulating;
+                    if self.is_simulating {
+                        self.is_playing = false; 
+                        self.play_steps_remaining = None;
+                        self.last_sim_time = Instant::now();
+                        self.magic_run = Some((Instant::now(), self.magic_step, self.interval_ms));
+                        self.magic_progress = Some(MagicProgress {
+                            started: Instant::now(),
+                            from: self.current_time,
+                            frames: 0,
+                            detections: 0,
+                            before: self.positions.clone(),
+                        });
+                    }
+                }
+                if self.is_simulating && ui.button("Cancel Magic").on_hover_text("Stop and discard what this scan detected").clicked() {
+                    self.is_simulating = false;
+                    if let Some(progress) = self.magic_progress.take() {
+                        self.selected.clear();
+                        self.trim_from = None;
+                        self.positions = progress.before;
+                        self.unbroken_pass = false;
+                    }
+                }
+
+                if ui.button("Clear Pos").clicked() {
+                    self.clear_positions();
+                }
+                if ui.add_enabled(loaded && self.traversed.is_some(), egui::Button::new("Rescan"))
+                    .on_hover_text("Detect again with the current settings over the frames gone through so far, replacing their positions")
+                    .clicked()
+                {
+                    self.start_rescan();
+                }
+
+                match self.recording {
+                    None => {
+                        if ui.button("Start Segment").on_hover_text("Keep detections in a new segment until stopped").clicked() {
+                            self.start_segment();
+                        }
+                    }
+                    Some(k) => {
+                        let name = &self.segments[k as usize];
+                        if ui.button("Stop Segment").on_hover_text(format!("Recording into {}", name)).clicked() {
+                            self.recording = None;
+                        }
+                    }
+                }
+                ui.toggle_value(&mut self.show_segments, "Segments");
+                ui.toggle_value(&mut self.show_bookmarks, "Bookmarks");
+                ui.toggle_value(&mut self.show_annotations, "Annotations");
+
+                if ui.toggle_value(&mut self.trimming, "Trim")
+                    .on_hover_text("Click two points on the trail to select the range between them, then Delete")
+                    .changed()
+                {
+                    self.trim_from = None;
+                    self.measuring &= !self.trimming;
+                }
+                if ui.toggle_value(&mut self.measuring, "Measure")
+                    .on_hover_text("Click two points on the video to measure the distance between them")
+                    .changed()
+                {
+                    self.measure_from = None;
+                    self.trimming &= !self.measuring;
+                }
+                if self.trimming {
+                    if ui.add_enabled(!self.selected.is_empty(), egui::Button::new("Delete range")).clicked() {
+                        self.delete_selected();
+                    }
+                    ui.checkbox(&mut self.stitch_trims, "Stitch ends")
+                        .on_hover_text("Join the points either side of a deleted range instead of leaving a break");
+                    if ui.add_enabled(!self.trim_undo.is_empty(), egui::Button::new("Undo")).on_hover_text("Ctrl+Z").clicked() {
+                        self.undo_trim();
+                    }
+                }
+
+                if ui.add_enabled(self.texture.is_some(), egui::Button::new("Calibrate")).clicked()
+                    && self.calibration_tool.is_none()
+                {
+                    // reference points are picked on a still frame
+                    self.pause_playback();
+                    self.is_simulating = false;
+                    self.calibration_tool = Some(CalibrationTool {
+                        points: Vec::new(),
+                        model: TransformModel::Affine,
+                        picking: true,
+                    });
+                }
+            });
+
+            ui.add_enabled_ui(self.file_path.is_some() && self.loading.is_none(), |ui| {
+                let xs = ui.horizontal(|ui| {
+                    let mut t = self.scrub_target.unwrap_or(self.current_time);
+                    let slider_width = (ui.available_width() - 200.0).max(100.0);
+                    ui.spacing_mut().slider_width = slider_width;
+                    let timeline = ui.add(egui::Slider::new(&mut t, 0.0..=self.video_duration.max(0.0))
+                        .fixed_decimals(2)
+                        .suffix(" s"));
+
+                    // where the slider puts each time, inset by its handle like the slider itself
+                    let rail = egui::Rect::from_min_size(timeline.rect.min, egui::vec2(slider_width, timeline.rect.height()));
+                    let xs = rail.x_range().shrink(rail.height() / 2.5);
+                    let hover = timeline.hover_pos();
+                    let mut hovered = None;
+                    for bookmark in &self.bookmarks {
+                        let x = egui::lerp(xs.min..=xs.max, (bookmark.time / self.video_duration.max(1e-9)).clamp(0.0, 1.0) as f32);
+                        ui.painter().line_segment(
+                            [egui::pos2(x, rail.top()), egui::pos2(x, rail.bottom())],
+                            egui::Stroke::new(2.0, BOOKMARK_COLOR),
+                        );
+                        if hover.is_some_and(|p| (p.x - x).abs() <= 4.0 && rail.contains(p)) {
+                            hovered = Some(bookmark);
+                        }
+                    }
+                    let timeline = match hovered {
+                        Some(b) => timeline.on_hover_text(format!("{}  {:.2} s", b.name, b.time)),
+                        None => timeline.on_hover_text("Drag to scrub. Detections on the frames shown are previewed, not recorded"),
+                    };
+                    if timeline.changed() {
+                        self.pause_playback();
+                        self.is_simulating = false;
+                        self.scrub_target = Some(t);
+                    }
+                    if ui.add_enabled(self.preview_point.is_some(), egui::Button::new("Keep detection"))
+                        .on_hover_text("Add the previewed detection to the positions")
+                        .clicked()
+                    {
+                        if let Some(point) = self.preview_point.take() {
+                            self.record_position(point.with_segment(self.recording));
+                        }
+                    }
+                    xs
+                }).inner;
+                if !self.annotations.is_empty() {
+                    self.annotation_bands(ui, xs);
+                }
+            });
+
+            if let Some(progress) = self.magic_progress.as_ref().filter(|_| self.is_simulating) {
+                let end = self.applied_range.map_or(self.video_duration, |r| r[1].min(self.video_duration));
+                let covered = (self.current_time - progress.from).max(0.0);
+                let fraction = if end > progress.from { covered / (end - progress.from) } else { 0.0 };
+                let elapsed = progress.started.elapsed().as_secs_f64();
+                // from how fast video time has gone by so far
+                let eta = (covered > 0.0).then(|| (end - self.current_time).max(0.0) * elapsed / covered);
+                let eta = eta.map_or("--".to_string(), |s| format!("{}:{:02}", s as u64 / 60, s as u64 % 60));
+                ui.add(egui::ProgressBar::new(fraction.clamp(0.0, 1.0) as f32).text(format!(
+                    "{:.0}%  {} frames  {:.1} frames/s  {} detections  ETA {}",
+                    fraction * 100.0, progress.frames, progress.frames as f64 / elapsed.max(1e-3), progress.detections, eta,
+                )));
+            }
+
+            if let Some((started, done)) = self.rescan {
+                ui.add(egui::ProgressBar::new(done as f32).text(format!(
+                    "Rescanning  {:.0}%  {:.0} s", done * 100.0, started.elapsed().as_secs_f64(),
+                )));
+                ctx.request_repaint_after(Duration::from_millis(250));
+            }
+
+            ui.horizontal(|ui| {
+                ui.add_enabled_ui(self.file_path.is_some() && self.loading.is_none(), |ui| {
+                    ui.add(egui::DragValue::new(&mut self.play_to_time)
+                        .speed(0.01)
+                        .range(0.0..=self.video_duration.max(0.0))
+                        .suffix(" s"));
+                    if ui.button("Play to time").clicked() {
+                        let target = (self.play_to_time * self.video_fps).ceil() as u64;
+                        self.play_frames(target.saturating_sub(self.current_frame));
+                    }
+
+                    ui.add(egui::DragValue::new(&mut self.play_frame_count).range(1..=100_000).suffix(" frames"));
+                    if ui.button("Play frames").clicked() {
+                        self.play_frames(self.play_frame_count);
+                    }
+
+                    if let Some(remaining) = self.play_steps_remaining {
+                        ui.label(format!("{} frames left", remaining));
+                    }
+
+                    ui.separator();
+
+                    let duration = self.video_duration.max(0.0);
+                    ui.label("In:");
+                    ui.add(egui::DragValue::new(&mut self.range[0]).speed(0.01).range(0.0..=duration).suffix(" s"));
+                    if ui.small_button("⏷").on_hover_text("Set to the current time").clicked() {
+                        self.range[0] = self.current_time;
+                    }
+                    ui.label("Out:");
+                    ui.add(egui::DragValue::new(&mut self.range[1]).speed(0.01).range(0.0..=duration).suffix(" s"));
+                    if ui.small_button("⏷").on_hover_text("Set to the current time").clicked() {
+                        self.range[1] = self.current_time;
+                    }
+                    self.range[1] = self.range[1].max(self.range[0]);
+
+                    if ui.button("Set range").on_hover_text("Only decode between the in and out points").clicked() {
+                        let [start, end] = self.range;
+                        self.applied_range = (start > 0.0 || end < duration).then_some(self.range);
+                        self.is_playing = false;
+                        self.play_steps_remaining = None;
+                        self.send(AppCommand::SetRange(start, end));
+                    }
+                    if ui.button("Annotate").on_hover_text("Add a note on the range between the in and out points").clicked() {
+                        self.add_annotation();
+                    }
+                    if ui.button("Full").clicked() {
+                        self.range = [0.0, duration];
+                        self.applied_range = None;
+                        self.send(AppCommand::SetRange(0.0, f64::INFINITY));
+                    }
+                });
+            });
+
+            ui.horizontal(|ui| {
+                let mut streaming = self.stream.is_some();
+                let toggle = ui.add_enabled(self.file_path.is_some() || streaming, egui::Checkbox::new(&mut streaming, "Stream to CSV"))
+                    .on_hover_text("Append every new detection to a CSV as it arrives, so long runs survive a crash");
+                if toggle.changed() {
+                    if streaming {
+                        self.start_stream();
+                    } else {
+                        self.stop_stream();
+                    }
+                }
+                if let Some(stream) = &self.stream {
+                    let path = stream.path().display().to_string();
+                    ui.label(privacy::file_name(stream.path(), self.privacy))
+                        .on_hover_text(privacy::shown(&path, self.privacy));
+                }
+                ui.separator();
+
+                ui.add_enabled_ui(!self.positions.is_empty(), |ui| {
+                    let content = if self.export_kinematics {
+                        ExportContent::PositionsWithKinematics
+                    } else {
+                        ExportContent::Positions
+                    };
+                    for format in [ExportFormat::Csv, ExportFormat::Json] {
+                        if ui.button(format!("Export {}", format.label())).clicked() {
+                            self.export(content, format);
+                        }
+                    }
+
+                    ui.checkbox(&mut self.export_kinematics, "With kinematics");
+
+                    ui.label("Diff window:")
+                        .on_hover_text("Samples on each side used to differentiate velocity and acceleration");
+                    ui.add(egui::DragValue::new(&mut self.kinematics_window).range(1..=30));
+
+                    if ui.button("Export kinematics").clicked() {
+                        self.export(ExportContent::KinematicsOnly, ExportFormat::Csv);
+                    }
+
+                    ui.separator();
+
+                    let mut median_changed = ui.checkbox(&mut self.median, "Median")
+                        .on_hover_text("Replace each position by the median of the samples around it, which drops single-frame outliers without rounding off sharp turns. Shown and exported; the recorded positions stay as detected")
+                        .changed();
+                    median_changed |= ui.add_enabled(self.median, egui::DragValue::new(&mut self.median_window).range(2..=51).suffix(" samples"))
+                        .changed();
+                    if median_changed {
+                        // same length and last frame, so the simplification can't tell by itself
+                        self.simplify_cache.clear();
+                    }
+
+                    ui.separator();
+
+                    ui.checkbox(&mut self.simplify, "Simplify");
+                    ui.add_enabled_ui(self.simplify, |ui| {
+                        ui.label("Epsilon (px):");
+                        ui.add(egui::DragValue::new(&mut self.simplify_epsilon).speed(0.1).range(0.0..=100.0));
+
+                        let filtered = if self.median {
+                            self.median_cache.get(&self.positions, self.median_window)
+                        } else {
+                            &self.positions[..]
+                        };
+                        let simplified = self.simplify_cache.get(filtered, self.simplify_epsilon).len();
+                        ui.label(format!("{} → {} pts", self.positions.len(), simplified));
+
+                        ui.checkbox(&mut self.export_simplified, "Export simplified");
+                    });
+
+                    ui.separator();
+
+                    ui.add_enabled(!(self.simplify && self.export_simplified), egui::Checkbox::new(&mut self.export_interpolated, "Fill gaps"))
+                        .on_hover_text("Interpolate missed detections linearly and flag those rows in an interpolated column. Not with a simplified export");
+                    ui.add_enabled(self.export_interpolated, egui::DragValue::new(&mut self.interpolate_max_gap).range(1..=1000).prefix("≤ ").suffix(" samples"))
+                        .on_hover_text("Longer gaps are left out");
+
+                    ui.separator();
+
+                    ui.add_enabled(self.calibration.is_some(), egui::Checkbox::new(&mut self.export_playfield, "Playfield coords"))
+                        .on_hover_text("Export positions in calibrated playfield units instead of video pixels");
+                    let playfield = self.calibration.is_some() && self.export_playfield;
+                    ui.add_enabled(!playfield && self.current_frame_size[0] > 0, egui::Checkbox::new(&mut self.export_normalized, "Normalized coords"))
+                        .on_hover_text("Export positions as fractions of the frame width and height, 0 to 1, so clips of different sizes compare");
+
+                    ui.separator();
+
+                    ui.checkbox(&mut self.reveal_after_export, "Reveal after export")
+                        .on_hover_text("Show each exported file in the system file manager once it is written");
+                });
+            });
+        });
+
+        if let Some(controls) = controls {
+            self.controls_height = controls.response.rect.height();
+        }
+
+        egui::SidePanel::right("sidebar").show_animated(ctx, !self.presenting, |ui| {
+            egui::CollapsingHeader::new("View").default_open(true).show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.follow, "Follow cursor")
+                        .on_hover_text("Zoom in and keep the detected cursor centred; shows the whole frame while detection is lost");
+                    ui.add_enabled(self.follow, egui::Slider::new(&mut self.follow_zoom, 1.0..=MAX_ZOOM).suffix("×"));
+                });
+                ui.horizontal(|ui| {
+                    let zoom = self.camera.map_or(1.0, |c| c.zoom);
+                    ui.label(format!("Zoom {:.1}×", zoom));
+                    if ui.add_enabled(self.manual_view.is_some(), egui::Button::new("Fit")).clicked() {
+                        self.manual_view = None;
+                    }
+                });
+                ui.label("Ctrl+scroll zooms towards the mouse, middle-drag pans, double-click fits.");
+                ui.horizontal(|ui| {
+                    ui.label("Background:");
+                    ui.color_edit_button_srgba(&mut self.style.letterbox)
+                        .on_hover_text("Colour of the bars around the video");
+                    if ui.checkbox(&mut self.sharp_pixels, "Sharp pixels")
+                        .on_hover_text("Show each video pixel as a crisp square when zoomed in, instead of smoothing between them")
+                        .changed()
+                    {
+                        self.refilter_frames();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.grid.shown, "Grid");
+                    ui.checkbox(&mut self.grid.snap, "Snap")
+                        .on_hover_text("Move labels, measurement ends and reference points you place to the nearest grid intersection");
+                    ui.add_enabled_ui(self.grid.shown || self.grid.snap, |ui| {
+                        let pixels = matches!(self.grid.spacing, GridSpacing::Pixels(_));
+                        if ui.selectable_label(pixels, "Every").clicked() && !pixels {
+                            self.grid.spacing = GridSpacing::Pixels(100.0);
+                        }
+                        if ui.selectable_label(!pixels, "Divide").clicked() && pixels {
+                            self.grid.spacing = GridSpacing::Divisions([3, 3]);
+                        }
+                        match &mut self.grid.spacing {
+                            GridSpacing::Pixels(step) => {
+                                ui.add(egui::DragValue::new(step).range(4.0..=10000.0).suffix(" px"));
+                            }
+                            GridSpacing::Divisions([columns, rows]) => {
+                                ui.add(egui::DragValue::new(columns).range(1..=100));
+                                ui.label("×");
+                                ui.add(egui::DragValue::new(rows).range(1..=100));
+                            }
+                        }
+                        ui.color_edit_button_srgba(&mut self.grid.color).on_hover_text("Line colour and opacity");
+                        ui.checkbox(&mut self.grid.labels, "Labels")
+                            .on_hover_text("Frame pixel coordinates of the lines along the top and left");
+                    });
+                });
+                ui.checkbox(&mut self.show_stats, "Playback stats")
+                    .on_hover_text("Decode rate, read and detection time per frame, UI frame time, frames never drawn and queue depths (F12)");
+                ui.checkbox(&mut self.pixel_readout, "Pixel readout")
+                    .on_hover_text("Show the frame coordinates and colour under the mouse, and how far the nearest detection is");
+                ui.checkbox(&mut self.loupe, "Loupe")
+                    .on_hover_text("Magnified view of the detected pixel, or of the one under the mouse when nothing was detected");
+                ui.checkbox(&mut self.show_minimap, "Minimap")
+                    .on_hover_text("The whole frame and trail in small, with the part zoomed into outlined. Click or drag on it to pan");
+                ui.horizontal(|ui| {
+                    ui.label("Step-back cache:");
+                    if ui.add(egui::DragValue::new(&mut self.frame_cache).range(0..=1000).suffix(" frames")).changed() {
+                        self.send(AppCommand::SetFrameCache(self.frame_cache));
+                    }
+                    let [w, h] = self.current_frame_size;
+                    let bytes = self.frame_cache_used * w as usize * h as usize * self.detection.depth.bytes_per_pixel();
+                    ui.label(format!("{} held, {:.0} MB", self.frame_cache_used, bytes as f64 / 1e6))
+                        .on_hover_text("Decoded frames kept so stepping back is instant; stepping further back re-decodes");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Probe timeout:");
+                    if ui.add(egui::DragValue::new(&mut self.probe_timeout).range(1.0..=600.0).suffix(" s"))
+                        .on_hover_text("Give up opening a file if ffmpeg can't read its header in this long")
+                        .changed()
+                    {
+                        self.send(AppCommand::SetProbeTimeout(Duration::from_secs_f64(self.probe_timeout)));
+                    }
+                });
+                if self.video_streams.len() > 1 {
+                    let mut stream = self.video_stream;
+                    ui.horizontal(|ui| {
+                        ui.label("Video stream:");
+                        let label = |i: usize| format!("{}: {}", i, self.video_streams[i].label());
+                        egui::ComboBox::from_id_salt("video_stream")
+                            .selected_text(label(stream))
+                            .show_ui(ui, |ui| {
+                                for i in 0..self.video_streams.len() {
+                                    ui.selectable_value(&mut stream, i, label(i));
+                                }
+                            })
+                            .response
+                            .on_hover_text("Which of the file's video streams to decode. Changing it reopens the file and clears the positions");
+                    });
+                    if let (true, Some(path)) = (stream != self.video_stream, self.file_path.clone()) {
+                        self.clear_positions();
+                        let _ = self.cmd_tx.send(AppCommand::SetVideoStream(Some(stream)));
+                        let _ = self.cmd_tx.send(AppCommand::LoadFile(path));
+                    }
+                }
+                ui.horizontal(|ui| {
+                    for mode in [DisplayMode::Normal, DisplayMode::Difference, DisplayMode::Threshold] {
+                        ui.selectable_value(&mut self.display_mode, mode, mode.label());
+                    }
+                })
+                .response
+                .on_hover_text("Show how each pixel changed since the previous frame, to spot anything that moves. Detection still runs on the frame itself");
+                ui.horizontal(|ui| {
+                    ui.add_enabled(self.display_mode == DisplayMode::Difference,
+                        egui::DragValue::new(&mut self.diff_gain).speed(0.1).range(1.0..=32.0).prefix("gain ×"));
+                    ui.add_enabled(self.display_mode == DisplayMode::Threshold,
+                        egui::Slider::new(&mut self.diff_threshold, 1..=255).text("threshold"))
+                        .on_hover_text("Smallest change in any channel shown white");
+                });
+                ui.checkbox(&mut self.blend_frames, "Blend frames during playback")
+                    .on_hover_text("Cross-fade between decoded frames for smoother slow motion. Off shows every frame exactly as decoded");
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.onion_skin, "Onion skin")
+                        .on_hover_text("Show the frames before this one faintly over it while paused or stepping");
+                    ui.add_enabled_ui(self.onion_skin, |ui| {
+                        ui.add(egui::DragValue::new(&mut self.onion_frames).range(1..=MAX_ONION_FRAMES).suffix(" frames"));
+                        ui.add(egui::DragValue::new(&mut self.onion_opacity).speed(0.01).range(0.05..=1.0).prefix("opacity "))
+                            .on_hover_text("Of the previous frame");
+                        ui.add(egui::DragValue::new(&mut self.onion_falloff).speed(0.01).range(0.1..=1.0).prefix("× "))
+                            .on_hover_text("Each earlier frame's opacity relative to the one after it");
+                    });
+                });
+            });
+
+            egui::CollapsingHeader::new("Overlay").default_open(true).show(ui, |ui| {
+                ui.checkbox(&mut self.show_overlays, "Show overlays")
+                    .on_hover_text("Hide or show all of these at once (O) without touching the data");
+                ui.add_enabled_ui(self.show_overlays, |ui| {
+                    ui.horizontal_wrapped(|ui| {
+                        ui.checkbox(&mut self.layers.trail, "Trail");
+                        ui.checkbox(&mut self.layers.markers, "Markers")
+                            .on_hover_text("Current point, turns, gaps, the selection and the preview");
+                        ui.checkbox(&mut self.layers.heatmap, "Heatmap");
+                        ui.checkbox(&mut self.layers.grid, "Grid");
+                        ui.checkbox(&mut self.layers.annotations, "Annotations")
+                            .on_hover_text("Measurements and hand-placed labels");
+                    });
+                });
+                ui.horizontal(|ui| {
+                    for mode in [OverlayMode::Trail, OverlayMode::Heatmap, OverlayMode::Both] {
+                        ui.selectable_value(&mut self.overlay_mode, mode, mode.label());
+                    }
+                });
+                ui.add_enabled_ui(self.overlay_mode.heatmap(), |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Heatmap opacity:");
+                        ui.add(egui::Slider::new(&mut self.heatmap_opacity, 0.0..=1.0));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Cell size (px):");
+                        ui.add(egui::DragValue::new(&mut self.heatmap_cell).range(2..=128));
+                    });
+                });
+                ui.add_enabled_ui(self.overlay_mode.trail(), |ui| {
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_salt("trail_window")
+                            .selected_text(self.trail_window.label())
+                            .show_ui(ui, |ui| {
+                                for window in [TrailWindow::All, TrailWindow::Points(500), TrailWindow::Seconds(10.0)] {
+                                    let selected = std::mem::discriminant(&self.trail_window) == std::mem::discriminant(&window);
+                                    if ui.selectable_label(selected, window.label()).clicked() && !selected {
+                                        self.trail_window = window;
+                                    }
+                                }
+                            });
+                        match &mut self.trail_window {
+                            TrailWindow::All => {}
+                            TrailWindow::Points(n) => {
+                                ui.add(egui::DragValue::new(n).range(0..=1_000_000).suffix(" pts"));
+                            }
+                            TrailWindow::Seconds(t) => {
+                                ui.add(egui::DragValue::new(t).speed(0.1).range(0.0..=86_400.0).suffix(" s"));
+                            }
+                        }
+                    })
+                    .response
+                    .on_hover_text("Only draw the newest part of the trail; 0 draws all of it");
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.gap_markers, "Gap markers")
+                            .on_hover_text("Mark where detection stopped: a wedge at the frame edge where the cursor left, a cross where it was lost inside the frame");
+                        ui.add(egui::DragValue::new(&mut self.edge_margin).speed(0.5).range(0.0..=500.0).prefix("edge ≤ ").suffix(" px"))
+                            .on_hover_text("How close to an edge the last detection has to be for a gap to count as the cursor leaving the frame");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.show_flicks, "Flicks")
+                            .on_hover_text("Mark where acceleration changed abruptly, judged on velocities over the kinematics window so noise doesn't count");
+                        ui.add(egui::DragValue::new(&mut self.jerk_threshold).speed(5000.0).range(1000.0..=1e9).prefix("jerk ≥ ").suffix(" px/s³"));
+                        if self.show_flicks {
+                            self.flick_analysis.update(&self.positions, self.kinematics_window, self.jerk_threshold);
+                            ui.label(format!("{} flicks", self.flick_analysis.flicks().len()));
+                        }
+                    });
+                    if self.gap_markers {
+                        self.gap_analysis.update(&self.positions, self.current_frame_size, self.edge_margin);
+                        let gaps = self.gap_analysis.gaps();
+                        let off_screen = gaps.iter().filter(|g| g.off_screen()).count();
+                        ui.label(format!("{} gaps: {} off-screen, {} lost", gaps.len(), off_screen, gaps.len() - off_screen));
+                    }
+                    egui::CollapsingHeader::new("Style").show(ui, |ui| {
+                        egui::Grid::new("overlay_style").num_columns(2).show(ui, |ui| {
+                            ui.label("Points:");
+                            ui.horizontal(|ui| {
+                                ui.color_edit_button_srgba(&mut self.style.point_color);
+                                ui.add(egui::DragValue::new(&mut self.style.point_radius).speed(0.1).range(0.0..=30.0).prefix("r "));
+                            });
+                            ui.end_row();
+                            ui.label("Line:");
+                            ui.horizontal(|ui| {
+                                ui.color_edit_button_srgba(&mut self.style.line_color);
+                                ui.add(egui::DragValue::new(&mut self.style.line_width).speed(0.1).range(0.0..=20.0).prefix("w "));
+                            });
+                            ui.end_row();
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Draw:");
+                            for render in TrailRender::ALL {
+                                ui.selectable_value(&mut self.style.render, render, render.label());
+                            }
+                        })
+                        .response
+                        .on_hover_text("T cycles through these");
+                        ui.add_enabled(self.style.render.lines(), egui::Checkbox::new(&mut self.style.spline, "Smooth curve"))
+                            .on_hover_text("Draw the line as a curve through the points, broken at gaps and cuts; only the drawing changes, not the data");
+                        ui.add_enabled_ui(self.style.render != TrailRender::Line, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.label("Marker every");
+                                ui.add(egui::DragValue::new(&mut self.style.marker_every).range(1..=1000));
+                                ui.label("points");
+                            });
+                        });
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut self.style.now_marker, "Mark current point");
+                            ui.add_enabled_ui(self.style.now_marker, |ui| {
+                                ui.color_edit_button_srgba(&mut self.style.now_color);
+                                ui.checkbox(&mut self.style.now_crosshair, "Crosshair");
+                            });
+                        });
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut self.style.point_labels, "Label points")
+                                .on_hover_text("Print the position next to trail points in view, for matching points to coordinates");
+                            ui.add_enabled_ui(self.style.point_labels, |ui| {
+                                ui.add(egui::DragValue::new(&mut self.style.label_every).range(1..=1000).prefix("every "));
+                                ui.checkbox(&mut self.style.label_frames, "Frame");
+                            });
+                        });
+                        ui.checkbox(&mut self.style.halo, "Contrast outline")
+                            .on_hover_text("Draw a dark halo under the trail so it stays visible on any background");
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut self.style.stamp, "Time stamp")
+                                .on_hover_text("Print the time and frame number on the video, in a box that scales with it");
+                            ui.add_enabled_ui(self.style.stamp, |ui| {
+                                egui::ComboBox::from_id_salt("stamp_corner")
+                                    .selected_text(self.style.stamp_corner.label())
+                                    .show_ui(ui, |ui| {
+                                        for corner in Corner::ALL {
+                                            ui.selectable_value(&mut self.style.stamp_corner, corner, corner.label());
+                                        }
+                                    });
+                                ui.add(egui::DragValue::new(&mut self.style.stamp_size).speed(0.2).range(6.0..=200.0).prefix("size "));
+                            });
+                        });
+                        if ui.button("Reset style").clicked() {
+                            self.style = OverlayStyle::default();
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.fade_trail, "Fade older points");
+                        ui.add_enabled(
+                            self.fade_trail,
+                            egui::DragValue::new(&mut self.fade_window).speed(0.05).range(0.1..=600.0).suffix(" s"),
+                        )
+                        .on_hover_text("Points this much older than the playhead are hidden, but still exported");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Colour:");
+                        ui.selectable_value(&mut self.trail_color, TrailColor::Solid, "Solid");
+                        ui.selectable_value(&mut self.trail_color, TrailColor::Speed, "Speed");
+                        ui.selectable_value(&mut self.trail_color, TrailColor::Stroke, "Stroke")
+                            .on_hover_text("A colour per stroke, pauses in grey; see the Strokes window");
+                        ui.selectable_value(&mut self.trail_color, TrailColor::Segment, "Segment")
+                            .on_hover_text("A colour per recording segment");
+                        ui.selectable_value(&mut self.trail_color, TrailColor::Time, "Time")
+                            .on_hover_text("Shade from the oldest shown point to the newest, so the order reads in a still");
+                    });
+                    if self.trail_color == TrailColor::Time {
+                        ui.horizontal(|ui| {
+                            ui.label("From");
+                            ui.color_edit_button_srgba(&mut self.style.time_colors[0]);
+                            ui.label("to");
+                            ui.color_edit_button_srgba(&mut self.style.time_colors[1]);
+                            if ui.small_button("Swap").clicked() {
+                                self.style.time_colors.swap(0, 1);
+                            }
+                        });
+                    }
+                    if self.trail_color == TrailColor::Speed {
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut self.speed_auto, "Auto range");
+                            ui.add_enabled_ui(!self.speed_auto, |ui| {
+                                ui.add(egui::DragValue::new(&mut self.speed_range[0]).speed(10.0).range(0.0..=1e6));
+                                ui.label("to");
+                                ui.add(egui::DragValue::new(&mut self.speed_range[1]).speed(10.0).range(0.0..=1e6));
+                                ui.label("px/s");
+                                self.speed_range[1] = self.speed_range[1].max(self.speed_range[0]);
+                            });
+                        });
+                    }
+                });
+            });
+
+            egui::CollapsingHeader::new("Detection").default_open(false).show(ui, |ui| {
+                let before = self.detection;
+                detection_grid(ui, &mut self.detection);
+                if self.current_frame_size[1] > 0 {
+                    let (probe, margin) = self.detection.probe_geometry(self.current_frame_size[1] as usize);
+                    ui.label(format!("At {} px: probe {} px, margin {} px", self.current_frame_size[1], probe, margin));
+                }
+                if self.detection != before {
+                    self.unbroken_pass = false;
+                    self.send(AppCommand::SetDetection(self.detection));
+                    if self.diagnosis.is_some() {
+                        // keep the counts in step while tuning
+                        let _ = self.cmd_tx.send(AppCommand::Diagnose);
+                    }
+                }
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    let paused = !self.is_playing && !self.is_simulating;
+                    if ui.add_enabled(self.texture.is_some() && paused, egui::Button::new("Diagnose frame"))
+                        .on_hover_text("Count where the heuristic rejects candidate tips on this frame")
+                        .clicked()
+                    {
+                        let _ = self.cmd_tx.send(AppCommand::Diagnose);
+                    }
+                    if self.diagnosis.is_some() && ui.small_button("✖").clicked() {
+                        self.diagnosis = None;
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let misses = self.frame_results.values().filter(|r| !r.detected).count();
+                    ui.label(format!("Missed on {} of {} frames", misses, self.frame_results.len()));
+                    ui.add_enabled_ui(misses > 0 && self.loading.is_none(), |ui| {
+                        if ui.small_button("⏴").on_hover_text("Previous frame detection missed on").clicked() {
+                            self.jump_to_miss(false);
+                        }
+                        if ui.small_button("⏵").on_hover_text("Next frame detection missed on").clicked() {
+                            self.jump_to_miss(true);
+                        }
+                    });
+                });
+                if let Some((frame, r)) = self.diagnosis {
+                    let stale = if frame == self.current_frame { "" } else { " (not the frame shown)" };
+                    ui.label(format!("Frame {}{}: {} bright candidates", frame, stale, r.candidates));
+                    egui::Grid::new("rejections").num_columns(2).striped(true).show(ui, |ui| {
+                        for (label, count) in [
+                            ("Right neighbour too bright", r.right),
+                            ("Vertical probe not bright", r.spine),
+                            ("Left probe not dark", r.left),
+                            ("Right probe not dark", r.right_edge),
+                            ("Too few diagonal hits", r.diagonal),
+                            ("Accepted", r.accepted),
+                        ] {
+                            ui.label(label);
+                            ui.add(egui::ProgressBar::new(count as f32 / r.candidates.max(1) as f32).text(count.to_string()));
+                            ui.end_row();
+                        }
+                    });
+                }
+            });
+
+            if self.compare.is_some() {
+                egui::CollapsingHeader::new("Compare").default_open(true).show(ui, |ui| {
+                    self.compare_controls(ui);
+                });
+            }
+
+            egui::CollapsingHeader::new("Annotation").default_open(false).show(ui, |ui| {
+                if ui.checkbox(&mut self.annotating, "Annotate frames").changed() && self.annotating {
+                    self.pause_playback();
+                    self.is_simulating = false;
+                }
+                ui.label("Click the cursor to label a frame. ←/→ step, Enter keeps the label and advances, Delete removes it.");
+                let current = match self.labels.get(&self.current_frame) {
+                    Some(p) => format!("{:.1}, {:.1}", p.pos[0], p.pos[1]),
+                    None => "unlabelled".to_string(),
+                };
+                ui.label(format!("Frame {}: {}", self.current_frame, current));
+                ui.label(format!("{} labelled frames", self.labels.len()));
+                ui.add_enabled_ui(!self.labels.is_empty(), |ui| {
+                    ui.horizontal(|ui| {
+                        for format in [ExportFormat::Csv, ExportFormat::Json] {
+                            if ui.button(format!("Export {}", format.label())).clicked() {
+                                self.export_labels(format);
+                            }
+                        }
+                        if ui.button("Clear labels").clicked() {
+                            self.labels.clear();
+                        }
+                    });
+                });
+            });
+
+            egui::CollapsingHeader::new("Layers").default_open(false).show(ui, |ui| {
+                let (mut activate, mut duplicate, mut merge, mut delete) = (None, None, None, None);
+                let single = self.trace_layers.len() == 1;
+                for k in 0..self.trace_layers.len() {
+                    let count = self.layer_points(k).len();
+                    let active = k == self.active_layer;
+                    let layer = &mut self.trace_layers[k];
+                    ui.push_id(k, |ui| {
+                        ui.horizontal(|ui| {
+                            if ui.radio(active, "").on_hover_text("Record into and edit this layer").clicked() {
+                                activate = Some(k);
+                            }
+                            ui.checkbox(&mut layer.visible, "");
+                            ui.color_edit_button_srgba(&mut layer.color)
+                                .on_hover_text("Drawn in this colour while another layer is active");
+                            ui.add(egui::TextEdit::singleline(&mut layer.name).desired_width(90.0));
+                            ui.weak(format!("{}", count));
+                        });
+                        ui.horizontal(|ui| {
+                            if ui.small_button("Duplicate").clicked() {
+                                duplicate = Some(k);
+                            }
+                            if ui.add_enabled(!active, egui::Button::new("Merge").small())
+                                .on_hover_text("Add this layer's points to the active one where it has none")
+                                .clicked()
+                            {
+                                merge = Some(k);
+                            }
+                            if ui.add_enabled(!single, egui::Button::new("✖").small()).clicked() {
+                                delete = Some(k);
+                            }
+                        });
+                    });
+                }
+                if ui.button("New layer").on_hover_text("Start an empty layer and record into it").clicked() {
+                    let n = self.trace_layers.len();
+                    self.trace_layers.push(TraceLayer::new(format!("Layer {}", n + 1), series_color(n), Vec::new()));
+                    activate = Some(n);
+                }
+                ui.add_enabled(!single, egui::Checkbox::new(&mut self.export_all_layers, "Export all layers"))
+                    .on_hover_text("Write each layer to its own file, named after it, instead of only the active one");
+                if let Some(k) = activate {
+                    self.activate_layer(k);
+                }
+                if let Some(k) = duplicate {
+                    self.duplicate_layer(k);
+                }
+                if let Some(k) = merge {
+                    self.merge_layer(k);
+                }
+                if let Some(k) = delete {
+                    self.delete_layer(k);
+                }
+            });
+
+            if !self.imported.is_empty() {
+                egui::CollapsingHeader::new("Imported traces").default_open(true).show(ui, |ui| {
+                    let mut remove = None;
+                    let mut export_distances = None;
+                    let mut to_layer = None;
+                    // a frame's worth of video, or a 60 fps one's
+                    let step = 1.0 / if self.video_fps > 0.0 { self.video_fps } else { 60.0 };
+                    for (i, trace) in self.imported.iter_mut().enumerate() {
+                        ui.push_id(i, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut trace.visible, "");
+                                ui.color_edit_button_srgba(&mut trace.color);
+                                ui.label(&trace.name);
+                                if !trace.metadata.is_empty() {
+                                    ui.toggle_value(&mut trace.show_info, "ℹ").on_hover_text("Trace info");
+                                }
+                                if ui.small_button("✖").clicked() {
+                                    remove = Some(i);
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Offset (s):");
+                                ui.add(egui::DragValue::new(&mut trace.time_offset).speed(0.01));
+                                ui.label("Scale:");
+                                ui.add(egui::DragValue::new(&mut trace.time_scale).speed(0.001).range(0.01..=100.0));
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Shift (px):");
+                                ui.add(egui::DragValue::new(&mut trace.pos_shift[0]).speed(0.5).prefix("x "));
+                                ui.add(egui::DragValue::new(&mut trace.pos_shift[1]).speed(0.5).prefix("y "));
+                                ui.label("Size:");
+                                ui.add(egui::DragValue::new(&mut trace.pos_scale).speed(0.001).range(0.01..=100.0))
+                                    .on_hover_text("Scales positions about the frame's top left corner, before the shift");
+                            });
+                            ui.label(format!("{} points", trace.points.len()));
+
+                            let placed = trace.placed();
+                            let series = align::distances(&self.positions, &placed, trace.time_offset);
+                            match align::stats(&series) {
+                                Some(s) => ui.label(format!("vs detected: mean {:.1} px, max {:.1} px over {} samples", s.mean, s.max, s.samples)),
+                                None => ui.label("No overlap with the detected trace"),
+                            };
+                            ui.horizontal(|ui| {
+                                if ui.add_enabled(!self.positions.is_empty(), egui::Button::new("Auto-align"))
+                                    .on_hover_text(format!("Set the offset within ±{} s of the current one where the mean distance to the detected trace is least", ALIGN_REACH))
+                                    .clicked()
+                                {
+                                    match align::best_offset(&self.positions, &placed, trace.time_offset, ALIGN_REACH, step) {
+                                        Some(offset) => trace.time_offset = offset,
+                                        None => self.notice = Some((Instant::now(), format!("{} doesn't overlap the detected trace within ±{} s", trace.name, ALIGN_REACH))),
+                                    }
+                                }
+                                if ui.add_enabled(!series.is_empty(), egui::Button::new("Export distances")).clicked() {
+                                    export_distances = Some(i);
+                                }
+                                if ui.button("To layer")
+                                    .on_hover_text("Copy the trace as aligned now into a layer of its own, to edit, merge or export like a detected one")
+                                    .clicked()
+                                {
+                                    to_layer = Some(i);
+                                }
+                            });
+                        });
+                        ui.separator();
+                    }
+                    if let Some(i) = remove {
+                        self.imported.remove(i);
+                    }
+                    if let Some(i) = export_distances {
+                        self.export_distances(i);
+                    }
+                    if let Some(i) = to_layer {
+                        self.imported_to_layer(i);
+                    }
+                });
+            }
+        });
+
+        self.show_analysis(ctx);
+
+        let central = if self.presenting { egui::Frame::none() } else { egui::Frame::central_panel(&ctx.style()) };
+        egui::CentralPanel::default().frame(central).show(ctx, |ui| {
+            if self.compare.as_ref().is_some_and(|c| c.stacked) {
+                let size = egui::vec2(ui.available_width(), (ui.available_height() - ui.spacing().item_spacing.y) / 2.0);
+                ui.vertical(|ui| {
+                    ui.allocate_ui(size, |ui| self.show_compare(ui));
+                    ui.allocate_ui(size, |ui| self.show_video(ctx, ui));
+                });
+            } else if self.compare.is_some() {
+                let size = egui::vec2((ui.available_width() - ui.spacing().item_spacing.x) / 2.0, ui.available_height());
+                ui.horizontal_top(|ui| {
+                    ui.allocate_ui(size, |ui| self.show_video(ctx, ui));
+                    ui.allocate_ui(size, |ui| self.show_compare(ui));
+                });
+            } else {
+                self.show_video(ctx, ui);
+            }
+        });
+
+        self.show_loupe(ctx);
+        self.show_minimap(ctx);
+    }
+}
+
+fn main() -> eframe::Result<()> {
+    env_logger::init();
+    let fullscreen = match cli::parse(std::env::args_os().skip(1)) {
+        Ok(Mode::Gui { fullscreen }) => fullscreen,
+        Ok(Mode::Analyze(args)) => std::process::exit(headless::run_analyze(&args)),
+        Ok(Mode::Batch(args)) => std::process::exit(headless::run_batch(&args)),
+        Ok(Mode::Serve) => std::process::exit(rpc::run()),
+        Err(e) => {
+            if !e.is_empty() {
+                eprintln!("{}\n", e);
+            }
+            eprintln!("{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    // The size is only the first-launch default, after that eframe restores
+    // the last window geometry from its storage.
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default().with_inner_size([1300.0, 800.0]),
+        viewport: egui::ViewportBuilder::default()
+            .with_inner_size([1300.0, 800.0])
+            .with_fullscreen(fullscreen),
+        persist_window: true,
         ..Default::default()
     };
     eframe::run_native(
-        "Cursor analyser",
+        APP_NAME,
         options,
-        Box::new(|_cc| Ok(Box::new(VideoApp::new()))),
+        Box::new(move |cc| Ok(Box::new(VideoApp::new(fullscreen, cc.storage)))),
     )
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Steps sent over `seconds` of UI updates `ui_hz` times a second.
+    fn play(fps: f64, speed: f64, ui_hz: f64, seconds: f64) -> u64 {
+        let interval = 1.0 / (fps * speed);
+        let (mut clock, mut steps) = (0.0, 0);
+        for update in 1..=(seconds * ui_hz).round() as u64 {
+            let now = update as f64 / ui_hz;
+            let (due, advance) = steps_due(now - clock, interval, None);
+            steps += due;
+            clock = advance.map_or(now, |a| clock + a);
+        }
+        steps
+    }
+
+    #[test]
+    fn plays_in_real_time() {
+        for fps in [24.0, 30.0, 60.0, 144.0] {
+            let steps = play(fps, 1.0, 60.0, 1.0);
+            assert!((steps as f64 - fps).abs() <= 1.0, "{} steps in a second at {} fps", steps, fps);
+        }
+        assert!((play(30.0, 2.0, 60.0, 1.0) as f64 - 60.0).abs() <= 1.0);
+        assert!((play(30.0, 0.25, 60.0, 4.0) as f64 - 30.0).abs() <= 1.0);
+    }
+
+    #[test]
+    fn catch_up_is_capped() {
+        let interval = 1.0 / 30.0;
+        // a long stall sends no burst and restarts the clock
+        assert_eq!(steps_due(10.0, interval, None), (MAX_STEPS_PER_UPDATE, None));
+        // a little behind catches up, keeping the remainder
+        let (due, advance) = steps_due(3.5 * interval, interval, None);
+        assert_eq!(due, 3);
+        assert!((advance.unwrap() - 3.0 * interval).abs() < 1e-12);
+        assert_eq!(steps_due(3.5 * interval, interval, Some(2)).0, 2);
+        // a 480 fps source on a 60 Hz display plays no faster than the cap
+        assert_eq!(play(480.0, 1.0, 60.0, 1.0), 60 * MAX_STEPS_PER_UPDATE);
+    }
+}
@@ -0,0 +1,56 @@
+use crate::trace::TracePoint;
+
+/// Median of `values`, the mean of the middle two for an even count.
+fn median(values: &mut [f32]) -> f32 {
+    values.sort_unstable_by(f32::total_cmp);
+    let mid = values.len() / 2;
+    if values.len() % 2 == 1 {
+        values[mid]
+    } else {
+        (values[mid - 1] + values[mid]) / 2.0
+    }
+}
+
+/// Temporal median filter: each point's x and y become the medians over a
+/// window of `window` samples around it. Unlike a mean, a single-frame
+/// outlier can't pull its neighbours off, and steps stay sharp. An even
+/// window reaches one sample further back than ahead; at the ends the window
+/// is cut short rather than padded.
+pub fn median_filter(points: &[TracePoint], window: usize) -> Vec<TracePoint> {
+    if window < 2 || points.len() < 2 {
+        return points.to_vec();
+    }
+
+    let (before, after) = (window / 2, (window - 1) / 2);
+    let mut xs = Vec::with_capacity(window);
+    let mut ys = Vec::with_capacity(window);
+    points.iter().enumerate()
+        .map(|(i, p)| {
+            let around = &points[i.saturating_sub(before)..(i + after + 1).min(points.len())];
+            xs.clear();
+            ys.clear();
+            xs.extend(around.iter().map(|q| q.pos[0]));
+            ys.extend(around.iter().map(|q| q.pos[1]));
+            TracePoint { pos: [median(&mut xs), median(&mut ys)], ..*p }
+        })
+        .collect()
+}
+
+/// Remembers the last filtered trace so it is only redone when the trace or
+/// the window changes, not on every repaint.
+#[derive(Default)]
+pub struct MedianCache {
+    key: Option<(usize, usize, Option<u64>)>,
+    points: Vec<TracePoint>,
+}
+
+impl MedianCache {
+    pub fn get(&mut self, points: &[TracePoint], window: usize) -> &[TracePoint] {
+        let key = (points.len(), window, points.last().map(|p| p.frame));
+        if self.key != Some(key) {
+            self.points = median_filter(points, window);
+            self.key = Some(key);
+        }
+        &self.points
+    }
+}
@@ -0,0 +1,88 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Extensions opened as an ffmpeg concat playlist rather than a video.
+pub const EXTENSIONS: [&str; 2] = ["ffconcat", "txt"];
+
+/// One `file` entry of a playlist.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clip {
+    /// Resolved against the playlist's folder, as ffmpeg does.
+    pub path: PathBuf,
+    /// `inpoint` and `outpoint`, seconds into the clip.
+    pub inpoint: Option<f64>,
+    pub outpoint: Option<f64>,
+}
+
+pub fn is_playlist(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| EXTENSIONS.contains(&e.to_string_lossy().to_lowercase().as_str()))
+}
+
+/// Adds `path` as ffmpeg's input, through the concat demuxer if it is a
+/// playlist. `-safe 0` lets entries be absolute or climb out of the
+/// playlist's folder.
+pub fn input(cmd: &mut Command, path: &Path) {
+    if is_playlist(path) {
+        cmd.args(["-f", "concat", "-safe", "0"]);
+    }
+    cmd.arg("-i").arg(path);
+}
+
+/// Undoes the concat format's quoting: single quotes around any part of
+/// the token, and backslash escapes outside them.
+fn unquote(token: &str) -> String {
+    let mut out = String::new();
+    let mut chars = token.chars();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => quoted = !quoted,
+            '\\' if !quoted => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// The clips a playlist lists, in order. Directives other than `file`,
+/// `inpoint` and `outpoint` are left to ffmpeg.
+pub fn parse(text: &str, folder: &Path) -> Result<Vec<Clip>, String> {
+    let mut clips: Vec<Clip> = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (directive, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        match directive {
+            "file" => clips.push(Clip { path: folder.join(unquote(rest)), inpoint: None, outpoint: None }),
+            "inpoint" | "outpoint" => {
+                let seconds = parse_time(rest).ok_or_else(|| format!("Line {}: cannot read the time {:?}", n + 1, rest))?;
+                let clip = clips.last_mut().ok_or_else(|| format!("Line {}: {} before any file", n + 1, directive))?;
+                if directive == "inpoint" {
+                    clip.inpoint = Some(seconds);
+                } else {
+                    clip.outpoint = Some(seconds);
+                }
+            }
+            _ => {}
+        }
+    }
+    if clips.is_empty() {
+        return Err("no file entries".to_string());
+    }
+    Ok(clips)
+}
+
+/// Seconds, or `[HH:]MM:SS[.m]` as ffmpeg writes durations.
+fn parse_time(s: &str) -> Option<f64> {
+    s.split(':').try_fold(0.0, |total, part| Some(total * 60.0 + part.parse::<f64>().ok()?))
+}
+
+pub fn read(path: &Path) -> Result<Vec<Clip>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Cannot read playlist {}: {}", path.display(), e))?;
+    let folder = path.parent().unwrap_or(Path::new(""));
+    parse(&text, folder).map_err(|e| format!("Cannot use playlist {}: {}", path.display(), e))
+}
@@ -0,0 +1,209 @@
+use crate::calibration::Transform;
+use crate::kinematics;
+use crate::trace::TracePoint;
+
+/// A series of `[time, value]` samples from `items`, reduced to at most the
+/// lowest and highest value per pixel column so long traces plot in
+/// constant time and spikes survive however far the plot is zoomed out.
+/// Only items within `range` (seconds) are kept, plus one either side so
+/// lines reach the edges. `items` must be in time order.
+pub fn min_max<T>(items: &[T], sample: impl Fn(&T) -> [f64; 2], range: [f64; 2], columns: usize) -> Vec<[f64; 2]> {
+    let start = items.partition_point(|p| sample(p)[0] < range[0]).saturating_sub(1);
+    let end = (items.partition_point(|p| sample(p)[0] <= range[1]) + 1).min(items.len());
+    let visible = &items[start..end.max(start)];
+
+    let columns = columns.max(1);
+    if visible.len() <= 2 * columns {
+        return visible.iter().map(&sample).collect();
+    }
+
+    let width = (range[1] - range[0]) / columns as f64;
+    let mut out = Vec::with_capacity(2 * columns + 2);
+    // column index, then its lowest and highest sample so far
+    let mut bucket: Option<(i64, [f64; 2], [f64; 2])> = None;
+    let flush = |out: &mut Vec<[f64; 2]>, low: [f64; 2], high: [f64; 2]| {
+        // in time order, so the line doesn't double back
+        let (first, second) = if low[0] <= high[0] { (low, high) } else { (high, low) };
+        out.push(first);
+        if second != first {
+            out.push(second);
+        }
+    };
+    for p in visible {
+        let s = sample(p);
+        let column = ((s[0] - range[0]) / width).floor() as i64;
+        match &mut bucket {
+            Some((c, low, high)) if *c == column => {
+                if s[1] < low[1] {
+                    *low = s;
+                }
+                if s[1] > high[1] {
+                    *high = s;
+                }
+            }
+            _ => {
+                if let Some((_, low, high)) = bucket {
+                    flush(&mut out, low, high);
+                }
+                bucket = Some((column, s, s));
+            }
+        }
+    }
+    if let Some((_, low, high)) = bucket {
+        flush(&mut out, low, high);
+    }
+    out
+}
+
+/// A stretch of the trace faster than the speed threshold.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Burst {
+    pub start: f64,
+    pub end: f64,
+    pub peak: f64,
+}
+
+type SpeedKey = (usize, Option<u64>, usize, Option<Transform>, u64);
+
+/// Speed against time for the speed plot, split where the kinematics have
+/// no value (gaps and the ends of runs) so those plot as breaks, not as
+/// zero. Only recomputed when the trace or the settings change.
+#[derive(Default)]
+pub struct SpeedSeries {
+    key: Option<SpeedKey>,
+    runs: Vec<Vec<[f64; 2]>>,
+    bursts: Vec<Burst>,
+}
+
+impl SpeedSeries {
+    /// `transform` maps into playfield units first, so speeds come out in
+    /// playfield units per second.
+    pub fn update(&mut self, points: &[TracePoint], window: usize, transform: Option<Transform>, threshold: f64) {
+        let key = (points.len(), points.last().map(|p| p.frame), window, transform, threshold.to_bits());
+        if self.key == Some(key) {
+            return;
+        }
+        let recompute = self.key.is_none_or(|k| (k.0, k.1, k.2, k.3) != (key.0, key.1, key.2, key.3));
+        self.key = Some(key);
+
+        if recompute {
+            let mapped;
+            let points = match &transform {
+                Some(t) => {
+                    mapped = t.map_points(points);
+                    &mapped[..]
+                }
+                None => points,
+            };
+            self.runs.clear();
+            let mut run = Vec::new();
+            for (p, k) in points.iter().zip(kinematics::compute(points, window)) {
+                match k.speed() {
+                    Some(speed) => run.push([p.time, speed]),
+                    None if !run.is_empty() => self.runs.push(std::mem::take(&mut run)),
+                    None => {}
+                }
+            }
+            if !run.is_empty() {
+                self.runs.push(run);
+            }
+        }
+
+        self.bursts.clear();
+        for run in &self.runs {
+            let mut current: Option<Burst> = None;
+            for &[time, speed] in run {
+                match (&mut current, speed > threshold) {
+                    (Some(b), true) => {
+                        b.end = time;
+                        b.peak = b.peak.max(speed);
+                    }
+                    (None, true) => current = Some(Burst { start: time, end: time, peak: speed }),
+                    (Some(_), false) => self.bursts.extend(current.take()),
+                    (None, false) => {}
+                }
+            }
+            self.bursts.extend(current);
+        }
+    }
+
+    /// Unbroken stretches of `[time, speed]`, in time order.
+    pub fn runs(&self) -> &[Vec<[f64; 2]>] {
+        &self.runs
+    }
+
+    pub fn bursts(&self) -> &[Burst] {
+        &self.bursts
+    }
+}
+
+/// Median, 95th percentile and maximum of the per-segment speeds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpeedStats {
+    pub segments: usize,
+    pub median: f64,
+    pub p95: f64,
+    pub max: f64,
+}
+
+type HistogramKey = (usize, Option<u64>, Option<Transform>, usize);
+
+/// Distribution of per-segment speeds, see [`kinematics::segment_speeds`],
+/// in equal bins from zero to the fastest segment. Only recomputed when the
+/// trace or the settings change, so it can follow a running scan.
+#[derive(Default)]
+pub struct SpeedHistogram {
+    key: Option<HistogramKey>,
+    counts: Vec<u32>,
+    bin_width: f64,
+    stats: Option<SpeedStats>,
+}
+
+impl SpeedHistogram {
+    pub fn update(&mut self, points: &[TracePoint], transform: Option<Transform>, bins: usize) {
+        let key = (points.len(), points.last().map(|p| p.frame), transform, bins);
+        if self.key == Some(key) {
+            return;
+        }
+        self.key = Some(key);
+
+        let segments = match &transform {
+            Some(t) => kinematics::segment_speeds(&t.map_points(points)),
+            None => kinematics::segment_speeds(points),
+        };
+        let mut speeds: Vec<f64> = segments.into_iter().flatten().filter(|s| s.is_finite()).collect();
+        let bins = bins.max(1);
+        self.counts = vec![0; bins];
+        self.stats = None;
+        if speeds.is_empty() {
+            return;
+        }
+
+        let max = speeds.iter().copied().fold(0.0, f64::max);
+        self.bin_width = if max > 0.0 { max / bins as f64 } else { 1.0 };
+        for &s in &speeds {
+            self.counts[((s / self.bin_width) as usize).min(bins - 1)] += 1;
+        }
+
+        // nearest rank, without sorting the whole trace
+        let segments = speeds.len();
+        let mut rank = |q: f64| {
+            let i = (q * (segments - 1) as f64).round() as usize;
+            *speeds.select_nth_unstable_by(i, f64::total_cmp).1
+        };
+        self.stats = Some(SpeedStats { segments, median: rank(0.5), p95: rank(0.95), max });
+    }
+
+    /// `(bin start, count)` for every bin.
+    pub fn bins(&self) -> impl Iterator<Item = (f64, u32)> + '_ {
+        self.counts.iter().enumerate().map(|(i, &c)| (i as f64 * self.bin_width, c))
+    }
+
+    pub fn bin_width(&self) -> f64 {
+        self.bin_width
+    }
+
+    pub fn stats(&self) -> Option<SpeedStats> {
+        self.stats
+    }
+}
@@ -0,0 +1,33 @@
+use std::borrow::Cow;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+/// An absolute path's folders: a separator, drive letter or `~` after a
+/// space, quote or bracket, then names each ending in a separator. Names
+/// may hold spaces but not end in one, so `/a and /b` stays two paths.
+const FOLDERS: &str = r#"(^|[\s'"(\[=])(?:[A-Za-z]:|~)?[\\/]{1,2}(?:[^\\/:*?"'<>|\r\n]*[^\\/:*?"'<>|\s][\\/])+"#;
+
+/// `text` with the folders of every absolute path in it cut to `…/`, so
+/// file names stay readable but not where they are.
+pub fn redact(text: &str) -> Cow<'_, str> {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(FOLDERS).unwrap()).replace_all(text, "${1}…/")
+}
+
+/// `text` as it goes on screen, redacted in privacy mode.
+pub fn shown(text: &str, private: bool) -> Cow<'_, str> {
+    if private {
+        redact(text)
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// What to call a file on screen: its name, or only its stem in privacy
+/// mode.
+pub fn file_name(path: &Path, private: bool) -> Cow<'_, str> {
+    let name = if private { path.file_stem() } else { path.file_name() };
+    name.unwrap_or(path.as_os_str()).to_string_lossy()
+}
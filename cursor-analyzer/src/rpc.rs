@@ -0,0 +1,154 @@
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel::{unbounded, Sender};
+use ffmpeg_sidecar::download::auto_download;
+use serde::{Deserialize, Serialize};
+
+use crate::audio::WAVEFORM_RATE;
+use crate::detect::{DetectionParams, Rejections};
+use crate::trace::TracePoint;
+use crate::worker::{video_worker, AppCommand, AppEvent, LogLevel, ScanScope, VideoStream, WorkerStats};
+
+/// One line of input, e.g. `{"cmd":"seek","t":1.5}`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+enum RpcCommand {
+    Open { path: PathBuf },
+    Seek { t: f64 },
+    Preview { t: f64 },
+    Step,
+    StepBack,
+    Advance { secs: f64 },
+    SetDetection { params: DetectionParams },
+    SetScope { scope: ScanScope },
+    SetRange { start: f64, end: f64 },
+    Diagnose,
+    SetFrameCache { frames: usize },
+    SetProbeTimeout { secs: f64 },
+    Waveform,
+    Rescan { start: f64, end: f64 },
+    SetVideoStream { index: Option<usize> },
+    Quit,
+}
+
+impl RpcCommand {
+    fn into_app_command(self) -> Option<AppCommand> {
+        Some(match self {
+            RpcCommand::Open { path } => AppCommand::LoadFile(path),
+            RpcCommand::Seek { t } => AppCommand::Seek(t),
+            RpcCommand::Preview { t } => AppCommand::Preview(t),
+            RpcCommand::Step => AppCommand::Step,
+            RpcCommand::StepBack => AppCommand::StepBack,
+            RpcCommand::Advance { secs } => AppCommand::Advance(secs),
+            RpcCommand::SetDetection { params } => AppCommand::SetDetection(params),
+            RpcCommand::SetScope { scope } => AppCommand::SetScope(scope),
+            RpcCommand::SetRange { start, end } => AppCommand::SetRange(start, end),
+            RpcCommand::Diagnose => AppCommand::Diagnose,
+            RpcCommand::SetFrameCache { frames } => AppCommand::SetFrameCache(frames),
+            RpcCommand::SetProbeTimeout { secs } => AppCommand::SetProbeTimeout(Duration::from_secs_f64(secs.max(0.0))),
+            RpcCommand::Waveform => AppCommand::LoadWaveform,
+            RpcCommand::Rescan { start, end } => AppCommand::Rescan { start, end },
+            RpcCommand::SetVideoStream { index } => AppCommand::SetVideoStream(index),
+            RpcCommand::Quit => return None,
+        })
+    }
+}
+
+/// One line of output. Mirrors [`AppEvent`] without the frame pixels.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum RpcEvent {
+    LoadStarted { path: PathBuf },
+    VideoStreams { streams: Vec<VideoStream>, selected: usize },
+    Metadata { duration: f64, width: u32, height: u32, fps: f64 },
+    Frame {
+        frame: u64,
+        time: f64,
+        position: Option<[f32; 2]>,
+        angle: Option<f32>,
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        preview: bool,
+    },
+    Diagnosis { frame: u64, rejections: Rejections },
+    FrameCache { cached: usize },
+    Stats(WorkerStats),
+    Log { level: LogLevel, line: String },
+    Waveform { path: PathBuf, rate: u32, levels: Vec<f32> },
+    RescanProgress { fraction: f64 },
+    Rescanned { points: Vec<TracePoint> },
+    EndOfStream,
+    Error { message: String },
+}
+
+impl From<AppEvent> for RpcEvent {
+    fn from(event: AppEvent) -> Self {
+        match event {
+            AppEvent::LoadStarted { path } => RpcEvent::LoadStarted { path },
+            AppEvent::VideoStreams { streams, selected } => RpcEvent::VideoStreams { streams, selected },
+            AppEvent::Metadata { duration, width, height, fps } => RpcEvent::Metadata { duration, width, height, fps },
+            AppEvent::FrameReady { frame, time, position, angle, preview, .. } => {
+                RpcEvent::Frame { frame, time, position, angle, preview }
+            }
+            AppEvent::Diagnosis { frame, rejections } => RpcEvent::Diagnosis { frame, rejections },
+            AppEvent::FrameCache { cached } => RpcEvent::FrameCache { cached },
+            AppEvent::Stats(stats) => RpcEvent::Stats(stats),
+            AppEvent::Log { level, line } => RpcEvent::Log { level, line },
+            AppEvent::RescanProgress(fraction) => RpcEvent::RescanProgress { fraction },
+            AppEvent::Rescanned(points) => RpcEvent::Rescanned { points },
+            AppEvent::Waveform { path, levels } => RpcEvent::Waveform { path, rate: WAVEFORM_RATE, levels },
+            AppEvent::EndOfStream => RpcEvent::EndOfStream,
+            AppEvent::Error(message) => RpcEvent::Error { message },
+        }
+    }
+}
+
+/// Forwards commands from stdin to the worker until EOF or `quit`.
+/// Lines that don't parse are answered with an error event.
+fn read_commands(cmd_tx: Sender<AppCommand>, event_tx: Sender<AppEvent>) {
+    for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<RpcCommand>(&line) {
+            Ok(cmd) => match cmd.into_app_command() {
+                Some(cmd) => {
+                    let _ = cmd_tx.send(cmd);
+                }
+                None => break,
+            },
+            Err(e) => {
+                let _ = event_tx.send(AppEvent::Error(format!("bad command: {}", e)));
+            }
+        }
+    }
+}
+
+/// `serve` entry point: drives the worker with newline-delimited JSON
+/// commands on stdin and writes its events as JSON lines to stdout.
+/// Returns the process exit code.
+pub fn run() -> i32 {
+    if let Err(e) = auto_download() {
+        eprintln!("Failed to download ffmpeg: {}", e);
+    }
+
+    let (cmd_tx, cmd_rx) = unbounded();
+    let (event_tx, event_rx) = unbounded();
+    let reader_events = event_tx.clone();
+    thread::spawn(move || video_worker(cmd_rx, event_tx));
+    // the worker stops once the reader drops the last command sender, which
+    // in turn closes the event channel and ends the loop below
+    thread::spawn(move || read_commands(cmd_tx, reader_events));
+
+    let mut out = std::io::stdout().lock();
+    for event in event_rx {
+        let line = serde_json::to_string(&RpcEvent::from(event)).expect("events always serialize");
+        if writeln!(out, "{}", line).and_then(|_| out.flush()).is_err() {
+            // nobody is listening any more
+            return 1;
+        }
+    }
+    0
+}
@@ -0,0 +1,71 @@
+use crate::trace::TracePoint;
+
+fn distance_to_segment(p: [f32; 2], a: [f32; 2], b: [f32; 2]) -> f32 {
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+    let len_sq = dx * dx + dy * dy;
+    if len_sq == 0.0 {
+        return (p[0] - a[0]).hypot(p[1] - a[1]);
+    }
+    let t = (((p[0] - a[0]) * dx + (p[1] - a[1]) * dy) / len_sq).clamp(0.0, 1.0);
+    (p[0] - (a[0] + t * dx)).hypot(p[1] - (a[1] + t * dy))
+}
+
+/// Douglas–Peucker simplification: drops points that lie within `epsilon`
+/// pixels of the line through their retained neighbours. The first and last
+/// points are always kept.
+pub fn douglas_peucker(points: &[TracePoint], epsilon: f32) -> Vec<TracePoint> {
+    if points.len() < 3 || epsilon <= 0.0 {
+        return points.to_vec();
+    }
+
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+
+    // explicit stack so long traces can't overflow the call stack
+    let mut stack = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = stack.pop() {
+        let (a, b) = (points[first].pos, points[last].pos);
+        let mut max_dist = 0.0;
+        let mut index = first;
+        for (i, p) in points.iter().enumerate().take(last).skip(first + 1) {
+            let d = distance_to_segment(p.pos, a, b);
+            if d > max_dist {
+                max_dist = d;
+                index = i;
+            }
+        }
+        if max_dist > epsilon {
+            keep[index] = true;
+            stack.push((first, index));
+            stack.push((index, last));
+        }
+    }
+
+    points.iter().zip(keep).filter(|(_, k)| *k).map(|(p, _)| *p).collect()
+}
+
+/// Remembers the last simplification so it is only redone when the trace or
+/// epsilon changes, not on every repaint.
+#[derive(Default)]
+pub struct SimplifyCache {
+    key: Option<(usize, u32, Option<u64>)>,
+    points: Vec<TracePoint>,
+}
+
+impl SimplifyCache {
+    pub fn get(&mut self, points: &[TracePoint], epsilon: f32) -> &[TracePoint] {
+        let key = (points.len(), epsilon.to_bits(), points.last().map(|p| p.frame));
+        if self.key != Some(key) {
+            self.points = douglas_peucker(points, epsilon);
+            self.key = Some(key);
+        }
+        &self.points
+    }
+
+    /// Forgets the last result, for when the points changed in a way the
+    /// key doesn't see, such as being filtered differently.
+    pub fn clear(&mut self) {
+        self.key = None;
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+
+use crate::trace::TracePoint;
+
+/// Side of a grid cell, in frame pixels.
+const CELL: f32 = 16.0;
+
+fn cell(v: f32) -> i32 {
+    (v / CELL).floor() as i32
+}
+
+/// A trace's points bucketed by position, so finding the one under the
+/// mouse only looks at the cells around it rather than at every point.
+/// Only rebuilt when the trace changes, and only extended while it grows.
+#[derive(Default)]
+pub struct PointGrid {
+    key: Option<(usize, Option<u64>)>,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl PointGrid {
+    pub fn update(&mut self, points: &[TracePoint]) {
+        let key = (points.len(), points.last().map(|p| p.frame));
+        if self.key == Some(key) {
+            return;
+        }
+        // appended to since, as happens on every frame while detecting
+        let from = match self.key {
+            Some((len, Some(last))) if len < points.len() && points[len - 1].frame == last => len,
+            _ => {
+                self.cells.clear();
+                0
+            }
+        };
+        self.key = Some(key);
+        for (i, p) in points.iter().enumerate().skip(from) {
+            self.cells.entry((cell(p.pos[0]), cell(p.pos[1]))).or_default().push(i);
+        }
+    }
+
+    /// Forgets the grid, for when the points changed in a way the key
+    /// doesn't see.
+    pub fn clear(&mut self) {
+        self.key = None;
+    }
+
+    /// Index of the point nearest `at` and no further than `reach`, both in
+    /// frame pixels, among those `keep` accepts. `points` must be what the
+    /// grid was last updated with.
+    pub fn nearest(&self, points: &[TracePoint], at: [f32; 2], reach: f32, keep: impl Fn(&TracePoint) -> bool) -> Option<usize> {
+        let (x0, x1) = (cell(at[0] - reach), cell(at[0] + reach));
+        let (y0, y1) = (cell(at[1] - reach), cell(at[1] + reach));
+        (x0..=x1)
+            .flat_map(|x| (y0..=y1).map(move |y| (x, y)))
+            .filter_map(|c| self.cells.get(&c))
+            .flatten()
+            .map(|&i| (i, (points[i].pos[0] - at[0]).hypot(points[i].pos[1] - at[1])))
+            .filter(|&(i, d)| d <= reach && keep(&points[i]))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+    }
+}
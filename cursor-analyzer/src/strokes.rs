@@ -0,0 +1,149 @@
+use crate::calibration::Transform;
+use crate::kinematics;
+use crate::trace::TracePoint;
+
+/// What counts as a pause between strokes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StrokeParams {
+    /// Segments slower than this (px/s, or playfield units per second when
+    /// calibrated) count as resting.
+    pub pause_speed: f64,
+    /// Resting for at least this long in seconds ends a stroke. Shorter
+    /// hesitations stay part of it.
+    pub pause_min: f64,
+}
+
+impl Default for StrokeParams {
+    fn default() -> Self {
+        Self { pause_speed: 50.0, pause_min: 0.15 }
+    }
+}
+
+/// A stretch of movement between two pauses or detection gaps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stroke {
+    /// First and last trace index, inclusive.
+    pub first: usize,
+    pub last: usize,
+    pub start: f64,
+    pub end: f64,
+    /// Path length along the samples.
+    pub length: f64,
+    pub peak_speed: f64,
+    /// Straight-line distance from start to end over path length, 1 for a
+    /// straight stroke.
+    pub straightness: f64,
+}
+
+impl Stroke {
+    pub fn duration(&self) -> f64 {
+        self.end - self.start
+    }
+}
+
+type TraceKey = (usize, Option<u64>, Option<Transform>);
+
+/// The trace split into strokes. The per-segment lengths are only
+/// recomputed when the trace changes, so moving the thresholds
+/// re-segments in a single pass.
+#[derive(Default)]
+pub struct Strokes {
+    trace_key: Option<TraceKey>,
+    params: Option<StrokeParams>,
+    /// Mapped positions and times, and the length and speed of each
+    /// segment after them, `None` across gaps.
+    positions: Vec<[f64; 2]>,
+    times: Vec<f64>,
+    segments: Vec<Option<(f64, f64)>>,
+    strokes: Vec<Stroke>,
+}
+
+impl Strokes {
+    /// `transform` maps into playfield units first, so lengths and speeds
+    /// come out in playfield units.
+    pub fn update(&mut self, points: &[TracePoint], transform: Option<Transform>, params: StrokeParams) {
+        let trace_key = (points.len(), points.last().map(|p| p.frame), transform);
+        if self.trace_key == Some(trace_key) && self.params == Some(params) {
+            return;
+        }
+        if self.trace_key != Some(trace_key) {
+            self.trace_key = Some(trace_key);
+            let mapped;
+            let points = match &transform {
+                Some(t) => {
+                    mapped = t.map_points(points);
+                    &mapped[..]
+                }
+                None => points,
+            };
+            self.positions = points.iter().map(|p| [p.pos[0] as f64, p.pos[1] as f64]).collect();
+            self.times = points.iter().map(|p| p.time).collect();
+            self.segments = kinematics::segment_speeds(points)
+                .into_iter()
+                .zip(points.windows(2))
+                .map(|(speed, w)| speed.map(|s| (s * (w[1].time - w[0].time), s)))
+                .collect();
+        }
+        self.params = Some(params);
+        self.strokes.clear();
+
+        // segment indices of the stroke being built
+        let mut current: Option<(usize, usize)> = None;
+        // start of the resting segments seen since the last movement
+        let mut rest: Option<usize> = None;
+        for i in 0..=self.segments.len() {
+            match self.segments.get(i).copied().flatten() {
+                Some((_, speed)) if speed < params.pause_speed => {
+                    let from = *rest.get_or_insert(i);
+                    if self.times[i + 1] - self.times[from] >= params.pause_min {
+                        // long enough to end the stroke before the rest began
+                        if let Some((first, _)) = current.take() {
+                            self.close(first, from);
+                        }
+                    }
+                }
+                Some(_) => {
+                    rest = None;
+                    current = Some((current.map_or(i, |c| c.0), i + 1));
+                }
+                None => {
+                    // a gap or the end of the trace
+                    rest = None;
+                    if let Some((first, end)) = current.take() {
+                        self.close(first, end);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Adds the stroke over segments `first..end`.
+    fn close(&mut self, first: usize, end: usize) {
+        let segments = self.segments[first..end].iter().flatten();
+        let (length, peak_speed) = segments.fold((0.0, 0.0_f64), |(l, p), &(length, speed)| (l + length, p.max(speed)));
+        if length <= 0.0 {
+            return;
+        }
+        let (a, b) = (self.positions[first], self.positions[end]);
+        let chord = (b[0] - a[0]).hypot(b[1] - a[1]);
+        self.strokes.push(Stroke {
+            first,
+            last: end,
+            start: self.times[first],
+            end: self.times[end],
+            length,
+            peak_speed,
+            straightness: (chord / length).min(1.0),
+        });
+    }
+
+    pub fn strokes(&self) -> &[Stroke] {
+        &self.strokes
+    }
+
+    /// Index of the stroke covering `time`, if any.
+    pub fn at(&self, time: f64) -> Option<usize> {
+        let i = self.strokes.partition_point(|s| s.end < time);
+        self.strokes.get(i).filter(|s| s.start <= time).map(|_| i)
+    }
+}
@@ -0,0 +1,110 @@
+use std::cmp::Ordering;
+
+use crate::kinematics;
+use crate::trace::TracePoint;
+
+/// Columns of the positions table that it can be sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortColumn {
+    #[default]
+    Index,
+    Time,
+    X,
+    Y,
+    Angle,
+    Speed,
+}
+
+impl SortColumn {
+    pub const ALL: [SortColumn; 6] = [
+        SortColumn::Index,
+        SortColumn::Time,
+        SortColumn::X,
+        SortColumn::Y,
+        SortColumn::Angle,
+        SortColumn::Speed,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SortColumn::Index => "#",
+            SortColumn::Time => "Time (s)",
+            SortColumn::X => "X",
+            SortColumn::Y => "Y",
+            SortColumn::Angle => "Angle",
+            SortColumn::Speed => "Speed (px/s)",
+        }
+    }
+}
+
+/// Everything the listed rows depend on: trace length and last frame, sort,
+/// direction, filter bounds and speed window.
+type ViewKey = (usize, Option<u64>, SortColumn, bool, Option<[u64; 2]>, usize);
+
+/// Which points of a trace the positions table lists and in what order.
+/// Only rebuilt when the trace, the sort or the filter change, so that a
+/// table over a long trace costs no more per frame than its visible rows.
+#[derive(Default)]
+pub struct TableView {
+    pub sort: SortColumn,
+    pub descending: bool,
+    /// Only list points within this time range, in seconds.
+    pub filter: Option<[f64; 2]>,
+    /// Differentiation window for the speed column, as for exports.
+    pub window: usize,
+    key: Option<ViewKey>,
+    rows: Vec<usize>,
+    speeds: Vec<Option<f64>>,
+}
+
+impl TableView {
+    pub fn update(&mut self, points: &[TracePoint]) {
+        let filter_bits = self.filter.map(|[a, b]| [a.to_bits(), b.to_bits()]);
+        let key = (points.len(), points.last().map(|p| p.frame), self.sort, self.descending, filter_bits, self.window);
+        if self.key == Some(key) {
+            return;
+        }
+        self.key = Some(key);
+
+        self.speeds = kinematics::compute(points, self.window).iter().map(|k| k.speed()).collect();
+        self.rows = (0..points.len())
+            .filter(|&i| self.filter.is_none_or(|[from, to]| (from..=to).contains(&points[i].time)))
+            .collect();
+
+        let speeds = &self.speeds;
+        let value = |i: usize| -> Option<f64> {
+            let p = &points[i];
+            match self.sort {
+                SortColumn::Index => Some(i as f64),
+                SortColumn::Time => Some(p.time),
+                SortColumn::X => Some(p.pos[0] as f64),
+                SortColumn::Y => Some(p.pos[1] as f64),
+                SortColumn::Angle => p.angle.map(f64::from),
+                SortColumn::Speed => speeds[i],
+            }
+        };
+        // points without a value go last whichever the direction
+        self.rows.sort_by(|&a, &b| match (value(a), value(b)) {
+            (Some(a), Some(b)) if self.descending => b.total_cmp(&a),
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        });
+    }
+
+    /// Makes the next update start over, for a trace swapped for another
+    /// of the same length.
+    pub fn invalidate(&mut self) {
+        self.key = None;
+    }
+
+    /// Indices into the trace, in display order.
+    pub fn rows(&self) -> &[usize] {
+        &self.rows
+    }
+
+    pub fn speed(&self, i: usize) -> Option<f64> {
+        self.speeds.get(i).copied().flatten()
+    }
+}
@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+/// A single detected cursor position together with where it sits in the video.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TracePoint {
+    pub frame: u64,
+    pub time: f64,
+    pub pos: [f32; 2],
+    /// Pointing direction in degrees (0° = right, 90° = down), if known.
+    pub angle: Option<f32>,
+    /// Wall-clock seconds since Magic started, for points Magic sampled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sampled_at: Option<f64>,
+    /// Recording segment the point was taken in, when the trace is split
+    /// into segments.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub segment: Option<u32>,
+}
+
+impl TracePoint {
+    pub fn new(frame: u64, time: f64, pos: [f32; 2]) -> Self {
+        Self { frame, time, pos, angle: None, sampled_at: None, segment: None }
+    }
+
+    pub fn with_angle(mut self, angle: Option<f32>) -> Self {
+        self.angle = angle;
+        self
+    }
+
+    pub fn with_sampled_at(mut self, sampled_at: Option<f64>) -> Self {
+        self.sampled_at = sampled_at;
+        self
+    }
+
+    pub fn with_segment(mut self, segment: Option<u32>) -> Self {
+        self.segment = segment;
+        self
+    }
+}
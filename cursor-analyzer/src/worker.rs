@@ -0,0 +1,1025 @@
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStderr, ChildStdout, Command, Stdio};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{Receiver, Sender};
+use image::RgbaImage;
+use parking_lot::Mutex;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::audio;
+use crate::playlist;
+use crate::detect::{diagnose, estimate_orientation, find_position, smooth, BitDepth, DetectionParams, Rejections, Roi, Sample};
+use crate::trace::TracePoint;
+
+const FIRST_FRAME_ATTEMPTS: usize = 3;
+const STDERR_TAIL_LINES: usize = 8;
+/// How long to wait for ffmpeg to report the size of the frames it will
+/// pipe before trusting the probe.
+const OUTPUT_SIZE_TIMEOUT: Duration = Duration::from_secs(3);
+/// How long probing a file may take before it is given up on, e.g. a
+/// large file on a slow network share.
+pub const DEFAULT_PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+/// How often [`AppEvent::Stats`] is sent while frames are being read.
+const STATS_INTERVAL: Duration = Duration::from_millis(500);
+/// Weight of the newest sample in smoothed timings.
+pub const STATS_SMOOTHING: f64 = 0.1;
+pub const DEFAULT_FPS: f64 = 60.0;
+
+#[derive(Debug, Clone)]
+pub enum AppCommand {
+    LoadFile(PathBuf),
+    Seek(f64), 
+    /// Seek for a look at the frame while scrubbing. It is sent with
+    /// `preview` set, so the UI shows its detection without recording it.
+    Preview(f64),
+    Step,      
+    /// Show the previous frame, from the frame cache while it has it and
+    /// by seeking otherwise.
+    StepBack,
+    /// Move this many seconds of video forward and report only the frame
+    /// landed on.
+    Advance(f64),
+    /// Starts a new epoch: frames read from now on carry it, so the UI can
+    /// drop those still in flight from Steps sent before the pause. If
+    /// frames past `frame`, the one the UI stopped on, were already read,
+    /// it is shown again so the next Step continues from there.
+    Pause { epoch: u64, frame: u64 },
+    SetDetection(DetectionParams),
+    SetScope(ScanScope),
+    /// Only decode between these two times in seconds, clamped to the
+    /// video. Applies to the loaded file and to later `LoadFile`s.
+    SetRange(f64, f64),
+    /// Count where the heuristic rejects candidates on the last frame read,
+    /// under the current settings. Answered with [`AppEvent::Diagnosis`].
+    Diagnose,
+    /// Keep up to this many decoded frames for [`AppCommand::StepBack`],
+    /// 0 to keep none.
+    SetFrameCache(usize),
+    /// Kill the probe of a file opened later if it takes longer than this.
+    SetProbeTimeout(Duration),
+    /// Decode the loaded file's audio on the side, answered with
+    /// [`AppEvent::Waveform`].
+    LoadWaveform,
+    /// Decode this video stream, counted among the file's video streams,
+    /// from the next `LoadFile` on. `None`, or a stream the file doesn't
+    /// have, picks the one ffmpeg would.
+    SetVideoStream(Option<usize>),
+    /// Decode from `start` to `end` seconds again, detecting with the
+    /// current settings, and answer with [`AppEvent::Rescanned`] rather
+    /// than a frame each. The frame shown before is shown again after.
+    Rescan { start: f64, end: f64 },
+}
+
+/// Which frames, and which part of each, the worker runs detection on.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ScanScope {
+    pub roi: Option<Roi>,
+    /// Detect on every n-th frame only; the others are decoded and shown
+    /// but report no position.
+    pub every_n: u64,
+}
+
+impl ScanScope {
+    pub fn scans(&self, frame: u64) -> bool {
+        frame.is_multiple_of(self.every_n.max(1))
+    }
+}
+
+impl Default for ScanScope {
+    fn default() -> Self {
+        Self { roi: None, every_n: 1 }
+    }
+}
+
+#[derive(Debug)]
+pub enum AppEvent {
+    /// Probing a file began; [`AppEvent::Metadata`] or an error follows.
+    LoadStarted { path: PathBuf },
+    FrameReady {
+        image: RgbaImage,
+        width: u32,
+        height: u32,
+        frame: u64,
+        time: f64,
+        position: Option<[f32; 2]>,
+        /// Pointing direction of the detected cursor in degrees, see
+        /// [`estimate_orientation`].
+        angle: Option<f32>,
+        /// Epoch of the last [`AppCommand::Pause`] when the frame was read.
+        epoch: u64,
+        /// Read for [`AppCommand::Preview`].
+        preview: bool,
+    },
+    /// The file's video streams and which one is decoded, sent just
+    /// before [`AppEvent::Metadata`].
+    VideoStreams { streams: Vec<VideoStream>, selected: usize },
+    Metadata {
+        duration: f64,
+        width: u32,
+        height: u32,
+        fps: f64,
+    },
+    Diagnosis {
+        frame: u64,
+        rejections: Rejections,
+    },
+    /// Frames now held for stepping back, sent when it changes.
+    FrameCache { cached: usize },
+    /// Smoothed timings, sent every [`STATS_INTERVAL`] while frames arrive.
+    Stats(WorkerStats),
+    /// A line ffmpeg wrote to stderr while decoding.
+    Log { level: LogLevel, line: String },
+    /// How far a [`AppCommand::Rescan`] has got, from 0 to 1.
+    RescanProgress(f64),
+    /// The detections of a finished [`AppCommand::Rescan`], in frame order.
+    Rescanned(Vec<TracePoint>),
+    /// RMS levels of `path`'s audio, [`audio::WAVEFORM_RATE`] per second.
+    /// Empty if it has no audio track.
+    Waveform { path: PathBuf, levels: Vec<f32> },
+    /// The decoder ran out of frames; sent once per ffmpeg run.
+    EndOfStream,
+    Error(String),
+}
+
+/// A video stream as ffmpeg describes it when probing.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VideoStream {
+    pub codec: String,
+    pub width: u32,
+    pub height: u32,
+    pub fps: f64,
+    /// A cover image rather than video, e.g. an MP4 thumbnail.
+    pub attached_pic: bool,
+}
+
+impl VideoStream {
+    pub fn label(&self) -> String {
+        let kind = if self.attached_pic { ", cover image" } else { "" };
+        format!("{}x{} {} fps, {}{}", self.width, self.height, self.fps, self.codec, kind)
+    }
+}
+
+/// Where the worker's time goes per frame, exponentially smoothed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct WorkerStats {
+    /// Frames read from the pipe per second.
+    pub decode_fps: f64,
+    /// Milliseconds spent waiting for one frame from the pipe.
+    pub read_ms: f64,
+    /// Milliseconds of detection per scanned frame.
+    pub detect_ms: f64,
+}
+
+/// Moves the running average `avg` towards `sample`; the first sample
+/// is taken as is.
+pub fn smooth_stat(avg: &mut f64, sample: f64) {
+    *avg = if *avg == 0.0 { sample } else { *avg + (sample - *avg) * STATS_SMOOTHING };
+}
+
+/// Severity of an ffmpeg log line, from the tag `-loglevel level+` adds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Info,
+    Warning,
+    Error,
+}
+
+impl LogLevel {
+    pub fn label(&self) -> &'static str {
+        match self {
+            LogLevel::Info => "Info",
+            LogLevel::Warning => "Warning",
+            LogLevel::Error => "Error",
+        }
+    }
+
+    /// Takes the level tag out of a line like `[h264 @ 0x..] [error] msg`.
+    /// Untagged lines count as info.
+    fn split(line: &str) -> (Self, String) {
+        let tags = [
+            ("[panic] ", LogLevel::Error),
+            ("[fatal] ", LogLevel::Error),
+            ("[error] ", LogLevel::Error),
+            ("[warning] ", LogLevel::Warning),
+            ("[info] ", LogLevel::Info),
+            ("[verbose] ", LogLevel::Info),
+            ("[debug] ", LogLevel::Info),
+            ("[trace] ", LogLevel::Info),
+        ];
+        // the tag follows the component prefix, if there is one
+        let found = tags.iter()
+            .filter_map(|&(tag, level)| line.find(tag).map(|at| (at, tag, level)))
+            .min_by_key(|&(at, _, _)| at);
+        match found {
+            Some((at, tag, level)) => (level, format!("{}{}", &line[..at], &line[at + tag.len()..])),
+            None => (LogLevel::Info, line.to_string()),
+        }
+    }
+}
+
+pub struct VideoWorker {
+    rx: Receiver<AppCommand>,
+    tx: Sender<AppEvent>,
+    current_process: Option<Child>,
+    current_reader: Option<BufReader<ChildStdout>>,
+    current_file: Option<PathBuf>,
+    stderr_tail: Arc<Mutex<VecDeque<String>>>,
+    /// Frame size of the rawvideo stream, as the running ffmpeg reports it.
+    output_size: Arc<Mutex<Option<(u32, u32)>>>,
+    width: u32,
+    height: u32,
+    duration: f64,
+    fps: f64,
+    next_frame: u64,
+    at_eof: bool,
+    params: DetectionParams,
+    /// Depth the running ffmpeg pipes, which `params.depth` only becomes
+    /// once decoding restarts.
+    depth: BitDepth,
+    scope: ScanScope,
+    /// Requested in and out points, see [`VideoWorker::clamped_range`].
+    range: (f64, f64),
+    /// The last frame shown as piped, kept for [`AppCommand::Diagnose`].
+    last_frame: Option<(u64, Vec<u8>)>,
+    /// Consecutive frames read from the running ffmpeg as piped, newest
+    /// last, so stepping back within them needs no restart.
+    frame_cache: VecDeque<(u64, Vec<u8>)>,
+    cache_capacity: usize,
+    /// How far behind the newest cached frame the shown one is.
+    cache_back: usize,
+    /// Cache occupancy last reported to the UI.
+    cache_reported: usize,
+    /// Tags every frame sent, see [`AppCommand::Pause`].
+    epoch: u64,
+    /// Set while answering [`AppCommand::Preview`].
+    previewing: bool,
+    probe_timeout: Duration,
+    /// Requested with [`AppCommand::SetVideoStream`], and the one decoded.
+    requested_stream: Option<usize>,
+    stream: usize,
+    stats: WorkerStats,
+    /// Smoothed seconds between frame reads, and when the last one ended.
+    read_interval: f64,
+    last_read: Option<Instant>,
+    stats_sent: Instant,
+}
+
+impl VideoWorker {
+    pub fn new(rx: Receiver<AppCommand>, tx: Sender<AppEvent>) -> Self {
+        Self {
+            rx,
+            tx,
+            current_process: None,
+            current_reader: None,
+            current_file: None,
+            stderr_tail: Arc::new(Mutex::new(VecDeque::new())),
+            output_size: Arc::new(Mutex::new(None)),
+            width: 0,
+            height: 0,
+            duration: 0.0,
+            fps: DEFAULT_FPS,
+            next_frame: 0,
+            at_eof: false,
+            params: DetectionParams::default(),
+            depth: BitDepth::Eight,
+            scope: ScanScope::default(),
+            range: (0.0, f64::INFINITY),
+            last_frame: None,
+            frame_cache: VecDeque::new(),
+            cache_capacity: 0,
+            cache_back: 0,
+            cache_reported: 0,
+            epoch: 0,
+            previewing: false,
+            probe_timeout: DEFAULT_PROBE_TIMEOUT,
+            requested_stream: None,
+            stream: 0,
+            stats: WorkerStats::default(),
+            read_interval: 0.0,
+            last_read: None,
+            stats_sent: Instant::now(),
+        }
+    }
+
+    pub fn run(&mut self) {
+        while let Ok(cmd) = self.rx.recv() {
+            match cmd {
+                AppCommand::LoadFile(path) => {
+                    self.load_file(path);
+                },
+                AppCommand::Step => {
+                    if self.cache_back > 0 {
+                        self.show_cached(self.cache_back - 1);
+                    } else if !self.read_next_frame() {
+                        self.end_of_stream();
+                    }
+                },
+                AppCommand::StepBack => {
+                    self.step_back();
+                },
+                AppCommand::Advance(secs) => {
+                    let mut frames = (secs * self.fps).round().max(1.0) as u64;
+                    if self.cache_back > 0 {
+                        // catch up with the pipe through the cache first
+                        let catch_up = frames.min(self.cache_back as u64);
+                        frames -= catch_up;
+                        self.cache_back -= catch_up as usize;
+                        if frames == 0 {
+                            self.show_cached(self.cache_back);
+                            continue;
+                        }
+                    }
+                    if !self.skip_frames(frames - 1) || !self.read_next_frame() {
+                        self.end_of_stream();
+                    }
+                },
+                AppCommand::Seek(t) => {
+                    self.seek(t);
+                },
+                AppCommand::Preview(t) => {
+                    self.previewing = true;
+                    self.seek(t);
+                    self.previewing = false;
+                },
+                AppCommand::Pause { epoch, frame } => {
+                    self.epoch = epoch;
+                    self.rewind_to(frame);
+                },
+                AppCommand::SetDetection(params) => {
+                    let redecode = params.depth != self.depth && self.current_file.is_some();
+                    self.params = params;
+                    if redecode {
+                        // the frames piped so far are at the old depth
+                        self.last_frame = None;
+                        self.seek(self.next_frame.saturating_sub(1 + self.cache_back as u64) as f64 / self.fps);
+                    }
+                },
+                AppCommand::SetScope(scope) => {
+                    self.scope = scope;
+                },
+                AppCommand::SetRange(start, end) => {
+                    self.range = (start, end);
+                    if self.current_file.is_some() {
+                        self.seek(0.0);
+                    }
+                },
+                AppCommand::Diagnose => {
+                    self.diagnose();
+                },
+                AppCommand::SetFrameCache(frames) => {
+                    self.cache_capacity = frames;
+                    // drop the oldest, but keep the one shown
+                    while self.frame_cache.len() > frames.max(self.cache_back + 1) {
+                        self.frame_cache.pop_front();
+                    }
+                    if frames == 0 {
+                        self.frame_cache.clear();
+                        self.cache_back = 0;
+                    }
+                    self.report_cache();
+                },
+                AppCommand::SetProbeTimeout(timeout) => {
+                    self.probe_timeout = timeout;
+                },
+                AppCommand::SetVideoStream(stream) => {
+                    self.requested_stream = stream;
+                },
+                AppCommand::Rescan { start, end } => {
+                    self.rescan(start, end);
+                },
+                AppCommand::LoadWaveform => {
+                    let Some(path) = self.current_file.clone() else { continue };
+                    let tx = self.tx.clone();
+                    // a long track takes a while, frames shouldn't wait for it
+                    thread::spawn(move || {
+                        let levels = audio::extract_waveform(&path).unwrap_or_else(|e| {
+                            let _ = tx.send(AppEvent::Log { level: LogLevel::Error, line: e });
+                            Vec::new()
+                        });
+                        let _ = tx.send(AppEvent::Waveform { path, levels });
+                    });
+                },
+            }
+        }
+    }
+
+    /// The requested range clamped to `[0, duration]`, as (in, out). Out is
+    /// `None` when decoding should run to the end of the file.
+    fn clamped_range(&self) -> (f64, Option<f64>) {
+        let duration = if self.duration > 0.0 { self.duration } else { f64::INFINITY };
+        let start = self.range.0.clamp(0.0, duration);
+        let end = self.range.1.clamp(start, duration);
+        (start, Some(end).filter(|e| *e < duration))
+    }
+
+    fn end_of_stream(&mut self) {
+        if self.at_eof || self.current_file.is_none() {
+            return;
+        }
+        self.at_eof = true;
+        let _ = self.tx.send(AppEvent::EndOfStream);
+    }
+
+    fn load_file(&mut self, path: PathBuf) {
+        self.last_frame = None;
+        let _ = self.tx.send(AppEvent::LoadStarted { path: path.clone() });
+
+        match probe_file(&path, self.probe_timeout, self.requested_stream) {
+            Ok(probe) => {
+                let stream = &probe.streams[probe.stream];
+                let (dur, fps) = (probe.duration, stream.fps);
+                self.duration = dur;
+                self.width = stream.width;
+                self.height = stream.height;
+                self.fps = fps;
+                self.stream = probe.stream;
+                self.current_file = Some(path.clone());
+                let _ = self.tx.send(AppEvent::VideoStreams { streams: probe.streams, selected: probe.stream });
+
+                let (start, _) = self.clamped_range();
+                self.start_ffmpeg(start);
+                self.check_output_size();
+
+                let _ = self.tx.send(AppEvent::Metadata {
+                    duration: dur,
+                    width: self.width,
+                    height: self.height,
+                    fps,
+                });
+
+                for attempt in 1..=FIRST_FRAME_ATTEMPTS {
+                    if attempt > 1 {
+                        self.start_ffmpeg(start);
+                    }
+                    if self.read_next_frame() {
+                        return;
+                    }
+                    log::warn!("No first frame on attempt {}/{}", attempt, FIRST_FRAME_ATTEMPTS);
+                }
+
+                let diagnostics = self.ffmpeg_diagnostics();
+                let _ = self.tx.send(AppEvent::Error(format!(
+                    "Could not decode the first frame of {} after {} attempts ({})",
+                    path.display(), FIRST_FRAME_ATTEMPTS, diagnostics,
+                )));
+            },
+            Err(e) => {
+                let _ = self.tx.send(AppEvent::Error(e));
+            }
+        }
+    }
+
+    /// Starts decoding at `start_time`, which must already be inside the
+    /// range; the out point is passed as `-to` so EOF lands there.
+    fn start_ffmpeg(&mut self, start_time: f64) {
+        if let Some(mut child) = self.current_process.take() {
+             let _ = child.kill();
+             let _ = child.wait();
+        }
+        self.current_reader = None;
+        self.stderr_tail.lock().clear();
+        *self.output_size.lock() = None;
+        self.depth = self.params.depth;
+        self.next_frame = (start_time * self.fps).round() as u64;
+        self.at_eof = false;
+        // the cache only holds frames read from one run, in order
+        self.frame_cache.clear();
+        self.cache_back = 0;
+
+        if let Some(path) = &self.current_file {
+            let binary = if cfg!(windows) { "ffmpeg" } else { "./ffmpeg" };
+            let mut cmd = Command::new(binary);
+            cmd.args(["-loglevel", "level+info"]);
+            // as an OsStr, so paths that aren't valid UTF-8 still open
+            playlist::input(&mut cmd, path);
+            cmd.arg("-map").arg(format!("0:v:{}", self.stream));
+
+            if start_time > 0.0 {
+                cmd.arg("-ss").arg(format!("{}", start_time));
+            }
+            if let (_, Some(end)) = self.clamped_range() {
+                cmd.arg("-to").arg(format!("{}", end));
+            }
+
+            cmd.args([
+                "-f", "image2pipe",
+                "-pix_fmt", self.depth.pix_fmt(),
+                "-vcodec", "rawvideo",
+                "-"
+            ]);
+            cmd.stdout(Stdio::piped());
+            cmd.stderr(Stdio::piped());
+
+            match cmd.spawn() {
+                Ok(mut child) => {
+                    if let Some(stderr) = child.stderr.take() {
+                        let tail = self.stderr_tail.clone();
+                        let output_size = self.output_size.clone();
+                        let tx = self.tx.clone();
+                        thread::spawn(move || drain_stderr(stderr, tail, output_size, tx));
+                    }
+                    if let Some(stdout) = child.stdout.take() {
+                        self.current_reader = Some(BufReader::new(stdout));
+                        self.current_process = Some(child);
+                    }
+                },
+                Err(e) => {
+                     let _ = self.tx.send(AppEvent::Error(format!("Cannot start {} for {}: {}", binary, path.display(), e)));
+                }
+            }
+        }
+    }
+
+    fn frame_bytes(&self) -> usize {
+        self.width as usize * self.height as usize * self.depth.bytes_per_pixel()
+    }
+
+    /// What detection runs on: the frame as decoded, or smoothed if the
+    /// parameters ask for a blur.
+    fn detection_input<'a, S: Sample>(&self, data: &'a [S]) -> Cow<'a, [S]> {
+        if self.params.blur_radius > 0 {
+            let (w, h) = (self.width as usize, self.height as usize);
+            Cow::Owned(smooth(data, w, h, self.params.blur_radius as usize, self.params.blur_strength))
+        } else {
+            Cow::Borrowed(data)
+        }
+    }
+
+    /// Detection on one piped frame, at the depth it was piped.
+    fn detect(&self, data: &[u8]) -> (Option<[f32; 2]>, Option<f32>) {
+        match self.depth {
+            BitDepth::Eight => self.detect_samples(data),
+            BitDepth::Sixteen => self.detect_samples(&samples16(data)),
+        }
+    }
+
+    fn detect_samples<S: Sample>(&self, data: &[S]) -> (Option<[f32; 2]>, Option<f32>) {
+        let (w, h) = (self.width as usize, self.height as usize);
+        // the blur only feeds detection, the frame is shown as decoded
+        let input = self.detection_input(data);
+        let pos = find_position(&input, w, h, &self.params, self.scope.roi.as_ref());
+        let angle = pos.and_then(|p| estimate_orientation(&input, w, h, p, &self.params));
+        (pos, angle)
+    }
+
+    fn diagnose(&mut self) {
+        let Some((frame, data)) = &self.last_frame else { return };
+        if data.len() != self.frame_bytes() {
+            return;
+        }
+        let (w, h) = (self.width as usize, self.height as usize);
+        let (params, roi) = (&self.params, self.scope.roi.as_ref());
+        let rejections = match self.depth {
+            BitDepth::Eight => diagnose(&self.detection_input(data), w, h, params, roi),
+            BitDepth::Sixteen => diagnose(&self.detection_input(&samples16(data)), w, h, params, roi),
+        };
+        let _ = self.tx.send(AppEvent::Diagnosis { frame: *frame, rejections });
+    }
+
+    /// Compares the probed frame size with what the freshly started ffmpeg
+    /// says it will pipe, before any frame is read. If they differ (e.g.
+    /// rotation metadata the probe didn't account for), reading with the
+    /// probed size would misalign every frame, so the reported size wins.
+    fn check_output_size(&mut self) {
+        let deadline = Instant::now() + OUTPUT_SIZE_TIMEOUT;
+        let reported = loop {
+            if let Some(size) = *self.output_size.lock() {
+                break Some(size);
+            }
+            let exited = self.current_process.as_mut().is_none_or(|c| !matches!(c.try_wait(), Ok(None)));
+            if exited || Instant::now() >= deadline {
+                break None;
+            }
+            thread::sleep(Duration::from_millis(5));
+        };
+
+        match reported {
+            Some((w, h)) if (w, h) != (self.width, self.height) => {
+                log::warn!(
+                    "ffmpeg pipes {}x{} frames ({} bytes each) but the probe reported {}x{} ({} bytes); using the piped size",
+                    w, h, w as usize * h as usize * self.depth.bytes_per_pixel(),
+                    self.width, self.height, self.frame_bytes(),
+                );
+                self.width = w;
+                self.height = h;
+            }
+            Some(_) => {}
+            None => log::warn!(
+                "ffmpeg did not report its output frame size; assuming the probed {}x{}",
+                self.width, self.height,
+            ),
+        }
+    }
+
+    fn seek(&mut self, time: f64) {
+        let (start, end) = self.clamped_range();
+        self.start_ffmpeg(time.clamp(start, end.unwrap_or(f64::INFINITY)));
+        let _ = self.read_next_frame();
+    }
+
+    /// Collects the exit status and the last stderr lines of the current
+    /// ffmpeg process, killing it if it is somehow still running.
+    fn ffmpeg_diagnostics(&mut self) -> String {
+        self.current_reader = None;
+        let status = match self.current_process.take() {
+            Some(mut child) => match child.try_wait() {
+                Ok(Some(status)) => status.to_string(),
+                _ => {
+                    let _ = child.kill();
+                    match child.wait() {
+                        Ok(status) => format!("killed, {}", status),
+                        Err(e) => format!("unknown status: {}", e),
+                    }
+                }
+            },
+            None => "not running".to_string(),
+        };
+
+        let tail = self.stderr_tail.lock();
+        if tail.is_empty() {
+            format!("ffmpeg {}", status)
+        } else {
+            let lines: Vec<&str> = tail.iter().map(|l| l.as_str()).collect();
+            format!("ffmpeg {}: {}", status, lines.join(" | "))
+        }
+    }
+
+    /// Reads and discards `count` frames without running detection.
+    fn skip_frames(&mut self, count: u64) -> bool {
+        if count > 0 {
+            // skipped frames would leave a hole in it
+            self.frame_cache.clear();
+            self.report_cache();
+        }
+        let mut buffer = vec![0u8; self.frame_bytes()];
+        let Some(reader) = &mut self.current_reader else { return false };
+        for _ in 0..count {
+            if reader.read_exact(&mut buffer).is_err() {
+                return false;
+            }
+            self.next_frame += 1;
+        }
+        true
+    }
+
+    /// Reads the next frame from the pipe into the cache, without showing it.
+    fn read_raw(&mut self) -> Option<(u64, Vec<u8>)> {
+        if self.width == 0 || self.height == 0 { return None; }
+
+        let mut buffer = vec![0u8; self.frame_bytes()];
+        let reader = self.current_reader.as_mut()?;
+        let started = Instant::now();
+        reader.read_exact(&mut buffer).ok()?;
+        smooth_stat(&mut self.stats.read_ms, started.elapsed().as_secs_f64() * 1000.0);
+        if let Some(last) = self.last_read {
+            smooth_stat(&mut self.read_interval, last.elapsed().as_secs_f64());
+            self.stats.decode_fps = if self.read_interval > 0.0 { 1.0 / self.read_interval } else { 0.0 };
+        }
+        self.last_read = Some(Instant::now());
+        let frame = self.next_frame;
+        self.next_frame += 1;
+
+        if self.cache_capacity > 0 {
+            // reusing the oldest allocation once full
+            let mut slot = match self.frame_cache.len() >= self.cache_capacity {
+                true => self.frame_cache.pop_front().map(|(_, data)| data).unwrap_or_default(),
+                false => Vec::new(),
+            };
+            slot.clone_from(&buffer);
+            self.frame_cache.push_back((frame, slot));
+        }
+        Some((frame, buffer))
+    }
+
+    fn read_next_frame(&mut self) -> bool {
+        match self.read_raw() {
+            Some((frame, buffer)) => self.show(frame, buffer),
+            None => false,
+        }
+    }
+
+    /// Shows the cached frame `back` frames behind the newest.
+    fn show_cached(&mut self, back: usize) -> bool {
+        let Some(i) = self.frame_cache.len().checked_sub(back + 1) else { return false };
+        let (frame, data) = self.frame_cache[i].clone();
+        self.cache_back = back;
+        self.show(frame, data)
+    }
+
+    /// Shows `frame` again if frames after it were read since, from the
+    /// cache while it still holds it and by seeking otherwise.
+    fn rewind_to(&mut self, frame: u64) {
+        let shown = self.next_frame.saturating_sub(1 + self.cache_back as u64);
+        if self.current_file.is_none() || shown <= frame {
+            return;
+        }
+        match self.frame_cache.iter().rposition(|(f, _)| *f == frame) {
+            Some(i) => {
+                self.show_cached(self.frame_cache.len() - 1 - i);
+            }
+            None => self.seek(frame as f64 / self.fps),
+        }
+    }
+
+    fn rescan(&mut self, start: f64, end: f64) {
+        if self.current_file.is_none() {
+            return;
+        }
+        let shown = self.next_frame.saturating_sub(1 + self.cache_back as u64);
+        let (first, last) = self.clamped_range();
+        self.start_ffmpeg(start.clamp(first, last.unwrap_or(f64::INFINITY)));
+        let from = self.next_frame;
+        let to = (end * self.fps).round() as u64;
+
+        let mut points = Vec::new();
+        let mut reported = Instant::now();
+        while let Some((frame, buffer)) = self.read_raw() {
+            if self.scope.scans(frame) {
+                if let (Some(pos), angle) = self.detect(&buffer) {
+                    points.push(TracePoint::new(frame, frame as f64 / self.fps, pos).with_angle(angle));
+                }
+            }
+            if frame >= to {
+                break;
+            }
+            if reported.elapsed() >= STATS_INTERVAL {
+                reported = Instant::now();
+                let done = (frame - from) as f64 / to.saturating_sub(from).max(1) as f64;
+                let _ = self.tx.send(AppEvent::RescanProgress(done));
+            }
+        }
+        let _ = self.tx.send(AppEvent::Rescanned(points));
+        self.seek(shown as f64 / self.fps);
+    }
+
+    /// Shows the frame before the current one. Within the cache that is
+    /// instant; past it, decoding restarts far enough back to fill the
+    /// cache up to the frame wanted.
+    fn step_back(&mut self) {
+        if self.cache_back + 1 < self.frame_cache.len() {
+            self.show_cached(self.cache_back + 1);
+            return;
+        }
+        let shown = self.next_frame.saturating_sub(1 + self.cache_back as u64);
+        let first = (self.clamped_range().0 * self.fps).round() as u64;
+        if shown <= first || self.current_file.is_none() {
+            return;
+        }
+        let target = shown - 1;
+        let from = target.saturating_sub(self.cache_capacity.saturating_sub(1) as u64).max(first);
+        self.start_ffmpeg(from as f64 / self.fps);
+        while let Some((frame, buffer)) = self.read_raw() {
+            if frame >= target {
+                self.show(frame, buffer);
+                return;
+            }
+        }
+        self.end_of_stream();
+    }
+
+    /// Runs detection on a frame as piped and sends it to the UI.
+    fn show(&mut self, frame: u64, buffer: Vec<u8>) -> bool {
+        let (pos, angle) = if self.scope.scans(frame) {
+            let started = Instant::now();
+            let found = self.detect(&buffer);
+            smooth_stat(&mut self.stats.detect_ms, started.elapsed().as_secs_f64() * 1000.0);
+            found
+        } else {
+            (None, None)
+        };
+
+        // reusing the allocation, this is one copy per frame
+        let last = self.last_frame.get_or_insert_with(|| (frame, Vec::new()));
+        last.0 = frame;
+        last.1.clone_from(&buffer);
+
+        let rgba = match self.depth {
+            BitDepth::Eight => buffer,
+            BitDepth::Sixteen => rgba8(&buffer),
+        };
+        let Some(img) = RgbaImage::from_raw(self.width, self.height, rgba) else { return false };
+        let _ = self.tx.send(AppEvent::FrameReady {
+            image: img,
+            width: self.width,
+            height: self.height,
+            frame,
+            time: frame as f64 / self.fps,
+            position: pos,
+            angle,
+            epoch: self.epoch,
+            preview: self.previewing,
+        });
+        self.report_cache();
+        if self.stats_sent.elapsed() >= STATS_INTERVAL {
+            self.stats_sent = Instant::now();
+            let _ = self.tx.send(AppEvent::Stats(self.stats));
+        }
+        true
+    }
+
+    fn report_cache(&mut self) {
+        if self.frame_cache.len() != self.cache_reported {
+            self.cache_reported = self.frame_cache.len();
+            let _ = self.tx.send(AppEvent::FrameCache { cached: self.cache_reported });
+        }
+    }
+}
+
+/// The samples of a frame piped as `rgba64le`.
+fn samples16(data: &[u8]) -> Vec<u16> {
+    data.chunks_exact(2).map(|b| u16::from_le_bytes([b[0], b[1]])).collect()
+}
+
+/// A frame piped as `rgba64le` rounded to 8-bit RGBA for display.
+fn rgba8(data: &[u8]) -> Vec<u8> {
+    data.chunks_exact(2).map(|b| ((u16::from_le_bytes([b[0], b[1]]) as u32 + 128) / 257) as u8).collect()
+}
+
+/// Keeps the last lines for error messages, watches for the output size
+/// and forwards every line to the UI.
+fn drain_stderr(stderr: ChildStderr, tail: Arc<Mutex<VecDeque<String>>>, output_size: Arc<Mutex<Option<(u32, u32)>>>, tx: Sender<AppEvent>) {
+    let reader = BufReader::new(stderr);
+    let size_regex = Regex::new(r"Video:.* (\d+)x(\d+)").unwrap();
+    let mut in_output = false;
+    for chunk in reader.split(b'\n').map_while(Result::ok) {
+        // progress updates are separated by '\r', keep only the latest one
+        let text = String::from_utf8_lossy(&chunk);
+        let line = text.rsplit('\r').find(|l| !l.trim().is_empty()).unwrap_or("").trim();
+        if line.is_empty() { continue; }
+        let (level, line) = LogLevel::split(line);
+        let line = line.as_str();
+
+        // the first video stream listed under "Output #0" is what we read
+        if line.starts_with("Output #") {
+            in_output = true;
+        } else if in_output {
+            if let Some(caps) = size_regex.captures(line) {
+                if let (Ok(w), Ok(h)) = (caps[1].parse(), caps[2].parse()) {
+                    *output_size.lock() = Some((w, h));
+                }
+                in_output = false;
+            }
+        }
+
+        {
+            let mut tail = tail.lock();
+            if tail.len() == STDERR_TAIL_LINES {
+                tail.pop_front();
+            }
+            tail.push_back(line.to_string());
+        }
+        let _ = tx.send(AppEvent::Log { level, line: line.to_string() });
+    }
+}
+
+/// What probing a file found out.
+struct Probe {
+    duration: f64,
+    streams: Vec<VideoStream>,
+    /// Index into `streams` of the one to decode.
+    stream: usize,
+}
+
+/// Parses the video streams from ffmpeg's description of its input, in
+/// the order `-map 0:v:N` counts them.
+fn video_streams(stderr: &str) -> Vec<VideoStream> {
+    let stream_regex = Regex::new(r"Stream #\d+:\d+\S*: Video: (.*)").unwrap();
+    let res_regex = Regex::new(r" (\d{2,})x(\d{2,})").unwrap();
+    let fps_regex = Regex::new(r" (\d+(?:\.\d+)?) fps").unwrap();
+    stderr.lines()
+        .filter_map(|line| {
+            let caps = stream_regex.captures(line)?;
+            let description = caps.get(1)?.as_str();
+            let (width, height) = res_regex.captures(description)
+                .map(|c| (c[1].parse().unwrap_or(0), c[2].parse().unwrap_or(0)))
+                .unwrap_or((0, 0));
+            let fps = fps_regex.captures(description)
+                .and_then(|c| c[1].parse::<f64>().ok())
+                .filter(|f| *f > 0.0)
+                .unwrap_or(DEFAULT_FPS);
+            Some(VideoStream {
+                codec: description.split([' ', ',']).next().unwrap_or_default().to_string(),
+                width,
+                height,
+                fps,
+                attached_pic: line.contains("(attached pic)"),
+            })
+        })
+        .collect()
+}
+
+/// The stream ffmpeg decodes by default: the largest, passing over cover
+/// images, the first of equals.
+fn default_stream(streams: &[VideoStream]) -> Option<usize> {
+    (0..streams.len())
+        .filter(|&i| streams[i].width > 0 && streams[i].height > 0)
+        .max_by_key(|&i| (!streams[i].attached_pic, streams[i].width as u64 * streams[i].height as u64, std::cmp::Reverse(i)))
+}
+
+/// Probes a video, or each clip of a concat playlist: the playlist plays
+/// as one video as long as the decoded stream is the same size in every
+/// clip, and lasts as long as its clips together.
+fn probe_file(path: &Path, timeout: Duration, requested: Option<usize>) -> Result<Probe, String> {
+    if !playlist::is_playlist(path) {
+        return probe_video(path, timeout, requested);
+    }
+    let clips = playlist::read(path)?;
+    let first = probe_video(&clips[0].path, timeout, requested)?;
+    let size = |probe: &Probe, stream: usize| probe.streams.get(stream).map(|s| (s.width, s.height));
+    let expected = size(&first, first.stream);
+    let mut duration = 0.0;
+    for (i, clip) in clips.iter().enumerate() {
+        let probe = if i == 0 { None } else { Some(probe_video(&clip.path, timeout, requested)?) };
+        let probe = probe.as_ref().unwrap_or(&first);
+        let found = size(probe, first.stream);
+        if found != expected {
+            let shown = |s: Option<(u32, u32)>| s.map_or("without that stream".to_string(), |(w, h)| format!("{}x{}", w, h));
+            return Err(format!(
+                "Clips in {} differ in size: {} is {}, {} is {}",
+                path.display(), clips[0].path.display(), shown(expected), clip.path.display(), shown(found),
+            ));
+        }
+        let end = clip.outpoint.unwrap_or(probe.duration).min(probe.duration);
+        duration += (end - clip.inpoint.unwrap_or(0.0)).max(0.0);
+    }
+    Ok(Probe { duration, ..first })
+}
+
+/// Runs `ffmpeg -i` on `path` and reads duration, size and frame rate from
+/// what it prints, killing it if it takes longer than `timeout`.
+fn probe_video(path: &Path, timeout: Duration, requested: Option<usize>) -> Result<Probe, String> {
+    let binary = if cfg!(windows) { "ffmpeg" } else { "./ffmpeg" };
+    let mut child = Command::new(binary)
+        .arg("-i")
+        .arg(path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Cannot run ffmpeg on {}: {}", path.display(), e))?;
+
+    // read on the side, so ffmpeg never blocks on a full pipe while we wait
+    let mut pipe = child.stderr.take().expect("stderr is piped");
+    let reader = thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = pipe.read_to_end(&mut output);
+        output
+    });
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(20)),
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("Probing {} timed out after {:.0} s", path.display(), timeout.as_secs_f64()));
+            }
+            Err(e) => return Err(format!("Cannot run ffmpeg on {}: {}", path.display(), e)),
+        }
+    }
+    let output = reader.join().unwrap_or_default();
+
+    let stderr = String::from_utf8_lossy(&output);
+
+    let dur_regex = Regex::new(r"Duration: (\d{2}):(\d{2}):(\d{2}\.\d+)").unwrap();
+    let mut duration = 0.0;
+    if let Some(caps) = dur_regex.captures(&stderr) {
+        let h: f64 = caps[1].parse().unwrap_or(0.0);
+        let m: f64 = caps[2].parse().unwrap_or(0.0);
+        let s: f64 = caps[3].parse().unwrap_or(0.0);
+        duration = h * 3600.0 + m * 60.0 + s;
+    }
+
+    let streams = video_streams(&stderr);
+    let stream = requested
+        .filter(|&i| streams.get(i).is_some_and(|s| s.width > 0 && s.height > 0))
+        .or_else(|| default_stream(&streams));
+
+    if let Some(stream) = stream {
+        Ok(Probe { duration, streams, stream })
+    } else {
+        // ffmpeg always ends with a complaint about the missing output file,
+        // the line before it says why, e.g. "Invalid data found when processing input"
+        let reason = stderr.lines()
+            .map(str::trim)
+            .rfind(|l| !l.is_empty() && !l.starts_with("At least one output file"))
+            .unwrap_or("no output from ffmpeg");
+        let shown = path.display().to_string();
+        let reason = reason.strip_prefix(shown.as_str()).and_then(|r| r.strip_prefix(": ")).unwrap_or(reason);
+        Err(format!("Could not parse video metadata from {}: {}", shown, reason))
+    }
+}
+
+pub fn video_worker(rx: Receiver<AppCommand>, tx: Sender<AppEvent>) {
+    let mut worker = VideoWorker::new(rx, tx);
+    worker.run();
+}
+